@@ -0,0 +1,108 @@
+use std::error::Error;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::digest::{digest, SHA256};
+
+use crate::{
+    db::{from_bytes, sled_db, to_bytes, DB},
+    index::Index,
+    models::internal_models::internal_image::InternalImage,
+    renditions::Renditions,
+};
+
+//the renditions of every image, keyed by the hash of their content
+const BLOBS: &str = "blobs";
+//every image record showing a blob, under its hash
+pub const IMAGES_BY_BLOB: Index = Index::multi("blobs.images");
+
+/// What's stored under a hash. Identical uploads re-encode to identical renditions,
+/// so they share one of these however many image records point at it.
+#[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(compare(PartialEq), check_bytes)]
+struct Blob {
+    //refreshed on every put, a blob is never collected right after being handed out
+    stored_at: i64,
+    renditions: Renditions,
+}
+
+pub fn content_hash(renditions: &Renditions) -> String {
+    URL_SAFE_NO_PAD.encode(digest(&SHA256, to_bytes(renditions).as_slice()))
+}
+
+/// How much a pass of `DB::collect_blobs` removed.
+#[derive(Debug, Default, PartialEq)]
+pub struct CollectedBlobs {
+    pub blobs: usize,
+    pub bytes: u64,
+}
+
+impl DB {
+    /// Stores the renditions under their hash and returns it.
+    pub fn put_blob(&self, renditions: &Renditions) -> Result<String, Box<dyn Error>> {
+        let hash = content_hash(renditions);
+        let blob = Blob {
+            stored_at: chrono::Utc::now().timestamp(),
+            renditions: renditions.clone(),
+        };
+        //written even when it's there already, a collection racing this then fails its swap
+        sled_db(&self.store)
+            .open_tree(BLOBS)?
+            .insert(hash.as_bytes(), to_bytes(&blob).as_slice())?;
+        Ok(hash)
+    }
+
+    pub fn read_blob(&self, hash: &str) -> Result<Option<Renditions>, Box<dyn Error>> {
+        match sled_db(&self.store)
+            .open_tree(BLOBS)?
+            .get(hash.as_bytes())?
+        {
+            Some(bytes) => Ok(Some(from_bytes::<Blob>(&bytes)?.renditions)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn blob_exists(&self, hash: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(sled_db(&self.store)
+            .open_tree(BLOBS)?
+            .contains_key(hash.as_bytes())?)
+    }
+
+    /// Removes every blob stored before `cutoff` that no image record points at anymore.
+    pub fn collect_blobs(&self, cutoff: i64) -> Result<CollectedBlobs, Box<dyn Error>> {
+        let tree = sled_db(&self.store).open_tree(BLOBS)?;
+        let mut collected = CollectedBlobs::default();
+        for entry in tree.iter() {
+            let (key, bytes) = entry?;
+            if from_bytes::<Blob>(&bytes)?.stored_at >= cutoff {
+                continue;
+            }
+            let hash = String::from_utf8(key.to_vec())?;
+            if self.blob_is_shown(&hash)? {
+                continue;
+            }
+            //only goes if nobody put it again since it was read
+            if tree
+                .compare_and_swap(&key, Some(&bytes), None::<&[u8]>)?
+                .is_ok()
+            {
+                collected.blobs += 1;
+                collected.bytes += bytes.len() as u64;
+            }
+        }
+        Ok(collected)
+    }
+
+    //drops the entries left by image records deleted before their keys were tracked
+    fn blob_is_shown(&self, hash: &str) -> Result<bool, Box<dyn Error>> {
+        let images = IMAGES_BY_BLOB.prefix::<InternalImage>(self, &format!("{}/", hash))?;
+        let mut shown = false;
+        for image in images {
+            if image.exists(self)? {
+                shown = true;
+            } else {
+                self.delete_index(IMAGES_BY_BLOB.name, &IMAGES_BY_BLOB.key(hash, &image))?;
+            }
+        }
+        Ok(shown)
+    }
+}
@@ -8,10 +8,14 @@ use super::{
     send_and_respond_to_chats::send_and_respond_to_chats,
 };
 
-pub fn init_bots(db: &DB, host: &str) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+//uuid and jwt for each bot, plus the refresh tokens in the same order
+pub type BotLogins = (Vec<(String, String)>, Vec<String>);
+
+pub fn init_bots(db: &DB, host: &str) -> Result<BotLogins, Box<dyn std::error::Error>> {
     log::info!("Initializing bots");
     let backend_url = format!("http://{}:8080", host);
     let mut uuids_jwts = vec![];
+    let mut refresh_tokens = vec![];
     let mut i = 0;
     for user in db.iter_obj::<InternalUser>()? {
         if i % 10 == 0 {
@@ -35,12 +39,40 @@ pub fn init_bots(db: &DB, host: &str) -> Result<Vec<(String, String)>, Box<dyn s
         let login_res = login_res.text()?;
         let login_res: serde_json::Value = serde_json::from_str(&login_res)?;
         let jwt = login_res["jwt"].as_str().unwrap();
+        let refresh_token = login_res["refresh_token"].as_str().unwrap();
         let uuid = user.uuid.id.to_string();
 
         uuids_jwts.push((uuid, jwt.to_string()));
+        refresh_tokens.push(refresh_token.to_string());
     }
 
-    Ok(uuids_jwts)
+    Ok((uuids_jwts, refresh_tokens))
+}
+
+//swaps every bot's tokens for fresh ones before the access tokens run out
+pub fn refresh_bots(
+    client: &reqwest::blocking::Client,
+    host: &str,
+    logins: &mut BotLogins,
+) -> Result<(), Box<dyn std::error::Error>> {
+    log::info!("Refreshing bot tokens");
+    let refresh_url = format!("http://{}:8080/refresh", host);
+    let (uuids_jwts, refresh_tokens) = logins;
+    for (uuid_jwt, refresh_token) in uuids_jwts.iter_mut().zip(refresh_tokens.iter_mut()) {
+        let refresh_body = serde_json::json!({ "refresh_token": refresh_token }).to_string();
+        let refresh_res = client
+            .post(&refresh_url)
+            .body(refresh_body)
+            .header("Content-Type", "application/json")
+            .send()?;
+        if !refresh_res.status().is_success() {
+            return Err(format!("Error refreshing bot {}: {:#?}", uuid_jwt.0, refresh_res).into());
+        }
+        let refresh_res: serde_json::Value = serde_json::from_str(&refresh_res.text()?)?;
+        uuid_jwt.1 = refresh_res["jwt"].as_str().unwrap().to_string();
+        *refresh_token = refresh_res["refresh_token"].as_str().unwrap().to_string();
+    }
+    Ok(())
 }
 
 pub fn post_with_jwt(
@@ -1,8 +1,9 @@
-use crate::bots::bot_actions::{init_bots, run_all_bot_actions};
+use crate::bots::bot_actions::{init_bots, refresh_bots, run_all_bot_actions};
 use crate::db::DB;
+use crate::middleware::jwt::ACCESS_TOKEN_MINUTES;
 use log;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{array, sync::Arc, thread};
 
 const BOT_ACTION_DELAY: u64 = 1;
@@ -17,8 +18,8 @@ pub fn start_bot_manager(
         log::info!("Waiting 1 second before starting bots");
         thread::sleep(Duration::from_secs(1));
 
-        let uuid_jwt = match init_bots(&db, &host) {
-            Ok(uuids) => uuids,
+        let mut logins = match init_bots(&db, &host) {
+            Ok(logins) => logins,
             Err(e) => {
                 log::error!("Failed to initialize bots: {:?}", e);
                 return;
@@ -28,7 +29,18 @@ pub fn start_bot_manager(
         let clients: [Arc<reqwest::blocking::Client>; NUM_BOT_THREADS] =
             array::from_fn(|_| Arc::new(reqwest::blocking::Client::new()));
 
+        //refresh halfway through the access token's life, a round can take a while
+        let refresh_every = Duration::from_secs(ACCESS_TOKEN_MINUTES as u64 * 60 / 2);
+        let mut logged_in_at = Instant::now();
+
         while running.load(Ordering::SeqCst) {
+            if logged_in_at.elapsed() > refresh_every {
+                match refresh_bots(&clients[0], &host, &mut logins) {
+                    Ok(_) => logged_in_at = Instant::now(),
+                    Err(e) => log::error!("Failed to refresh bots: {:?}", e),
+                }
+            }
+            let uuid_jwt = &logins.0;
             log::info!("Running bots");
             let num_threads = 10;
             let total = uuid_jwt.len();
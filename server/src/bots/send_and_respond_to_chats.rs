@@ -7,7 +7,7 @@ use crate::{
 };
 
 use super::bot_actions::post_with_jwt;
-//TODO: jwt refresh
+
 pub fn send_and_respond_to_chats(
     client: &reqwest::blocking::Client,
     db: &DB,
@@ -18,11 +18,14 @@ use std::{
 };
 
 use crate::models::internal_models::{
-    internal_prefs_config::PREFS_CARDINALITY,
+    internal_prefs::search_bbox,
+    internal_prefs_config::{prefs_schema, PREFS_CARDINALITY},
     internal_user::InternalUser,
-    shared::{GetBbox, GetVector, Insertable, InternalUuid},
+    shared::{GetVector, Insertable, InternalUuid},
 };
-use crate::vec::search_linear::LinearSearch;
+use crate::push::PushHub;
+use crate::vec::persist::PersistentVecIndex;
+use crate::vec::search_backend::{VecBackend, VecIndex};
 use crate::vec::shared::VectorSearch;
 
 pub const SCRATCH_SPACE_SIZE: usize = 8192;
@@ -33,38 +36,85 @@ pub struct PubStore {
     pub db: sled::Db,
 }
 
-pub fn flush(store: &Store) -> Result<usize, sled::Error> {
+pub fn sled_db(store: &Store) -> &sled::Db {
     let public_struct = unsafe { mem::transmute::<_, &PubStore>(store) };
-    public_struct.db.flush()
+    &public_struct.db
+}
+
+pub fn flush(store: &Store) -> Result<usize, sled::Error> {
+    sled_db(store).flush()
+}
+
+pub fn to_bytes<T>(object: &T) -> AlignedVec
+where
+    T: Serialize<
+        CompositeSerializer<
+            AlignedSerializer<AlignedVec>,
+            FallbackScratch<HeapScratch<SCRATCH_SPACE_SIZE>, AllocScratch>,
+            SharedSerializeMap,
+        >,
+    >,
+{
+    let mut serializer = DefaultSerializer::default();
+    serializer.serialize_value(object).unwrap();
+    serializer.into_serializer().into_inner()
+}
+
+pub fn from_bytes<T>(bytes: &[u8]) -> Result<T, Box<dyn std::error::Error>>
+where
+    T: Archive,
+    for<'a> T::Archived: rkyv::CheckBytes<DefaultValidator<'a>> + Deserialize<T, Infallible>,
+{
+    let archived = rkyv::check_archived_root::<T>(bytes)?;
+    let deserialized: T = archived.deserialize(&mut Infallible)?;
+    Ok(deserialized)
 }
 
 pub struct DB {
     pub store: Store,
-    pub vec_index: Arc<Mutex<LinearSearch<PREFS_CARDINALITY>>>,
+    pub vec_index: Arc<Mutex<PersistentVecIndex<PREFS_CARDINALITY>>>,
     pub path: String,
+    //open sockets, see `PushHub`
+    pub push: PushHub,
 }
 
 impl DB {
     pub fn new(path: &str) -> Result<Self, kv::Error> {
-        log::info!("Opening database");
+        Self::new_with_backend(path, VecBackend::default())
+    }
+
+    pub fn new_with_backend(path: &str, backend: VecBackend) -> Result<Self, kv::Error> {
+        log::info!("Opening database with {:?} vector search", backend);
         let db_path = "db/".to_owned() + path;
 
         let cfg = Config::new(db_path.clone() + "/kv");
         let store = Store::new(cfg)?;
 
-        let vector_search = LinearSearch::new();
+        let vector_search = PersistentVecIndex::in_memory(VecIndex::with_backend(backend));
         let db = DB {
             store,
             vec_index: Arc::new(Mutex::new(vector_search)),
             path: db_path,
+            push: PushHub::default(),
         };
 
+        let user_version = db.get_version::<InternalUser>()?;
+        let schema = prefs_schema().fingerprint();
+        match PersistentVecIndex::load(Path::new(&db.path), user_version, schema, backend) {
+            Ok(Some(vec_index)) => {
+                *db.vec_index.lock().unwrap() = vec_index;
+                return Ok(db);
+            }
+            Ok(None) => log::info!("Rebuilding vec index from users"),
+            Err(e) => log::error!("Failed to load vec index snapshot, rebuilding {:?}", e),
+        }
+
         let users = db.iter_obj::<InternalUser>()?;
 
         for user in users {
             let user = user.unwrap();
             let mut vec_index = db.vec_index.lock().unwrap();
-            let bbox = user.prefs.get_bbox();
+            let bbox = search_bbox(&user.prefs, &user.props);
             let vec = user.props.get_vector();
             if user.published {
                 vec_index.add(&vec, &user.uuid.id);
@@ -74,6 +124,16 @@ impl DB {
             }
         }
 
+        {
+            let mut vec_index = db.vec_index.lock().unwrap();
+            if let Err(e) = vec_index
+                .attach(Path::new(&db.path))
+                .and_then(|_| vec_index.snapshot(user_version, schema))
+            {
+                log::error!("Failed to snapshot rebuilt vec index {:?}", e);
+            }
+        }
+
         Ok(db)
     }
 
@@ -84,8 +144,30 @@ impl DB {
         std::fs::remove_dir_all("db/".to_owned() + path).unwrap();
     }
 
+    /// Flushes sled and syncs the vec index journal, so both survive a crash.
     pub fn flush(&self) -> Result<usize, sled::Error> {
-        flush(&self.store)
+        let flushed = flush(&self.store)?;
+        let journal = self
+            .vec_index
+            .lock()
+            .map_err(|_| std::io::Error::other("Could not lock vec_index"))?
+            .journal_handle()?;
+        //synced outside of the lock, searches don't wait on the disk
+        if let Some(journal) = journal {
+            journal.sync_data()?;
+        }
+        Ok(flushed)
+    }
+
+    /// Writes the whole vec index out and empties the journal. It blocks searches
+    /// while it runs, so it's done by the task thread and on shutdown.
+    pub fn snapshot_vec_index(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let user_version = self.get_version::<InternalUser>()?;
+        let mut vec_index = self
+            .vec_index
+            .lock()
+            .map_err(|_| "Could not lock vec_index")?;
+        vec_index.snapshot(user_version, prefs_schema().fingerprint())
     }
 
     pub fn get_flag(&self, key: &str) -> Result<bool, Box<dyn std::error::Error>> {
@@ -103,7 +185,7 @@ impl DB {
         }
     }
 
-    pub fn set_flag(&mut self, key_in: &str, value: bool) -> Result<(), kv::Error> {
+    pub fn set_flag(&self, key_in: &str, value: bool) -> Result<(), kv::Error> {
         let key = key_in.as_bytes();
         let key_raw = Raw::from(key);
         let value_str = if value { "t" } else { "f" };
@@ -155,10 +237,10 @@ impl DB {
         Ok(())
     }
 
-    pub fn delete_index(&self, view: &str, value: &String) -> Result<(), kv::Error> {
+    pub fn delete_index(&self, view: &str, value: &String) -> Result<bool, kv::Error> {
         let bucket = self.store.bucket::<String, String>(Some(view))?;
-        bucket.remove(value)?;
-        Ok(())
+        let removed = bucket.remove(value)?;
+        Ok(removed.is_some())
     }
 
     pub fn read_index<T>(
@@ -174,6 +256,37 @@ impl DB {
         }
     }
 
+    /// Every uuid indexed under a value starting with `prefix`.
+    pub fn read_index_prefix<T>(
+        &self,
+        view: &str,
+        prefix: &String,
+    ) -> Result<Vec<InternalUuid<T>>, kv::Error> {
+        let bucket = self.store.bucket::<String, String>(Some(view))?;
+        bucket
+            .iter_prefix(prefix)?
+            .map(|item| Ok(item?.value::<String>()?.into()))
+            .collect()
+    }
+
+    /// Index entries with a value in `from..to` as (value, uuid), in order of value.
+    /// Runs either way, so a page can be read from the newest end.
+    pub fn read_index_range<T>(
+        &self,
+        view: &str,
+        from: &String,
+        to: &String,
+    ) -> Result<
+        impl DoubleEndedIterator<Item = Result<(String, InternalUuid<T>), kv::Error>>,
+        kv::Error,
+    > {
+        let bucket = self.store.bucket::<String, String>(Some(view))?;
+        Ok(bucket.iter_range(from, to)?.map(|item| {
+            let item = item?;
+            Ok((item.key::<String>()?, item.value::<String>()?.into()))
+        }))
+    }
+
     pub fn write_object<T>(
         &self,
         key: &InternalUuid<T>,
@@ -188,31 +301,15 @@ impl DB {
                 >,
             > + Insertable,
     {
-        let version = self.get_version::<T>()?;
-        if version != T::version() {
-            return Err(format!(
-                "Version mismatch for object {:?} of type {:?}, object has {:?}, db has {:?}",
-                key.id,
-                T::bucket(),
-                T::version(),
-                version
-            ))?;
-        }
-        let mut serializer = DefaultSerializer::default();
-        serializer.serialize_value(object).unwrap();
-        let bytes = serializer.into_serializer().into_inner();
-        let bucket = self.store.bucket::<Raw, Raw>(Some(T::bucket()))?;
-        let key_raw = Raw::from(key.id.as_bytes());
-        let value_raw = Raw::from(bytes.as_slice());
-        let contains = bucket.contains(&key_raw)?;
-        if !contains {
+        if !self.object_exists(key)? {
             log::trace!(
                 "Writing new object to db: {:?}, it's a {:?}",
                 key.id,
                 T::bucket()
             );
         }
-        bucket.set(&key_raw, &value_raw)?;
+        //a transaction of its own, so the object and its index keys land together
+        self.transaction(|txn| txn.write(key, object))?;
         Ok(key.id.clone().into())
     }
 
@@ -233,12 +330,7 @@ impl DB {
             None => return Ok(None),
         };
 
-        // Ensuring value_raw is scoped to the check_archived_root and deserialize
-        {
-            let archived = rkyv::check_archived_root::<T>(&value_raw[..])?;
-            let deserialized: T = archived.deserialize(&mut Infallible)?;
-            Ok(Some(deserialized))
-        }
+        Ok(Some(from_bytes(&value_raw[..])?))
     }
 
     pub fn iter_obj<T>(
@@ -263,25 +355,23 @@ impl DB {
         Ok(iter)
     }
 
+    /// Removes the object and its index keys, returns whether there was anything to remove.
     pub fn delete_object<T>(
         &self,
         key: &InternalUuid<T>,
-    ) -> Result<Option<std::convert::Infallible>, Box<dyn std::error::Error>>
+    ) -> Result<bool, Box<dyn std::error::Error>>
     where
-        T: Archive,
-        for<'a> T::Archived: rkyv::CheckBytes<DefaultValidator<'a>> + Deserialize<T, Infallible>,
+        T: Insertable,
     {
-        let bucket = self.store.bucket::<Raw, Raw>(Some("object_storage"))?;
-        let key_raw = Raw::from(key.id.as_bytes());
-        let result = bucket.remove(&key_raw)?;
-        match result {
-            Some(_) => return Err("This should never happen, delete_object::db".into()),
-            None => return Ok(None),
-        };
+        let removed = self.transaction(|txn| txn.delete(key))?;
+        if removed {
+            log::trace!("Deleted object {:?} from {:?}", key.id, T::bucket());
+        }
+        Ok(removed)
     }
 
-    pub fn object_exists<T>(&self, key: &InternalUuid<T>) -> Result<bool, kv::Error> {
-        let bucket = self.store.bucket::<Raw, Raw>(Some("object_storage"))?;
+    pub fn object_exists<T: Insertable>(&self, key: &InternalUuid<T>) -> Result<bool, kv::Error> {
+        let bucket = self.store.bucket::<Raw, Raw>(Some(T::bucket()))?;
         let key_raw = Raw::from(key.id.as_bytes());
         let result = bucket.contains(&key_raw)?;
         Ok(result)
@@ -1,16 +1,20 @@
 // use std::error::Error;
 
-use crate::models::internal_models::{
-    internal_prefs::LabeledProperty,
-    internal_user::{Action, InternalRating, TimestampedAction},
+use paperclip::actix::Apiv2Schema;
+use serde::Serialize;
+
+use crate::{
+    glicko::Glicko,
+    models::internal_models::{
+        internal_prefs::LabeledProperty,
+        internal_user::{Action, TimestampedAction},
+    },
 };
 
 // use crate::{
 //     db::DB,
 //     internal_models::{rating::InternalRating, shared::UuidModel, user::User},
 // };
-pub const BEGINNING_LEFT_SWIPES: usize = 100;
-
 pub const ELO_SCALE: f32 = 500.0;
 pub const ELO_SHIFT: f32 = 2.0;
 
@@ -37,27 +41,42 @@ const MAX_MESSAGES_SENT_PER_DAY_REWARDED: usize = 20;
 const MAX_MESSAGES_RECIEVED_PER_DAY_REWARDED: usize = 20;
 const MAX_RATES_PER_DAY_REWARDED: usize = 20;
 
-pub fn calc_elo(
-    rates: &Vec<InternalRating>,
-    actions: &Vec<TimestampedAction>,
-    props: &Vec<LabeledProperty>,
-) -> f32 {
-    let mut liked = 0;
-    let mut passed = 0;
-
-    for rate in rates {
-        match rate {
-            InternalRating::LikedBy(_) => liked += 1,
-            InternalRating::PassedBy(_) => passed += 1,
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct EloComponent {
+    pub name: String,
+    //0 to 1
+    pub value: f32,
+    pub weight: f32,
+    //value * weight, the contributions of all components add up to the elo
+    pub contribution: f32,
+}
+
+impl EloComponent {
+    fn new(name: &str, value: f32, weight: f32) -> Self {
+        EloComponent {
+            name: name.to_string(),
+            value,
+            weight,
+            contribution: value * weight,
         }
     }
+}
 
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct EloBreakdown {
+    pub elo: f32,
+    pub components: Vec<EloComponent>,
+}
+
+pub fn calc_elo(
+    glicko: &Glicko,
+    actions: &Vec<TimestampedAction>,
+    props: &Vec<LabeledProperty>,
+) -> EloBreakdown {
     let props_weight = props.len() as f32 / 1000.0;
     let num_props_filled = props.iter().filter(|prop| prop.value != -32768).count() as f32;
     let perc_props_filled = num_props_filled / props.len() as f32;
 
-    passed += BEGINNING_LEFT_SWIPES;
-
     let mut message_value = 0.0;
     let mut recieve_message_value = 0.0;
     let mut rate_value = 0.0;
@@ -93,15 +112,31 @@ pub fn calc_elo(
         }
     }
 
-    let perc_liked = liked as f32 / (liked + passed) as f32;
-
-    let elo = perc_liked * LIKES_WEIGHT
-        + message_value * MESSAGES_WEIGHT
-        + recieve_message_value * RECIEVE_MESSAGES_WEIGHT
-        + rate_value * RATE_WEIGHT;
-
-    let elo = (1.0 - props_weight) * elo + props_weight * perc_props_filled;
-    elo
+    //how the user fares in rates, weighted by who did the rating
+    let rating_score = glicko.score();
+
+    //the props filled fraction takes its weight off the top of everything else
+    let activity_weight = 1.0 - props_weight;
+    let components = vec![
+        EloComponent::new("rating", rating_score, activity_weight * LIKES_WEIGHT),
+        EloComponent::new(
+            "messages_sent",
+            message_value,
+            activity_weight * MESSAGES_WEIGHT,
+        ),
+        EloComponent::new(
+            "messages_recieved",
+            recieve_message_value,
+            activity_weight * RECIEVE_MESSAGES_WEIGHT,
+        ),
+        EloComponent::new("rates", rate_value, activity_weight * RATE_WEIGHT),
+        EloComponent::new("props_filled", perc_props_filled, props_weight),
+    ];
+
+    EloBreakdown {
+        elo: components.iter().map(|c| c.contribution).sum(),
+        components,
+    }
 }
 
 const NUM_ELOS: usize = 16;
@@ -142,10 +177,55 @@ const ELO_THRESHOLDS: [f32; NUM_ELOS - 1] = [
     15.0 / 16.0,
 ];
 
-pub fn elo_to_label(elo: f32) -> String {
+fn elo_to_tier(elo: f32) -> usize {
     let mut i = 0;
     while i < NUM_ELOS - 1 && elo > ELO_THRESHOLDS[i] {
         i += 1;
     }
-    ELO_LABELS[i].to_string()
+    i
+}
+
+pub fn elo_to_label(elo: f32) -> String {
+    ELO_LABELS[elo_to_tier(elo)].to_string()
+}
+
+/// The label of the tier above `elo` and how much elo is missing to get there,
+/// `None` at the top tier.
+pub fn next_tier(elo: f32) -> Option<(String, f32)> {
+    let tier = elo_to_tier(elo);
+    if tier == NUM_ELOS - 1 {
+        return None;
+    }
+    Some((ELO_LABELS[tier + 1].to_string(), ELO_THRESHOLDS[tier] - elo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breakdown_adds_up_to_elo() {
+        let actions = vec![TimestampedAction {
+            action: Action::Rate,
+            timestamp: chrono::Utc::now().timestamp(),
+        }];
+        let props = vec![
+            LabeledProperty {
+                name: "age".to_string(),
+                value: 30,
+            },
+            LabeledProperty {
+                name: "height".to_string(),
+                value: -32768,
+            },
+        ];
+        let breakdown = calc_elo(&Glicko::default(), &actions, &props);
+        let sum: f32 = breakdown.components.iter().map(|c| c.contribution).sum();
+        assert!((sum - breakdown.elo).abs() < 1e-6);
+
+        let (label, gap) = next_tier(breakdown.elo).unwrap();
+        assert_ne!(label, elo_to_label(breakdown.elo));
+        assert_eq!(elo_to_label(breakdown.elo + gap + 1e-4), label);
+        assert!(next_tier(1.0).is_none());
+    }
 }
@@ -0,0 +1,148 @@
+//great-circle distances on a spherical earth, good to about 0.5%, plenty for matching
+
+use std::f64::consts::{FRAC_PI_2, PI};
+
+pub const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// A point in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatLong {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Haversine distance between two points in km.
+pub fn distance_km(a: LatLong, b: LatLong) -> f64 {
+    let d_lat = (b.latitude - a.latitude).to_radians();
+    let d_long = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2)
+        + a.latitude.to_radians().cos()
+            * b.latitude.to_radians().cos()
+            * (d_long / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().min(1.0).asin()
+}
+
+/// A latitude/longitude box in degrees, min to max on each axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoBounds {
+    pub latitude: (f64, f64),
+    pub longitude: (f64, f64),
+}
+
+/// The smallest box holding every point within `radius_km` of `center`, see
+/// http://janmatuschek.de/LatitudeLongitudeBoundingCoordinates. Boxes can't wrap,
+/// so a circle over a pole or across the antimeridian gets every longitude.
+pub fn bounding_box(center: LatLong, radius_km: f64) -> GeoBounds {
+    let angular = radius_km / EARTH_RADIUS_KM;
+    let latitude = center.latitude.to_radians();
+    let longitude = center.longitude.to_radians();
+    let min_latitude = latitude - angular;
+    let max_latitude = latitude + angular;
+
+    let (latitude, longitude) = if min_latitude > -FRAC_PI_2 && max_latitude < FRAC_PI_2 {
+        let delta = (angular.sin() / latitude.cos()).asin();
+        let longitude = if longitude - delta < -PI || longitude + delta > PI {
+            (-PI, PI)
+        } else {
+            (longitude - delta, longitude + delta)
+        };
+        ((min_latitude, max_latitude), longitude)
+    } else {
+        (
+            (min_latitude.max(-FRAC_PI_2), max_latitude.min(FRAC_PI_2)),
+            (-PI, PI),
+        )
+    };
+
+    GeoBounds {
+        latitude: (latitude.0.to_degrees(), latitude.1.to_degrees()),
+        longitude: (longitude.0.to_degrees(), longitude.1.to_degrees()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONTREAL: LatLong = LatLong {
+        latitude: 45.5017,
+        longitude: -73.5673,
+    };
+    const TORONTO: LatLong = LatLong {
+        latitude: 43.6532,
+        longitude: -79.3832,
+    };
+
+    #[test]
+    fn test_distance() {
+        assert!((distance_km(MONTREAL, TORONTO) - 504.0).abs() < 5.0);
+        assert_eq!(distance_km(MONTREAL, MONTREAL), 0.0);
+
+        //a degree of longitude shrinks away from the equator, a bbox doesn't know that
+        let equator = distance_km(
+            LatLong {
+                latitude: 0.0,
+                longitude: 0.0,
+            },
+            LatLong {
+                latitude: 0.0,
+                longitude: 1.0,
+            },
+        );
+        let north = distance_km(
+            LatLong {
+                latitude: 60.0,
+                longitude: 0.0,
+            },
+            LatLong {
+                latitude: 60.0,
+                longitude: 1.0,
+            },
+        );
+        assert!((north / equator - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_bounding_box_holds_the_circle() {
+        let bounds = bounding_box(MONTREAL, 600.0);
+        let inside = |p: LatLong| {
+            p.latitude >= bounds.latitude.0
+                && p.latitude <= bounds.latitude.1
+                && p.longitude >= bounds.longitude.0
+                && p.longitude <= bounds.longitude.1
+        };
+        assert!(inside(TORONTO));
+        for step in 0..36 {
+            //walk the edge of the circle
+            let bearing = (step as f64 * 10.0).to_radians();
+            let angular = 599.0 / EARTH_RADIUS_KM;
+            let lat = MONTREAL.latitude.to_radians();
+            let edge_lat =
+                (lat.sin() * angular.cos() + lat.cos() * angular.sin() * bearing.cos()).asin();
+            let edge_long = MONTREAL.longitude.to_radians()
+                + (bearing.sin() * angular.sin() * lat.cos())
+                    .atan2(angular.cos() - lat.sin() * edge_lat.sin());
+            assert!(inside(LatLong {
+                latitude: edge_lat.to_degrees(),
+                longitude: edge_long.to_degrees(),
+            }));
+        }
+    }
+
+    #[test]
+    fn test_bounding_box_at_the_antimeridian_and_poles() {
+        let fiji = LatLong {
+            latitude: -17.7,
+            longitude: 178.0,
+        };
+        assert_eq!(bounding_box(fiji, 500.0).longitude, (-180.0, 180.0));
+
+        let svalbard = LatLong {
+            latitude: 89.0,
+            longitude: 15.0,
+        };
+        let bounds = bounding_box(svalbard, 500.0);
+        assert_eq!(bounds.latitude.1, 90.0);
+        assert_eq!(bounds.longitude, (-180.0, 180.0));
+    }
+}
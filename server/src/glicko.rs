@@ -0,0 +1,195 @@
+//Glicko-2, see http://www.glicko.net/glicko/glicko2.pdf
+//every rate is a game between the rater and the target: a like is a win for the
+//target, a pass is a win for the rater, and both sides are updated from it
+
+use std::f64::consts::PI;
+
+pub const DEFAULT_RATING: f64 = 1500.0;
+pub const DEFAULT_DEVIATION: f64 = 350.0;
+pub const DEFAULT_VOLATILITY: f64 = 0.06;
+
+//constrains how fast volatility can change, glickman suggests 0.3 to 1.2
+const TAU: f64 = 0.5;
+const GLICKO2_SCALE: f64 = 173.7178;
+const CONVERGENCE_TOLERANCE: f64 = 0.000001;
+
+//how many deviations below the rating the score is taken at, so a user nobody
+//has rated yet starts near the bottom instead of at the average
+const CONSERVATIVE_DEVIATIONS: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glicko {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Default for Glicko {
+    fn default() -> Self {
+        Glicko {
+            rating: DEFAULT_RATING,
+            deviation: DEFAULT_DEVIATION,
+            volatility: DEFAULT_VOLATILITY,
+        }
+    }
+}
+
+fn g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+}
+
+fn expected(mu: f64, mu_j: f64, phi_j: f64) -> f64 {
+    1.0 / (1.0 + (-g(phi_j) * (mu - mu_j)).exp())
+}
+
+impl Glicko {
+    fn mu(&self) -> f64 {
+        (self.rating - DEFAULT_RATING) / GLICKO2_SCALE
+    }
+
+    fn phi(&self) -> f64 {
+        self.deviation / GLICKO2_SCALE
+    }
+
+    /// The rating after one rating period with `results`, each an opponent and
+    /// the score against them (1 win, 0 loss).
+    pub fn update(&self, results: &[(Glicko, f64)]) -> Glicko {
+        let mu = self.mu();
+        let phi = self.phi();
+
+        //no games, only the deviation grows
+        if results.is_empty() {
+            let phi_star = (phi * phi + self.volatility * self.volatility).sqrt();
+            return Glicko {
+                deviation: phi_star * GLICKO2_SCALE,
+                ..*self
+            };
+        }
+
+        let mut v_inv = 0.0;
+        let mut delta_sum = 0.0;
+        for (opponent, score) in results {
+            let g_j = g(opponent.phi());
+            let e_j = expected(mu, opponent.mu(), opponent.phi());
+            v_inv += g_j * g_j * e_j * (1.0 - e_j);
+            delta_sum += g_j * (score - e_j);
+        }
+        let v = 1.0 / v_inv;
+        let delta = v * delta_sum;
+
+        let volatility = self.new_volatility(phi, v, delta);
+        let phi_star = (phi * phi + volatility * volatility).sqrt();
+        let new_phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+        let new_mu = mu + new_phi * new_phi * delta_sum;
+
+        Glicko {
+            rating: new_mu * GLICKO2_SCALE + DEFAULT_RATING,
+            deviation: new_phi * GLICKO2_SCALE,
+            volatility,
+        }
+    }
+
+    //step 5 of the paper, the Illinois algorithm
+    fn new_volatility(&self, phi: f64, v: f64, delta: f64) -> f64 {
+        let a = (self.volatility * self.volatility).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            let denom = phi * phi + v + ex;
+            ex * (delta * delta - phi * phi - v - ex) / (2.0 * denom * denom)
+                - (x - a) / (TAU * TAU)
+        };
+
+        let mut big_a = a;
+        let mut big_b = if delta * delta > phi * phi + v {
+            (delta * delta - phi * phi - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+
+        let mut f_a = f(big_a);
+        let mut f_b = f(big_b);
+        while (big_b - big_a).abs() > CONVERGENCE_TOLERANCE {
+            let big_c = big_a + (big_a - big_b) * f_a / (f_b - f_a);
+            let f_c = f(big_c);
+            if f_c * f_b <= 0.0 {
+                big_a = big_b;
+                f_a = f_b;
+            } else {
+                f_a /= 2.0;
+            }
+            big_b = big_c;
+            f_b = f_c;
+        }
+
+        (big_a / 2.0).exp()
+    }
+
+    /// The chance of winning against an average, settled player, 0 to 1. Taken at
+    /// the conservative rating so it only climbs as the deviation shrinks.
+    pub fn score(&self) -> f32 {
+        let conservative = self.rating - CONSERVATIVE_DEVIATIONS * self.deviation;
+        (1.0 / (1.0 + 10f64.powf((DEFAULT_RATING - conservative) / 400.0))) as f32
+    }
+}
+
+/// Both sides of one rate, from their ratings before it.
+pub fn rate_pair(rater: &Glicko, target: &Glicko, liked: bool) -> (Glicko, Glicko) {
+    let target_score = if liked { 1.0 } else { 0.0 };
+    let new_rater = rater.update(&[(*target, 1.0 - target_score)]);
+    let new_target = target.update(&[(*rater, target_score)]);
+    (new_rater, new_target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glickman_example() {
+        let player = Glicko {
+            rating: 1500.0,
+            deviation: 200.0,
+            volatility: 0.06,
+        };
+        let opponent = |rating, deviation| Glicko {
+            rating,
+            deviation,
+            volatility: 0.06,
+        };
+        let updated = player.update(&[
+            (opponent(1400.0, 30.0), 1.0),
+            (opponent(1550.0, 100.0), 0.0),
+            (opponent(1700.0, 300.0), 0.0),
+        ]);
+        assert!((updated.rating - 1464.06).abs() < 0.01);
+        assert!((updated.deviation - 151.52).abs() < 0.01);
+        assert!((updated.volatility - 0.05999).abs() < 0.00001);
+    }
+
+    #[test]
+    fn test_likes_from_higher_rated_users_count_more() {
+        let target = Glicko::default();
+        let low = Glicko {
+            rating: 1200.0,
+            deviation: 50.0,
+            ..Glicko::default()
+        };
+        let high = Glicko {
+            rating: 1800.0,
+            deviation: 50.0,
+            ..Glicko::default()
+        };
+        let (_, liked_by_low) = rate_pair(&low, &target, true);
+        let (_, liked_by_high) = rate_pair(&high, &target, true);
+        assert!(liked_by_high.rating > liked_by_low.rating);
+        assert!(liked_by_low.rating > target.rating);
+
+        let (rater, passed) = rate_pair(&high, &target, false);
+        assert!(passed.rating < target.rating);
+        assert!(rater.rating > high.rating);
+    }
+}
@@ -0,0 +1,243 @@
+use std::{error::Error, fmt};
+
+use rkyv::{validation::validators::DefaultValidator, Archive, Deserialize, Infallible};
+
+use crate::{
+    db::{from_bytes, sled_db, to_bytes, DB},
+    models::internal_models::shared::{Insertable, InternalUuid},
+    transaction::{abort, Txn, TxnResult},
+};
+
+//"{bucket}/{uuid}" to the index keys the object was last written with, so a write
+//knows which keys to drop without reading the object it replaces
+pub const INDEX_KEYS: &str = "index.keys";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexKind {
+    //one object per value, stored under the value itself
+    Unique,
+    //any number of objects per value, stored under "{value}/{uuid}"
+    Multi,
+}
+
+/// A secondary index a model declares in `Insertable::indexes`. Writes and deletes
+/// of the model keep it in step, nothing else should write to it.
+#[derive(Debug)]
+pub struct Index {
+    pub name: &'static str,
+    pub kind: IndexKind,
+}
+
+impl Index {
+    pub const fn unique(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: IndexKind::Unique,
+        }
+    }
+
+    pub const fn multi(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: IndexKind::Multi,
+        }
+    }
+
+    pub fn key<T>(&self, value: &str, uuid: &InternalUuid<T>) -> String {
+        match self.kind {
+            IndexKind::Unique => value.to_string(),
+            IndexKind::Multi => format!("{}/{}", value, uuid.id),
+        }
+    }
+
+    /// The object with `value`, for unique indexes.
+    pub fn get<T>(&self, db: &DB, value: &str) -> Result<Option<InternalUuid<T>>, kv::Error> {
+        db.read_index(self.name, &value.to_string())
+    }
+
+    pub fn get_txn<T>(&self, txn: &Txn, value: &str) -> TxnResult<Option<InternalUuid<T>>> {
+        txn.read_index(self.name, value)
+    }
+
+    /// Every object with a value starting with `prefix`, in order of value.
+    pub fn prefix<T>(&self, db: &DB, prefix: &str) -> Result<Vec<InternalUuid<T>>, kv::Error> {
+        db.read_index_prefix(self.name, &prefix.to_string())
+    }
+
+    /// Entries with a key in `from..to` as (key, uuid), see `DB::read_index_range`.
+    pub fn range<T>(
+        &self,
+        db: &DB,
+        from: &str,
+        to: &str,
+    ) -> Result<
+        impl DoubleEndedIterator<Item = Result<(String, InternalUuid<T>), kv::Error>>,
+        kv::Error,
+    > {
+        db.read_index_range(self.name, &from.to_string(), &to.to_string())
+    }
+}
+
+/// Returned when a write would give a unique index value to a second object.
+#[derive(Debug)]
+pub struct IndexConflict {
+    pub index: &'static str,
+    pub value: String,
+}
+
+impl fmt::Display for IndexConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is already taken in {}", self.value, self.index)
+    }
+}
+
+impl Error for IndexConflict {}
+
+#[derive(Debug, PartialEq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
+struct IndexedKey {
+    index: String,
+    key: String,
+}
+
+fn record_key(bucket: &str, id: &str) -> String {
+    format!("{}/{}", bucket, id)
+}
+
+impl<'a> Txn<'a> {
+    fn indexed_keys(&self, bucket: &str, id: &str) -> TxnResult<Vec<IndexedKey>> {
+        match self
+            .tree(INDEX_KEYS)?
+            .get(record_key(bucket, id).as_bytes())?
+        {
+            Some(bytes) => from_bytes(&bytes).map_err(abort),
+            None => Ok(vec![]),
+        }
+    }
+
+    //a key is only dropped by the object it points at
+    fn remove_index_key(&self, index: &str, key: &str, id: &str) -> TxnResult<()> {
+        let tree = self.tree(index)?;
+        if tree
+            .get(key.as_bytes())?
+            .is_some_and(|held| &held[..] == id.as_bytes())
+        {
+            tree.remove(key.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the object's index keys and drops the ones its last write had that it
+    /// doesn't anymore. Aborts with an `IndexConflict` if a unique value is taken.
+    pub fn write_index_keys<T: Insertable>(
+        &self,
+        uuid: &InternalUuid<T>,
+        object: &T,
+    ) -> TxnResult<()> {
+        let entries = object
+            .index_values()
+            .into_iter()
+            .map(|(index, value)| (index, index.key(&value, uuid)))
+            .collect::<Vec<_>>();
+        let keys = entries
+            .iter()
+            .map(|(index, key)| IndexedKey {
+                index: index.name.to_string(),
+                key: key.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        for old in self.indexed_keys(T::bucket(), &uuid.id)? {
+            if !keys.contains(&old) {
+                self.remove_index_key(&old.index, &old.key, &uuid.id)?;
+            }
+        }
+        for (index, key) in &entries {
+            let tree = self.tree(index.name)?;
+            if index.kind == IndexKind::Unique {
+                if let Some(holder) = tree.get(key.as_bytes())? {
+                    let holder = String::from_utf8(holder.to_vec()).map_err(abort)?;
+                    //keys written before they were tracked can be left over, those are free
+                    if holder != uuid.id
+                        && self
+                            .indexed_keys(T::bucket(), &holder)?
+                            .iter()
+                            .any(|held| held.index == index.name && held.key == *key)
+                    {
+                        return Err(abort(IndexConflict {
+                            index: index.name,
+                            value: key.clone(),
+                        }));
+                    }
+                }
+            }
+            tree.insert(key.as_bytes(), uuid.id.as_bytes())?;
+        }
+
+        let records = self.tree(INDEX_KEYS)?;
+        let record = record_key(T::bucket(), &uuid.id);
+        if keys.is_empty() {
+            records.remove(record.as_bytes())?;
+        } else {
+            records.insert(record.as_bytes(), to_bytes(&keys).as_slice())?;
+        }
+        Ok(())
+    }
+
+    /// Drops every index key the object was last written with.
+    pub fn delete_index_keys<T: Insertable>(&self, uuid: &InternalUuid<T>) -> TxnResult<()> {
+        for old in self.indexed_keys(T::bucket(), &uuid.id)? {
+            self.remove_index_key(&old.index, &old.key, &uuid.id)?;
+        }
+        self.tree(INDEX_KEYS)?
+            .remove(record_key(T::bucket(), &uuid.id).as_bytes())?;
+        Ok(())
+    }
+}
+
+/// What a `DB::rebuild_indexes` pass did.
+#[derive(Debug, Default, PartialEq)]
+pub struct RebuiltIndexes {
+    pub objects: usize,
+    //objects left out of the indexes because a unique value of theirs was taken
+    pub conflicts: usize,
+}
+
+impl DB {
+    /// Rebuilds the indexes of `T` from its objects. They're cleared first, so lookups
+    /// miss until it's done and a unique value can be taken twice meanwhile. Only run it
+    /// at startup, before any requests come in.
+    pub fn rebuild_indexes<T>(&self) -> Result<RebuiltIndexes, Box<dyn Error>>
+    where
+        T: Archive + Insertable,
+        for<'a> T::Archived: rkyv::CheckBytes<DefaultValidator<'a>> + Deserialize<T, Infallible>,
+    {
+        let sled = sled_db(&self.store);
+        for index in T::indexes() {
+            sled.open_tree(index.name)?.clear()?;
+        }
+        let records = sled.open_tree(INDEX_KEYS)?;
+        for record in records
+            .scan_prefix(format!("{}/", T::bucket()).as_bytes())
+            .keys()
+        {
+            records.remove(record?)?;
+        }
+
+        let mut rebuilt = RebuiltIndexes::default();
+        for entry in sled.open_tree(T::bucket())?.iter() {
+            let (id, bytes) = entry?;
+            let uuid: InternalUuid<T> = String::from_utf8(id.to_vec())?.into();
+            let object = from_bytes::<T>(&bytes)?;
+            match self.transaction(|txn| txn.write_index_keys(&uuid, &object)) {
+                Ok(()) => rebuilt.objects += 1,
+                Err(e) if e.is::<IndexConflict>() => {
+                    log::error!("Not indexing {} {}: {}", T::bucket(), uuid.id, e);
+                    rebuilt.conflicts += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(rebuilt)
+    }
+}
@@ -0,0 +1,46 @@
+use std::{error::Error, path::PathBuf};
+
+pub const DEFAULT_MAIL_DIR: &str = "outbox";
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Where outgoing mail goes. The server only needs to hand a message off, so
+/// swapping in a real provider is one more implementation.
+pub trait Mailer: Send + Sync {
+    fn send(&self, mail: &Mail) -> Result<(), Box<dyn Error>>;
+}
+
+/// Writes each mail to its own file instead of sending it, for running locally.
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<FileMailer, Box<dyn Error>> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(FileMailer { dir })
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, mail: &Mail) -> Result<(), Box<dyn Error>> {
+        let name = format!(
+            "{}_{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+            uuid::Uuid::new_v4()
+        );
+        let contents = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            mail.to, mail.subject, mail.body
+        );
+        std::fs::write(self.dir.join(&name), contents)?;
+        log::info!("Wrote mail to {} as {}", mail.to, name);
+        Ok(())
+    }
+}
@@ -14,34 +14,88 @@ use actix_web::{
 use bots::bot_manager::start_bot_manager;
 use db::DB;
 use logger::init_logs;
-use middleware::jwt::Jwt;
+use mailer::{FileMailer, Mailer, DEFAULT_MAIL_DIR};
+use middleware::{
+    jwt::Jwt,
+    keyring::{init_keyring, Keyring, DEFAULT_KEYRING_DIR},
+    rate_limit::{Limit, LoginLockouts, RateLimit},
+};
+use renditions::MAX_UPLOAD_BYTES;
 
 use paperclip::actix::{web, OpenApiExt};
 
 use dotenv::dotenv;
+use models::internal_models::internal_prefs_config::{
+    init_prefs_schema, PrefsSchema, DEFAULT_PREFS_SCHEMA_PATH,
+};
 use routes::{
-    check_username::check_username, delete_image::delete_image, delete_message::delete_message,
-    delete_user::delete_user, fetch_notifications::fetch_notifications, get_chats::get_chats,
-    get_images::get_images, get_internal_me::get_internal_me, get_me::get_me,
-    get_message::get_message, get_messages::get_messages, get_next_users::get_next_users,
-    get_prefs_config::get_prefs_config, get_users::get_users,
+    admin::{
+        ban_user, broadcast, generate_access_codes, get_user_activity, list_photo_clusters,
+        search_users, set_role, unpublish_user,
+    },
+    block::block,
+    change_password::change_password,
+    check_username::check_username,
+    delete_image::delete_image,
+    delete_message::delete_message,
+    delete_user::delete_user,
+    fetch_notifications::fetch_notifications,
+    get_chats::get_chats,
+    get_elo_breakdown::get_elo_breakdown,
+    get_image::get_image,
+    get_images::get_images,
+    get_internal_me::get_internal_me,
+    get_me::get_me,
+    get_message::get_message,
+    get_messages::get_messages,
+    get_messages_page::get_messages_page,
+    get_next_users::get_next_users,
+    get_prefs_config::get_prefs_config,
+    get_users::get_users,
     get_users_i_perfer_count_dry_run::get_users_i_perfer_count_dry_run,
-    get_users_mutual_perfer_count_dry_run::get_users_mutual_perfer_count_dry_run, login::login,
-    put_image::put_image, put_message::put_message, put_user::put_user, rate::rate, report::report,
+    get_users_mutual_perfer_count_dry_run::get_users_mutual_perfer_count_dry_run,
+    login::login,
+    logout::{logout, logout_all},
+    mark_read::mark_read,
+    moderation::{add_report_note, assign_report, get_report, list_reports, resolve_report},
+    put_image::{put_image, upload_image},
+    put_message::put_message,
+    put_user::put_user,
+    rate::rate,
+    refresh::refresh,
+    report::report,
+    reset_password::{issue_reset_code, reset_password},
+    rotate_signing_key::rotate_signing_key,
     signup::signup,
+    typing::typing,
+    unmatch::unmatch,
+    ws::ws,
+};
+use tasks::{
+    backfill_glicko::backfill_glicko_if_needed, collect_images::count_image_refs_if_needed,
+    rebuild_indexes::rebuild_indexes_if_needed, remap_prefs::remap_prefs_if_needed,
+    tasks::run_all_tasks,
 };
-use tasks::tasks::run_all_tasks;
+use vec::search_backend::VecBackend;
 
+pub mod blobs;
 pub mod bots;
 pub mod constants;
 pub mod db;
 pub mod elo;
+pub mod geo;
+pub mod glicko;
+pub mod index;
 pub mod logger;
+pub mod mailer;
 pub mod middleware;
 pub mod models;
+pub mod push;
+pub mod renditions;
 pub mod routes;
 pub mod tasks;
 pub mod test;
+pub mod transaction;
 pub mod util;
 pub mod vec;
 
@@ -63,22 +117,61 @@ async fn main() -> std::io::Result<()> {
 
     let db_name = if prod { "prod" } else { "dummy" };
 
+    let vec_backend = match std::env::var("VEC_BACKEND") {
+        Ok(backend) => backend.parse().map_err(|e| {
+            log::error!("Invalid VEC_BACKEND {:?}", e);
+            std::io::Error::other("Invalid VEC_BACKEND")
+        })?,
+        Err(_) => VecBackend::default(),
+    };
+
+    let prefs_schema_path =
+        std::env::var("PREFS_SCHEMA").unwrap_or_else(|_| DEFAULT_PREFS_SCHEMA_PATH.to_string());
+    let prefs_schema =
+        PrefsSchema::load(std::path::Path::new(&prefs_schema_path)).map_err(|e| {
+            log::error!("Failed to load prefs schema {:?}", e);
+            std::io::Error::other("Failed to load prefs schema")
+        })?;
+    log::info!(
+        "Loaded prefs schema version {} with {} preferences",
+        prefs_schema.version,
+        prefs_schema.preferences.len()
+    );
+    init_prefs_schema(prefs_schema).unwrap();
+
+    let keyring_dir =
+        std::env::var("JWT_KEYRING").unwrap_or_else(|_| DEFAULT_KEYRING_DIR.to_string());
+    let keyring = Keyring::load(std::path::Path::new(&keyring_dir)).map_err(|e| {
+        log::error!("Failed to load keyring {:?}", e);
+        std::io::Error::other("Failed to load keyring")
+    })?;
+    log::info!("Signing tokens with key {}", keyring.active_kid());
+    init_keyring(keyring).unwrap();
+
     let running = Arc::new(AtomicBool::new(true));
     let running_clone = running.clone();
     let running_clone_clone = running.clone();
 
-    let db = web::Data::new(DB::new(db_name).map_err(|e| {
+    let db = web::Data::new(DB::new_with_backend(db_name, vec_backend).map_err(|e| {
         log::error!("Failed to create db {:?}", e);
         std::io::Error::new(std::io::ErrorKind::Other, "Failed to create db")
     })?);
 
     db.migrate_all().unwrap();
+    rebuild_indexes_if_needed(&db).unwrap();
+    remap_prefs_if_needed(&db).unwrap();
+    backfill_glicko_if_needed(&db).unwrap();
+    count_image_refs_if_needed(&db).unwrap();
 
     // Task thread
     let db_clone = db.clone();
     std::thread::spawn(move || {
         while running_clone.load(Ordering::SeqCst) {
             run_all_tasks(&db_clone).unwrap();
+            //the journal has everything since the last one if this fails
+            if let Err(e) = db_clone.snapshot_vec_index() {
+                log::error!("Failed to snapshot vec index {:?}", e);
+            }
             std::thread::sleep(std::time::Duration::from_secs(TASK_DELAY));
         }
     });
@@ -101,6 +194,32 @@ async fn main() -> std::io::Result<()> {
 
     let db_clone_for_flushing = db.clone();
 
+    //shared by every worker, counted per IP and per logged in account
+    let rate_limit = RateLimit::new(Limit::per_minute(300, 600))
+        .route("/login", Limit::per_minute(10, 10))
+        .route("/signup", Limit::per_hour(5, 10))
+        .route("/refresh", Limit::per_minute(10, 10))
+        .route("/check_username", Limit::per_minute(30, 30))
+        .route("/reset_password", Limit::per_minute(5, 5))
+        //reports work logged out and each one stores a snapshot
+        .route("/report", Limit::per_hour(10, 20));
+    //the bots all log in from this machine
+    let rate_limit = if enable_bots {
+        rate_limit
+            .trust(std::net::Ipv4Addr::LOCALHOST.into())
+            .trust(std::net::Ipv6Addr::LOCALHOST.into())
+    } else {
+        rate_limit
+    };
+    let login_lockouts = web::Data::new(LoginLockouts::default());
+
+    let mail_dir = std::env::var("MAIL_DIR").unwrap_or_else(|_| DEFAULT_MAIL_DIR.to_string());
+    let mailer = FileMailer::new(mail_dir).map_err(|e| {
+        log::error!("Failed to set up mailer {:?}", e);
+        std::io::Error::other("Failed to set up mailer")
+    })?;
+    let mailer: web::Data<Box<dyn Mailer>> = web::Data::new(Box::new(mailer));
+
     // Your existing HttpServer setup
     let result = HttpServer::new(move || {
         App::new()
@@ -113,6 +232,17 @@ async fn main() -> std::io::Result<()> {
                     .max_age(3600),
             )
             .app_data(db.clone())
+            .app_data(login_lockouts.clone())
+            .app_data(mailer.clone())
+            //raw image uploads, everything else is json
+            .app_data(actix_web::web::PayloadConfig::new(MAX_UPLOAD_BYTES))
+            //a plain actix route, the upgrade has no place in the openapi spec
+            .service(actix_web::web::resource("/ws").route(actix_web::web::get().to(ws)))
+            //plain too, and ahead of the static files, which take every other get
+            .service(
+                actix_web::web::resource("/image/{uuid}")
+                    .route(actix_web::web::get().to(get_image)),
+            )
             .service(
                 Files::new("/", "./public")
                     .index_file("index.html")
@@ -123,13 +253,23 @@ async fn main() -> std::io::Result<()> {
             )
             .wrap_api()
             .with_json_spec_at(JSON_SPEC_PATH)
+            //inside the jwt middleware so it can limit by account too
+            .wrap(rate_limit.clone())
             .wrap(Jwt)
             .service(signup)
             .service(login)
+            .service(refresh)
+            .service(logout)
+            .service(logout_all)
             .service(get_users)
             .service(get_chats)
             .service(get_message)
             .service(get_messages)
+            .service(get_messages_page)
+            .service(mark_read)
+            .service(typing)
+            .service(unmatch)
+            .service(block)
             .service(check_username)
             .service(put_message)
             .service(get_prefs_config)
@@ -140,13 +280,32 @@ async fn main() -> std::io::Result<()> {
             .service(get_next_users)
             .service(get_images)
             .service(put_image)
+            .service(upload_image)
             .service(rate)
             .service(report)
+            .service(list_reports)
+            .service(get_report)
+            .service(assign_report)
+            .service(add_report_note)
+            .service(resolve_report)
+            .service(search_users)
+            .service(get_user_activity)
+            .service(unpublish_user)
+            .service(ban_user)
+            .service(list_photo_clusters)
+            .service(set_role)
+            .service(broadcast)
+            .service(generate_access_codes)
             .service(fetch_notifications)
             .service(delete_image)
             .service(delete_user)
             .service(delete_message)
             .service(get_internal_me)
+            .service(get_elo_breakdown)
+            .service(rotate_signing_key)
+            .service(change_password)
+            .service(issue_reset_code)
+            .service(reset_password)
             .build()
     })
     .workers(4)
@@ -159,6 +318,9 @@ async fn main() -> std::io::Result<()> {
     println!("Flushing db");
     let res = db_clone_for_flushing.flush()?;
     println!("Flushed {:?}", res);
+    if let Err(e) = db_clone_for_flushing.snapshot_vec_index() {
+        log::error!("Failed to snapshot vec index {:?}", e);
+    }
 
     result
 }
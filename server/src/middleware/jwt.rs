@@ -1,14 +1,18 @@
 use actix_web::HttpMessage;
-use jsonwebtoken::{decode, Algorithm, DecodingKey, EncodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use std::future::Future;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Claims {
     uuid: String,
+    //the session the token was issued under, see `InternalSession`
+    sid: String,
     exp: usize,
 }
 
+//access tokens are short lived, clients get new ones from /refresh
+pub const ACCESS_TOKEN_MINUTES: i64 = 120;
+
 use std::{
     future::{ready, Ready},
     pin::Pin,
@@ -19,8 +23,12 @@ use actix_web::{
     Error,
 };
 
+use super::keyring::keyring;
 use crate::{
-    models::internal_models::{internal_user::InternalUser, shared::InternalUuid},
+    db::DB,
+    models::internal_models::{
+        internal_session::InternalSession, internal_user::InternalUser, shared::InternalUuid,
+    },
     JSON_SPEC_PATH,
 };
 
@@ -54,9 +62,11 @@ pub struct JwtMiddleware<S> {
     service: S,
 }
 
-const NOAUTH_PATHS: [&str; 5] = [
+const NOAUTH_PATHS: [&str; 7] = [
     "/login",
     "/signup",
+    "/refresh",
+    "/reset_password",
     "/check_username",
     "/report",
     JSON_SPEC_PATH,
@@ -89,46 +99,17 @@ where
             Some(header_value) => match header_value.to_str() {
                 Ok(auth_str) if auth_str.starts_with("Bearer ") => {
                     let token = &auth_str[7..]; // Extract the actual token
-                    let jwk = include_str!("key/jwk.pem.pub"); // Load your key here
-                    let decoding_key = match DecodingKey::from_rsa_pem(jwk.as_bytes()) {
-                        Ok(key) => key,
-                        Err(e) => {
-                            log::error!("Failed to decode key {:?}", e);
-                            return Box::pin(async {
-                                Err(Error::from(actix_web::error::ErrorUnauthorized(
-                                    "Invalid key format",
-                                )))
-                            });
-                        }
-                    };
-                    let validation = Validation::new(Algorithm::RS256);
-
-                    match decode::<Claims>(token, &decoding_key, &validation) {
-                        Ok(claims) => {
-                            if claims.claims.exp < chrono::Utc::now().timestamp() as usize {
-                                log::info!("Token expired");
-                                return Box::pin(async {
-                                    Err(Error::from(actix_web::error::ErrorUnauthorized(
-                                        "Token expired",
-                                    )))
-                                });
-                            }
-                            let uuid: InternalUuid<InternalUser> = claims.claims.uuid.into();
-
+                    let db = req.app_data::<actix_web::web::Data<DB>>();
+                    match authenticate(db.map(|db| db.as_ref()), token) {
+                        Ok((uuid, session)) => {
                             {
                                 let mut ext = req.extensions_mut();
                                 ext.insert(uuid);
+                                ext.insert(session);
                             }
                             Box::pin(self.service.call(req))
                         }
-                        Err(err) => {
-                            log::error!("Failed to decode token {:?}", err);
-                            Box::pin(async move {
-                                Err(Error::from(actix_web::error::ErrorUnauthorized(
-                                    err.to_string(),
-                                )))
-                            })
-                        }
+                        Err(e) => Box::pin(async { Err(e) }),
                     }
                 }
                 e => {
@@ -152,18 +133,81 @@ where
     }
 }
 
-pub fn make_jwt(uuid: &InternalUuid<InternalUser>) -> Result<String, jsonwebtoken::errors::Error> {
-    //make it expire in 2 hours
+/// The token from an `Authorization: Bearer` header, for routes the middleware skips.
+pub fn bearer_token(req: &actix_web::HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// Checks an access token, returns who it was issued to and under which session.
+/// Also used where there is no header to put it in, like opening a socket.
+pub fn authenticate(
+    db: Option<&DB>,
+    token: &str,
+) -> Result<(InternalUuid<InternalUser>, InternalUuid<InternalSession>), Error> {
+    let verified = match keyring().read() {
+        Ok(keyring) => keyring.verify::<Claims>(token),
+        Err(e) => {
+            log::error!("Keyring lock poisoned {:?}", e);
+            return Err(actix_web::error::ErrorInternalServerError(
+                "Failed to read signing keys",
+            ));
+        }
+    };
+    let claims = match verified {
+        Ok(Ok(claims)) => claims,
+        Ok(Err(e)) => {
+            log::info!("Refused token: {}", e.message());
+            return Err(actix_web::error::ErrorUnauthorized(e.message()));
+        }
+        Err(err) => {
+            log::error!("Failed to decode token {:?}", err);
+            return Err(actix_web::error::ErrorUnauthorized(err.to_string()));
+        }
+    };
+    if claims.exp < chrono::Utc::now().timestamp() as usize {
+        log::info!("Token expired");
+        return Err(actix_web::error::ErrorUnauthorized("Token expired"));
+    }
+    let uuid: InternalUuid<InternalUser> = claims.uuid.into();
+    let session: InternalUuid<InternalSession> = claims.sid.into();
+
+    //logged out, or the refresh token was stolen and reused
+    let active = match db {
+        Some(db) => InternalSession::is_active(db, &session),
+        None => Err("No db to check the session against".into()),
+    };
+    match active {
+        Ok(true) => Ok((uuid, session)),
+        Ok(false) => {
+            log::info!("Session revoked");
+            Err(actix_web::error::ErrorUnauthorized("Session revoked"))
+        }
+        Err(e) => {
+            log::error!("Failed to check session {:?}", e);
+            Err(actix_web::error::ErrorInternalServerError(
+                "Failed to check session",
+            ))
+        }
+    }
+}
+
+pub fn make_jwt(
+    uuid: &InternalUuid<InternalUser>,
+    session: &InternalUuid<InternalSession>,
+) -> Result<String, Box<dyn std::error::Error>> {
     let claims = Claims {
         uuid: uuid.id.clone(),
-        exp: (chrono::Utc::now() + chrono::Duration::hours(2)).timestamp() as usize,
+        sid: session.id.clone(),
+        exp: (chrono::Utc::now() + chrono::Duration::minutes(ACCESS_TOKEN_MINUTES)).timestamp()
+            as usize,
     };
 
-    let pem_data = include_str!("key/jwk.pem.priv"); // Load your key here
-    let encoding_key = EncodingKey::from_rsa_pem(pem_data.as_bytes())?;
-    jsonwebtoken::encode(
-        &jsonwebtoken::Header::new(Algorithm::RS256),
-        &claims,
-        &encoding_key,
-    )
+    let keyring = keyring()
+        .read()
+        .map_err(|e| format!("Keyring lock poisoned {:?}", e))?;
+    Ok(keyring.sign(&claims)?)
 }
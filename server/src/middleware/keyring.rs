@@ -0,0 +1,346 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{OnceLock, RwLock},
+};
+
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use ring::{
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::jwt::ACCESS_TOKEN_MINUTES;
+
+pub const DEFAULT_KEYRING_DIR: &str = "keyring";
+
+//a retired key keeps verifying until every token it signed has expired
+pub const KEY_RETIREMENT_MINUTES: i64 = ACCESS_TOKEN_MINUTES;
+
+const MANIFEST_FILE: &str = "keyring.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyEntry {
+    kid: String,
+    created: i64,
+    retired: Option<i64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    active: String,
+    keys: Vec<KeyEntry>,
+}
+
+struct LoadedKey {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+/// Why a token's signing key was not accepted.
+#[derive(Debug, PartialEq)]
+pub enum KeyError {
+    MissingKid,
+    UnknownKid,
+    Retired,
+}
+
+impl KeyError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            KeyError::MissingKid => "Token has no key id",
+            KeyError::UnknownKid => "Token signed with an unknown key",
+            KeyError::Retired => "Token signed with a retired key",
+        }
+    }
+}
+
+/// Signing keys kept in a directory, one Ed25519 key per file next to a manifest.
+/// Tokens are signed with the active key and name it in their `kid` header. After a
+/// rotation the old key still verifies for `KEY_RETIREMENT_MINUTES`.
+pub struct Keyring {
+    dir: PathBuf,
+    manifest: Manifest,
+    keys: HashMap<String, LoadedKey>,
+}
+
+fn key_path(dir: &Path, kid: &str) -> PathBuf {
+    dir.join(format!("{}.der", kid))
+}
+
+//only the server's user gets to read a signing key
+fn write_private_key(path: &Path, pkcs8: &[u8]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(pkcs8)
+}
+
+fn load_key(dir: &Path, kid: &str) -> Result<LoadedKey, Box<dyn Error>> {
+    let pkcs8 = std::fs::read(key_path(dir, kid))?;
+    let pair = Ed25519KeyPair::from_pkcs8(&pkcs8).map_err(|e| format!("Bad key {}: {}", kid, e))?;
+    Ok(LoadedKey {
+        encoding: EncodingKey::from_ed_der(&pkcs8),
+        decoding: DecodingKey::from_ed_der(pair.public_key().as_ref()),
+    })
+}
+
+impl Keyring {
+    /// Reads the keyring in `dir`, creating it with a first key if it is empty.
+    pub fn load(dir: &Path) -> Result<Keyring, Box<dyn Error>> {
+        std::fs::create_dir_all(dir)?;
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let manifest: Manifest = match std::fs::read_to_string(&manifest_path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Manifest::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut keys = HashMap::new();
+        for entry in &manifest.keys {
+            keys.insert(entry.kid.clone(), load_key(dir, &entry.kid)?);
+        }
+        let mut keyring = Keyring {
+            dir: dir.to_path_buf(),
+            manifest,
+            keys,
+        };
+        if !keyring.keys.contains_key(&keyring.manifest.active) {
+            log::info!("No active signing key in {:?}, creating one", dir);
+            keyring.rotate()?;
+        }
+        Ok(keyring)
+    }
+
+    pub fn active_kid(&self) -> &str {
+        &self.manifest.active
+    }
+
+    /// Makes a new key active and retires the old one, returns the new key id. Keys
+    /// retired long enough that nothing they signed is still valid are deleted.
+    pub fn rotate(&mut self) -> Result<String, Box<dyn Error>> {
+        let now = chrono::Utc::now().timestamp();
+        let kid = uuid::Uuid::new_v4().to_string();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|e| format!("Failed to generate key: {}", e))?;
+        write_private_key(&key_path(&self.dir, &kid), pkcs8.as_ref())?;
+        self.keys.insert(kid.clone(), load_key(&self.dir, &kid)?);
+
+        for entry in self.manifest.keys.iter_mut() {
+            if entry.retired.is_none() {
+                entry.retired = Some(now);
+            }
+        }
+        self.manifest.keys.push(KeyEntry {
+            kid: kid.clone(),
+            created: now,
+            retired: None,
+        });
+        self.manifest.active = kid.clone();
+        let expired = self.prune(now);
+        self.save()?;
+
+        //only delete once the manifest no longer names them
+        for kid in expired {
+            if let Err(e) = std::fs::remove_file(key_path(&self.dir, &kid)) {
+                log::warn!("Failed to delete retired key {} {:?}", kid, e);
+            }
+        }
+        Ok(kid)
+    }
+
+    fn prune(&mut self, now: i64) -> Vec<String> {
+        let cutoff = now - KEY_RETIREMENT_MINUTES * 60;
+        let (expired, kept) = std::mem::take(&mut self.manifest.keys)
+            .into_iter()
+            .partition(|entry| entry.retired.is_some_and(|retired| retired < cutoff));
+        self.manifest.keys = kept;
+        expired
+            .into_iter()
+            .map(|entry: KeyEntry| {
+                self.keys.remove(&entry.kid);
+                entry.kid
+            })
+            .collect()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        //write then rename so a crash never leaves half a manifest
+        let tmp = self.dir.join(format!("{}.tmp", MANIFEST_FILE));
+        std::fs::write(&tmp, serde_json::to_string_pretty(&self.manifest)?)?;
+        std::fs::rename(tmp, self.dir.join(MANIFEST_FILE))?;
+        Ok(())
+    }
+
+    fn decoding_key(&self, kid: &str, now: i64) -> Result<&DecodingKey, KeyError> {
+        let entry = self
+            .manifest
+            .keys
+            .iter()
+            .find(|entry| entry.kid == kid)
+            .ok_or(KeyError::UnknownKid)?;
+        if let Some(retired) = entry.retired {
+            if retired + KEY_RETIREMENT_MINUTES * 60 < now {
+                return Err(KeyError::Retired);
+            }
+        }
+        self.keys
+            .get(kid)
+            .map(|key| &key.decoding)
+            .ok_or(KeyError::UnknownKid)
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(self.manifest.active.clone());
+        //the active key is always loaded, `load` and `rotate` make sure of it
+        let key = &self.keys[&self.manifest.active].encoding;
+        jsonwebtoken::encode(&header, claims, key)
+    }
+
+    /// Checks the token against the key its `kid` names. The outer error is a bad
+    /// token, the inner one a key that is no longer trusted.
+    pub fn verify<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<Result<T, KeyError>, jsonwebtoken::errors::Error> {
+        let header = jsonwebtoken::decode_header(token)?;
+        let kid = match header.kid {
+            Some(kid) => kid,
+            None => return Ok(Err(KeyError::MissingKid)),
+        };
+        let key = match self.decoding_key(&kid, chrono::Utc::now().timestamp()) {
+            Ok(key) => key,
+            Err(e) => return Ok(Err(e)),
+        };
+        let claims = jsonwebtoken::decode::<T>(token, key, &Validation::new(Algorithm::EdDSA))?;
+        Ok(Ok(claims.claims))
+    }
+}
+
+static KEYRING: OnceLock<RwLock<Keyring>> = OnceLock::new();
+
+/// Installs the keyring at startup, before any token is signed or checked.
+pub fn init_keyring(keyring: Keyring) -> Result<(), Box<dyn Error>> {
+    KEYRING
+        .set(RwLock::new(keyring))
+        .map_err(|_| "Keyring is already loaded".into())
+}
+
+/// The loaded keyring, read from `DEFAULT_KEYRING_DIR` if nothing was installed.
+pub fn keyring() -> &'static RwLock<Keyring> {
+    KEYRING.get_or_init(|| {
+        RwLock::new(
+            Keyring::load(Path::new(DEFAULT_KEYRING_DIR)).unwrap_or_else(|e| panic!("{}", e)),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestClaims {
+        uuid: String,
+        exp: usize,
+    }
+
+    fn claims() -> TestClaims {
+        TestClaims {
+            uuid: "someone".to_string(),
+            exp: (chrono::Utc::now().timestamp() + 60) as usize,
+        }
+    }
+
+    fn temp_keyring() -> (PathBuf, Keyring) {
+        let dir = std::env::temp_dir().join(format!("keyring_{}", uuid::Uuid::new_v4()));
+        let keyring = Keyring::load(&dir).unwrap();
+        (dir, keyring)
+    }
+
+    #[test]
+    fn test_rotation_keeps_retired_keys_for_a_while() {
+        let (dir, mut keyring) = temp_keyring();
+        let first = keyring.active_kid().to_string();
+        let old_token = keyring.sign(&claims()).unwrap();
+        assert_eq!(
+            jsonwebtoken::decode_header(&old_token).unwrap().kid,
+            Some(first.clone())
+        );
+
+        let second = keyring.rotate().unwrap();
+        assert_ne!(first, second);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(key_path(&dir, &second))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let new_token = keyring.sign(&claims()).unwrap();
+        assert_eq!(keyring.verify(&old_token).unwrap(), Ok(claims()));
+        assert_eq!(keyring.verify(&new_token).unwrap(), Ok(claims()));
+
+        //another server process reading the same directory agrees
+        let reloaded = Keyring::load(&dir).unwrap();
+        assert_eq!(reloaded.active_kid(), second);
+        assert_eq!(reloaded.verify(&old_token).unwrap(), Ok(claims()));
+
+        //past the window the retired key is refused, then dropped on the next rotation
+        let long_ago = chrono::Utc::now().timestamp() - KEY_RETIREMENT_MINUTES * 60 - 1;
+        keyring.manifest.keys[0].retired = Some(long_ago);
+        assert_eq!(
+            keyring.verify::<TestClaims>(&old_token).unwrap(),
+            Err(KeyError::Retired)
+        );
+        keyring.rotate().unwrap();
+        assert_eq!(
+            keyring.verify::<TestClaims>(&old_token).unwrap(),
+            Err(KeyError::UnknownKid)
+        );
+        assert!(!key_path(&dir, &first).exists());
+        assert_eq!(keyring.verify(&new_token).unwrap(), Ok(claims()));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_foreign_tokens_are_refused() {
+        let (dir, keyring) = temp_keyring();
+        let (other_dir, other) = temp_keyring();
+        let token = other.sign(&claims()).unwrap();
+        assert_eq!(
+            keyring.verify::<TestClaims>(&token).unwrap(),
+            Err(KeyError::UnknownKid)
+        );
+
+        //right kid, wrong signature
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(keyring.active_kid().to_string());
+        let forged =
+            jsonwebtoken::encode(&header, &claims(), &other.keys[other.active_kid()].encoding)
+                .unwrap();
+        assert!(keyring.verify::<TestClaims>(&forged).is_err());
+
+        let no_kid = jsonwebtoken::encode(
+            &Header::new(Algorithm::EdDSA),
+            &claims(),
+            &keyring.keys[keyring.active_kid()].encoding,
+        )
+        .unwrap();
+        assert_eq!(
+            keyring.verify::<TestClaims>(&no_kid).unwrap(),
+            Err(KeyError::MissingKid)
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(other_dir).unwrap();
+    }
+}
@@ -1 +1,3 @@
 pub mod jwt;
+pub mod keyring;
+pub mod rate_limit;
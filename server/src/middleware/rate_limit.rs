@@ -0,0 +1,389 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Future, Ready},
+    net::IpAddr,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{header, StatusCode},
+    Error, HttpMessage, HttpResponse, ResponseError,
+};
+
+use crate::models::internal_models::{internal_user::InternalUser, shared::InternalUuid};
+
+//past this many buckets, full ones are dropped since they say nothing a new one wouldn't
+const MAX_BUCKETS: usize = 100_000;
+
+//failed logins an account gets before it starts locking
+pub const FREE_LOGIN_FAILURES: u32 = 5;
+//the first lockout, doubled with each further failure
+pub const BASE_LOCKOUT: Duration = Duration::from_secs(30);
+pub const MAX_LOCKOUT: Duration = Duration::from_secs(60 * 60);
+//a day without failures and the count starts over
+const FAILURE_MEMORY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    //the maps only hold counters, whatever a panicking thread left is still usable
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// How much a client may send: `burst` requests up front, then one more every
+/// `refill`.
+#[derive(Debug, Clone, Copy)]
+pub struct Limit {
+    pub burst: u32,
+    pub refill: Duration,
+}
+
+impl Limit {
+    pub fn per_minute(burst: u32, per_minute: u32) -> Limit {
+        Limit {
+            burst,
+            refill: Duration::from_secs(60) / per_minute,
+        }
+    }
+
+    pub fn per_hour(burst: u32, per_hour: u32) -> Limit {
+        Limit {
+            burst,
+            refill: Duration::from_secs(60 * 60) / per_hour,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(limit: &Limit, now: Instant) -> TokenBucket {
+        TokenBucket {
+            tokens: limit.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, limit: &Limit, now: Instant) {
+        let earned =
+            now.saturating_duration_since(self.updated).as_secs_f64() / limit.refill.as_secs_f64();
+        self.tokens = (self.tokens + earned).min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    //takes a token, or says how long until the next one
+    fn take(&mut self, limit: &Limit, now: Instant) -> Result<(), Duration> {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(limit.refill.mul_f64(1.0 - self.tokens))
+    }
+
+    fn is_full(&mut self, limit: &Limit, now: Instant) -> bool {
+        self.refill(limit, now);
+        self.tokens >= limit.burst as f64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Ip(IpAddr),
+    Account(String),
+}
+
+/// Rejected for sending too much, tells the client when to come back.
+#[derive(Debug)]
+pub struct TooManyRequests {
+    pub retry_after: Duration,
+}
+
+impl std::fmt::Display for TooManyRequests {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Too many requests, try again in {} seconds",
+            self.retry_after.as_secs().max(1)
+        )
+    }
+}
+
+impl ResponseError for TooManyRequests {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, self.retry_after.as_secs().max(1)))
+            .body(self.to_string())
+    }
+}
+
+/// Token bucket limits per IP, and per account once the jwt middleware knows who
+/// is asking. Routes without their own limit share the default one. Clones share
+/// their buckets, so every worker sees the same counts.
+#[derive(Clone)]
+pub struct RateLimit {
+    default: Limit,
+    routes: HashMap<String, Limit>,
+    trusted: Vec<IpAddr>,
+    buckets: Arc<Mutex<HashMap<(String, Client), TokenBucket>>>,
+}
+
+impl RateLimit {
+    pub fn new(default: Limit) -> RateLimit {
+        RateLimit {
+            default,
+            routes: HashMap::new(),
+            trusted: vec![],
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn route(mut self, path: &str, limit: Limit) -> RateLimit {
+        self.routes.insert(path.to_string(), limit);
+        self
+    }
+
+    /// Requests from `ip` only count against their account.
+    pub fn trust(mut self, ip: IpAddr) -> RateLimit {
+        self.trusted.push(ip);
+        self
+    }
+
+    fn limit_for(&self, route: &str) -> Limit {
+        *self.routes.get(route).unwrap_or(&self.default)
+    }
+
+    fn check(&self, path: &str, clients: &[Client], now: Instant) -> Result<(), Duration> {
+        let route = if self.routes.contains_key(path) {
+            path
+        } else {
+            ""
+        };
+        let limit = self.limit_for(route);
+        let mut buckets = lock(&self.buckets);
+        if buckets.len() > MAX_BUCKETS {
+            buckets.retain(|(route, _), bucket| !bucket.is_full(&self.limit_for(route), now));
+        }
+        for client in clients {
+            buckets
+                .entry((route.to_string(), client.clone()))
+                .or_insert_with(|| TokenBucket::full(&limit, now))
+                .take(&limit, now)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service,
+            limits: self.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    limits: RateLimit,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + 'static>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if req.method() == "OPTIONS" {
+            return Box::pin(self.service.call(req));
+        }
+
+        //the socket address, forwarding headers are whatever the client wants them to be
+        let mut clients = vec![];
+        if let Some(addr) = req.peer_addr() {
+            if !self.limits.trusted.contains(&addr.ip()) {
+                clients.push(Client::Ip(addr.ip()));
+            }
+        }
+        if let Some(uuid) = req.extensions().get::<InternalUuid<InternalUser>>() {
+            clients.push(Client::Account(uuid.id.clone()));
+        }
+
+        match self.limits.check(req.path(), &clients, Instant::now()) {
+            Ok(()) => Box::pin(self.service.call(req)),
+            Err(retry_after) => {
+                log::info!("Rate limited {:?} on {}", clients, req.path());
+                Box::pin(async move { Err(TooManyRequests { retry_after }.into()) })
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    last: Instant,
+}
+
+impl Failures {
+    fn locked_until(&self) -> Option<Instant> {
+        let over = self.count.checked_sub(FREE_LOGIN_FAILURES)?;
+        if over == 0 {
+            return None;
+        }
+        let lockout = BASE_LOCKOUT
+            .checked_mul(2u32.saturating_pow(over - 1))
+            .unwrap_or(MAX_LOCKOUT)
+            .min(MAX_LOCKOUT);
+        Some(self.last + lockout)
+    }
+}
+
+/// Failed logins per username, locking the name out for longer after each one past
+/// `FREE_LOGIN_FAILURES`. Names that don't exist lock the same way so the lockout
+/// doesn't tell them apart.
+#[derive(Default)]
+pub struct LoginLockouts {
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+impl LoginLockouts {
+    /// Ok, or how long the name is locked for.
+    pub fn check(&self, username: &str, now: Instant) -> Result<(), Duration> {
+        let failures = lock(&self.failures);
+        match failures.get(username).and_then(|f| f.locked_until()) {
+            Some(until) if until > now => Err(until - now),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn failed(&self, username: &str, now: Instant) {
+        let mut failures = lock(&self.failures);
+        if failures.len() > MAX_BUCKETS {
+            failures.retain(|_, f| now.saturating_duration_since(f.last) < FAILURE_MEMORY);
+        }
+        let entry = failures.entry(username.to_string()).or_insert(Failures {
+            count: 0,
+            last: now,
+        });
+        if now.saturating_duration_since(entry.last) >= FAILURE_MEMORY {
+            entry.count = 0;
+        }
+        entry.count += 1;
+        entry.last = now;
+    }
+
+    pub fn succeeded(&self, username: &str) {
+        lock(&self.failures).remove(username);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_up_to_burst() {
+        let limit = Limit::per_minute(3, 60);
+        let start = Instant::now();
+        let mut bucket = TokenBucket::full(&limit, start);
+        for _ in 0..3 {
+            assert!(bucket.take(&limit, start).is_ok());
+        }
+        let wait = bucket.take(&limit, start).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(1));
+
+        assert!(bucket.take(&limit, start + wait).is_ok());
+        assert!(bucket.take(&limit, start + wait).is_err());
+
+        //a long break only earns back the burst
+        let later = start + Duration::from_secs(3600);
+        assert!(bucket.is_full(&limit, later));
+        for _ in 0..3 {
+            assert!(bucket.take(&limit, later).is_ok());
+        }
+        assert!(bucket.take(&limit, later).is_err());
+    }
+
+    #[test]
+    fn test_limits_are_per_route_and_client() {
+        let limits =
+            RateLimit::new(Limit::per_minute(100, 100)).route("/login", Limit::per_hour(2, 2));
+        let now = Instant::now();
+        let me = [Client::Ip("10.0.0.1".parse().unwrap())];
+        let you = [Client::Ip("10.0.0.2".parse().unwrap())];
+
+        assert!(limits.check("/login", &me, now).is_ok());
+        assert!(limits.check("/login", &me, now).is_ok());
+        assert_eq!(
+            limits.check("/login", &me, now),
+            Err(Duration::from_secs(30 * 60))
+        );
+        assert!(limits.check("/login", &you, now).is_ok());
+        assert!(limits.check("/get_me", &me, now).is_ok());
+
+        //an account is limited wherever it connects from
+        let account = Client::Account("someone".to_string());
+        let phone = [me[0].clone(), account.clone()];
+        let laptop = [you[0].clone(), account];
+        assert!(limits.check("/get_me", &phone, now).is_ok());
+        for _ in 0..99 {
+            limits.check("/get_me", &laptop, now).unwrap();
+        }
+        assert!(limits.check("/get_me", &phone, now).is_err());
+    }
+
+    #[test]
+    fn test_lockout_doubles_and_resets() {
+        let lockouts = LoginLockouts::default();
+        let now = Instant::now();
+        for _ in 0..FREE_LOGIN_FAILURES {
+            assert!(lockouts.check("alice", now).is_ok());
+            lockouts.failed("alice", now);
+        }
+        assert!(lockouts.check("alice", now).is_ok());
+
+        lockouts.failed("alice", now);
+        assert_eq!(lockouts.check("alice", now), Err(BASE_LOCKOUT));
+        let now = now + BASE_LOCKOUT;
+        assert!(lockouts.check("alice", now).is_ok());
+        lockouts.failed("alice", now);
+        assert_eq!(lockouts.check("alice", now), Err(BASE_LOCKOUT * 2));
+        assert!(lockouts.check("bob", now).is_ok());
+
+        for _ in 0..20 {
+            lockouts.failed("alice", now);
+        }
+        assert_eq!(lockouts.check("alice", now), Err(MAX_LOCKOUT));
+
+        lockouts.succeeded("alice");
+        assert!(lockouts.check("alice", now).is_ok());
+    }
+}
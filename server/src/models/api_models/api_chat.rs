@@ -3,8 +3,11 @@ use std::error::Error;
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
 
-use crate::models::internal_models::{
-    internal_chat::InternalChat, internal_message::InternalMessage, internal_user::InternalUser,
+use crate::{
+    db::DB,
+    models::internal_models::{
+        internal_chat::InternalChat, internal_message::InternalMessage, internal_user::InternalUser,
+    },
 };
 
 use super::shared::ApiUuid;
@@ -14,6 +17,7 @@ pub struct ApiChat {
     pub uuid: ApiUuid<InternalChat>,
     pub users: Vec<ApiUuid<InternalUser>>,
     pub unread: u32, //same order as users
+    //every message, for clients that predate /get_messages_page
     pub messages: Vec<ApiUuid<InternalMessage>>,
     pub most_recent_message: String,
     pub most_recent_sender: Option<ApiUuid<InternalUser>>,
@@ -21,18 +25,23 @@ pub struct ApiChat {
 }
 
 impl ApiChat {
-    pub fn from_internal(chat: InternalChat, user: &InternalUser) -> Result<Self, Box<dyn Error>> {
+    pub fn from_internal(
+        chat: InternalChat,
+        user: &InternalUser,
+        db: &DB,
+    ) -> Result<Self, Box<dyn Error>> {
         let user_index = chat
             .users
             .iter()
             .position(|u| u == &user.uuid)
             .ok_or("User not found")?;
+        let messages = InternalMessage::in_chat(db, &chat.uuid)?;
 
         Ok(ApiChat {
             uuid: chat.uuid.into(),
             users: chat.users.into_iter().map(|u| u.into()).collect(),
             unread: chat.unread[user_index],
-            messages: chat.messages.into_iter().map(|m| m.into()).collect(),
+            messages: messages.into_iter().map(|m| m.into()).collect(),
             most_recent_message: chat.most_recent_message.clone(),
             most_recent_sender: chat.most_recent_sender.map(|s| s.into()),
             most_recent_message_sent_at: chat.most_recent_message_sent_at,
@@ -1,9 +1,10 @@
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
 
-use crate::models::internal_models::internal_image::Access;
+use crate::db::DB;
+use crate::models::internal_models::internal_image::{Access, ImageSize};
 use crate::models::internal_models::internal_user::InternalUser;
-use crate::models::internal_models::shared::InternalUuid;
+use crate::renditions::Renditions;
 use crate::test::fake::Gen;
 
 use super::super::internal_models::internal_image::InternalImage;
@@ -19,7 +20,6 @@ impl Gen<'_, bool> for ApiImageWritable {
         //get bytes from test.jpeg in this src folder, it's not a string, so we can't use include_str!
         const TEST_IMG_BYTES: &[u8] = include_bytes!("test.jpeg");
         ApiImageWritable {
-            #[allow(deprecated)]
             content: TEST_IMG_BYTES.to_vec(),
         }
     }
@@ -30,7 +30,6 @@ impl ApiImageWritable {
         //get bytes from test.jpeg in this src folder, it's not a string, so we can't use include_str!
         const ADMIN_IMG_BYTES: &[u8] = include_bytes!("admin.jpeg");
         ApiImageWritable {
-            #[allow(deprecated)]
             content: ADMIN_IMG_BYTES.to_vec(),
         }
     }
@@ -39,15 +38,14 @@ impl ApiImageWritable {
         Self { content }
     }
 
-    pub fn to_internal(self, access: Access) -> Result<InternalImage, Box<dyn std::error::Error>> {
-        #[allow(deprecated)]
-        let content = self.content;
-
-        Ok(InternalImage {
-            uuid: InternalUuid::new(),
-            content,
-            access,
-        })
+    /// Decodes the upload and stores it re-encoded, the bytes sent are never kept.
+    pub fn to_internal(
+        self,
+        db: &DB,
+        access: Access,
+    ) -> Result<InternalImage, Box<dyn std::error::Error>> {
+        let renditions = Renditions::from_upload(&self.content).map_err(|e| e.message())?;
+        InternalImage::new(db, &renditions, access)
     }
 }
 
@@ -59,10 +57,12 @@ pub struct ApiImage {
 
 impl ApiImage {
     pub fn from_internal(
+        db: &DB,
         image: InternalImage,
         user: Option<&InternalUser>,
+        size: ImageSize,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        match image.access {
+        match &image.access {
             Access::Everyone => (),
             Access::UserList(user_list) => {
                 if user.is_none() {
@@ -75,7 +75,7 @@ impl ApiImage {
         }
 
         #[allow(deprecated)]
-        let b64_content = base64::encode(&image.content);
+        let b64_content = base64::encode(image.rendition(db, size)?);
 
         Ok(Self {
             uuid: image.uuid.into(),
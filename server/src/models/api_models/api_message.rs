@@ -4,8 +4,11 @@ use serde::{Deserialize, Serialize};
 use crate::{
     db::DB,
     models::internal_models::{
-        internal_chat::InternalChat, internal_image::InternalImage,
-        internal_message::InternalMessage, internal_user::InternalUser, shared::InternalUuid,
+        internal_chat::InternalChat,
+        internal_image::InternalImage,
+        internal_message::{InternalMessage, ReadReceipt},
+        internal_user::InternalUser,
+        shared::InternalUuid,
     },
 };
 
@@ -18,10 +21,25 @@ pub struct ApiMessage {
     pub author: ApiUuid<InternalUser>,
     pub content: String,
     pub image: Option<ApiUuid<InternalImage>>,
-    pub read_by: Vec<ApiUuid<InternalUser>>,
+    pub read_by: Vec<ApiReadReceipt>,
     pub edited: bool,
 }
 
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ApiReadReceipt {
+    pub user: ApiUuid<InternalUser>,
+    pub read_at: i64,
+}
+
+impl From<ReadReceipt> for ApiReadReceipt {
+    fn from(receipt: ReadReceipt) -> Self {
+        Self {
+            user: receipt.user.into(),
+            read_at: receipt.read_at,
+        }
+    }
+}
+
 impl From<InternalMessage> for ApiMessage {
     fn from(message: InternalMessage) -> Self {
         Self {
@@ -30,7 +48,7 @@ impl From<InternalMessage> for ApiMessage {
             author: message.author.into(),
             content: message.content,
             image: message.image.map(|i| i.into()),
-            read_by: message.read_by.into_iter().map(|u| u.into()).collect(),
+            read_by: message.read_by.into_iter().map(|r| r.into()).collect(),
             edited: message.edited,
         }
     }
@@ -70,25 +88,32 @@ impl ApiMessageWritable {
                     return Err(actix_web::error::ErrorBadRequest("Not the author"));
                 }
                 //is the message in the chat?
-                if !chat.messages.contains(&message.uuid) {
+                if message.chat != chat.uuid {
                     return Err(actix_web::error::ErrorBadRequest("Message not in chat"));
                 }
+                //sent_at stays, it's the message's place in the chat
                 let mut message = message;
-                message.sent_at = chrono::Utc::now().timestamp();
                 message.content = self.content;
                 message.edited = true;
                 message
             }
-            None => InternalMessage {
-                uuid: InternalUuid::new(),
-                sent_at: chrono::Utc::now().timestamp(),
-                author: author.clone(),
-                content: self.content,
-                edited: false,
-                image: None,
-                read_by: vec![author.clone()],
-                chat: chat.uuid.clone(),
-            },
+            None => {
+                let sent_at = chrono::Utc::now().timestamp();
+                InternalMessage {
+                    //breaks ties between messages sent in the same second
+                    uuid: InternalUuid::new_ordered(),
+                    sent_at,
+                    author: author.clone(),
+                    content: self.content,
+                    edited: false,
+                    image: None,
+                    read_by: vec![ReadReceipt {
+                        user: author.clone(),
+                        read_at: sent_at,
+                    }],
+                    chat: chat.uuid.clone(),
+                }
+            }
         };
 
         //is there an image
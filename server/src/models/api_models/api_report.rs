@@ -0,0 +1,125 @@
+use paperclip::actix::Apiv2Schema;
+use serde::Serialize;
+
+use crate::models::internal_models::{
+    internal_chat::InternalChat,
+    internal_report::{
+        Evidence, InternalReport, ModAction, ModeratorNote, ReportKind, ReportStatus,
+        ReportedMessage,
+    },
+    internal_user::InternalUser,
+};
+
+use super::shared::ApiUuid;
+
+#[allow(deprecated)]
+fn b64(bytes: &[u8]) -> String {
+    base64::encode(bytes)
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ApiReportedMessage {
+    pub author: ApiUuid<InternalUser>,
+    pub sent_at: i64,
+    pub content: String,
+    pub image: Option<String>,
+}
+
+/// Images are base64 jpegs, like `ApiImage::content`.
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ApiEvidence {
+    pub screenshot: String,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub user_images: Vec<String>,
+    pub messages: Vec<ApiReportedMessage>,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ApiModeratorNote {
+    pub author: ApiUuid<InternalUser>,
+    pub written_at: i64,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ApiResolution {
+    pub by: ApiUuid<InternalUser>,
+    pub at: i64,
+    pub action: ModAction,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ApiReport {
+    pub uuid: ApiUuid<InternalReport>,
+    pub kind: ReportKind,
+    pub status: ReportStatus,
+    pub created_at: i64,
+    pub reporter: Option<ApiUuid<InternalUser>>,
+    pub target_user: Option<ApiUuid<InternalUser>>,
+    pub target_chat: Option<ApiUuid<InternalChat>>,
+    pub content: String,
+    pub platform: String,
+    //left out of listings, it's most of the report's size
+    pub evidence: Option<ApiEvidence>,
+    pub assignee: Option<ApiUuid<InternalUser>>,
+    pub notes: Vec<ApiModeratorNote>,
+    pub resolution: Option<ApiResolution>,
+}
+
+impl From<ReportedMessage> for ApiReportedMessage {
+    fn from(message: ReportedMessage) -> Self {
+        Self {
+            author: message.author.into(),
+            sent_at: message.sent_at,
+            content: message.content,
+            image: message.image.as_deref().map(b64),
+        }
+    }
+}
+
+impl From<Evidence> for ApiEvidence {
+    fn from(evidence: Evidence) -> Self {
+        Self {
+            screenshot: b64(&evidence.screenshot),
+            display_name: evidence.display_name,
+            description: evidence.description,
+            user_images: evidence.user_images.iter().map(|i| b64(i)).collect(),
+            messages: evidence.messages.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<ModeratorNote> for ApiModeratorNote {
+    fn from(note: ModeratorNote) -> Self {
+        Self {
+            author: note.author.into(),
+            written_at: note.written_at,
+            text: note.text,
+        }
+    }
+}
+
+impl ApiReport {
+    pub fn from_internal(report: InternalReport, with_evidence: bool) -> Self {
+        Self {
+            uuid: report.uuid.into(),
+            kind: report.kind,
+            status: report.status,
+            created_at: report.created_at,
+            reporter: report.reporter.map(Into::into),
+            target_user: report.target_user.map(Into::into),
+            target_chat: report.target_chat.map(Into::into),
+            content: report.content,
+            platform: report.platform,
+            evidence: with_evidence.then(|| report.evidence.into()),
+            assignee: report.assignee.map(Into::into),
+            notes: report.notes.into_iter().map(Into::into).collect(),
+            resolution: report.resolution.map(|resolution| ApiResolution {
+                by: resolution.by.into(),
+                at: resolution.at,
+                action: resolution.action,
+            }),
+        }
+    }
+}
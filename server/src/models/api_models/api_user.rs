@@ -5,10 +5,10 @@ use crate::{
     models::internal_models::{
         internal_chat::InternalChat,
         internal_image::{Access, InternalImage},
-        internal_prefs::{LabeledPreferenceRange, LabeledProperty, PreferenceRange},
-        internal_prefs_config::PREFS_CONFIG,
+        internal_prefs::{LabeledPreferenceRange, LabeledProperty},
+        internal_prefs_config::{prefs_config, prefs_schema},
         internal_user::{BotProps, InternalRating, InternalUser, Notification, TimestampedAction},
-        migration::migration::get_admin_uuid,
+        migration::migration::{get_admin_uuid, initial_role},
         shared::{InternalUuid, Save},
     },
     test::fake::Gen,
@@ -133,8 +133,6 @@ impl ApiUserWritable {
             return Err("No password for no internal user".into());
         };
 
-        self.handle_image_updates(db, &internal_user)?;
-
         let owned_images = internal_user
             .as_ref()
             .map(|user| user.owned_images.clone())
@@ -154,14 +152,21 @@ impl ApiUserWritable {
                 .and_then(|user| user.bot_props.clone())
         };
 
+        //staff roles are handed out by an admin, never through the profile
+        let role = match &internal_user {
+            Some(user) => user.role,
+            None => initial_role(&internal_uuid),
+        };
+
         self.set_age();
 
         if chats.is_empty() && !is_admin {
-            let mut admin = db.get_admin()?;
-            let mut admin_chats = admin.chats;
+            let mut admin_chats = vec![];
             self.create_admin_chat(db, &internal_uuid, &mut chats, &mut admin_chats)?;
-            admin.chats = admin_chats;
-            admin.save(db)?;
+            //every signup adds to the admin, so this has to survive concurrent signups
+            db.update(&get_admin_uuid(), |admin: &mut InternalUser| {
+                admin.chats.extend(admin_chats.iter().cloned())
+            })?;
         }
 
         Ok(InternalUser {
@@ -184,32 +189,20 @@ impl ApiUserWritable {
             actions,
             notifications,
             bot_props,
+            role,
         })
     }
 
-    fn handle_image_updates(
-        &self,
-        db: &DB,
-        internal_user: &Option<InternalUser>,
-    ) -> Result<(), Box<dyn Error>> {
-        if let Some(internal_user) = internal_user {
-            let old_images = internal_user.images.clone();
-            for image in old_images {
-                if !self.images.contains(&image.clone().into()) {
-                    if let Some(image) = image.load(db)? {
-                        image.uuid.delete(db)?;
-                    } else {
-                        return Err("Image not found, can't delete, this shouldn't happen".into());
-                    }
-                }
-            }
-        }
-        Ok(())
-    }
-
     fn validate_props_and_prefs(&self) -> Result<(), Box<dyn Error>> {
-        if self.props.len() != PREFS_CONFIG.len() || self.prefs.len() != PREFS_CONFIG.len() {
-            return Err("Invalid number of props or prefs".into());
+        if !prefs_schema().is_laid_out(&self.props, &self.prefs) {
+            return Err("Props and prefs don't match the prefs config".into());
+        }
+        if self
+            .prefs
+            .iter()
+            .any(|pref| !pref.weight.is_finite() || pref.weight < 0.0)
+        {
+            return Err("Pref weights have to be positive numbers".into());
         }
         Ok(())
     }
@@ -304,28 +297,13 @@ impl ApiUserWritable {
         }
     }
 
+    //matched by name, missing ones get their defaults and unknown ones are dropped
     pub fn fill_props(&mut self) {
-        let last_filled = self.props.len();
-        for i in last_filled..PREFS_CONFIG.len() {
-            let pref = &PREFS_CONFIG[i];
-            self.props.push(LabeledProperty {
-                name: pref.name.to_string(),
-                value: pref.default.unwrap_or(-32768),
-            });
-        }
+        self.props = prefs_schema().remap_props(&self.props);
     }
 
     pub fn fill_prefs(&mut self) {
-        let last_filled = self.prefs.len();
-        for i in last_filled..PREFS_CONFIG.len() {
-            self.prefs.push(LabeledPreferenceRange {
-                name: PREFS_CONFIG[i].name.to_string(),
-                range: PreferenceRange {
-                    min: -32768,
-                    max: 32767,
-                },
-            });
-        }
+        self.prefs = prefs_schema().remap_prefs(&self.prefs);
     }
 }
 
@@ -354,7 +332,7 @@ impl Gen<'_, DB> for ApiUserWritable {
         let mut uuids = Vec::with_capacity(6);
         for _ in 0..2 {
             let image = ApiImageWritable::gen(&true)
-                .to_internal(Access::Everyone)
+                .to_internal(db, Access::Everyone)
                 .unwrap();
             let img_uuid = image.save(db).unwrap();
             uuids.push(img_uuid);
@@ -371,25 +349,31 @@ impl Gen<'_, DB> for ApiUserWritable {
         let latitudei16 = to_i16(latitude, -90.0, 90.0);
         let longitude = -73.567253;
         let longitudei16 = to_i16(longitude, -180.0, 180.0);
-        let mut props = PREFS_CONFIG
+        let mut props = prefs_config()
             .iter()
             .map(|config| LabeledProperty {
-                name: config.name.to_string(),
+                name: config.name.clone(),
                 value: config.sample(&mut rng),
             })
             .collect::<Vec<_>>();
 
-        props[0].value = get_age(birthdate);
-        props[1].value = percent_male;
-        props[2].value = percent_female;
-        props[3].value = latitudei16;
-        props[4].value = longitudei16;
+        for (name, value) in [
+            ("age", get_age(birthdate)),
+            ("percent_male", percent_male),
+            ("percent_female", percent_female),
+            ("latitude", latitudei16),
+            ("longitude", longitudei16),
+        ] {
+            if let Some(prop) = props.iter_mut().find(|p| p.name == name) {
+                prop.value = value;
+            }
+        }
 
-        let prefs = PREFS_CONFIG
+        let prefs = prefs_config()
             .iter()
-            .map(|config| LabeledPreferenceRange {
-                name: config.name.to_string(),
-                range: config.sample_range(&props, &mut rng),
+            .map(|config| {
+                let range = config.sample_range(&props, &mut rng);
+                LabeledPreferenceRange::sample(config.name.clone(), range, &mut rng)
             })
             .collect();
 
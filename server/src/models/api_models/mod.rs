@@ -2,5 +2,6 @@ pub mod api_chat;
 pub mod api_image;
 pub mod api_message;
 pub mod api_rating;
+pub mod api_report;
 pub mod api_user;
 pub mod shared;
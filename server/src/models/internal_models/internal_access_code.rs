@@ -1,10 +1,14 @@
 use std::error::Error;
 
 use crate::db::DB;
+use crate::index::Index;
+use crate::transaction::{SaveTxn, Txn, TxnResult};
 
 use super::shared::{Insertable, InternalUuid, Save};
 use rand::Rng;
 
+pub const ACCESS_CODES_BY_CODE: Index = Index::unique("access_code.code");
+
 #[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, serde::Serialize, paperclip::actix::Apiv2Schema)]
 #[archive(compare(PartialEq), check_bytes)]
 pub struct InternalAccessCode {
@@ -40,21 +44,34 @@ impl Insertable for InternalAccessCode {
     fn version() -> u64 {
         0
     }
+
+    fn indexes() -> &'static [Index] {
+        &[ACCESS_CODES_BY_CODE]
+    }
+
+    fn index_values(&self) -> Vec<(&'static Index, String)> {
+        vec![(&ACCESS_CODES_BY_CODE, self.code.clone())]
+    }
 }
 
 impl Save for InternalAccessCode {
     fn save(self, db: &crate::db::DB) -> Result<InternalUuid<InternalAccessCode>, Box<dyn Error>> {
-        db.write_index("access_code.code", &self.code, &self.uuid)?;
         self.uuid.write(&self, db)
     }
 }
 
+impl SaveTxn for InternalAccessCode {
+    fn save_txn(&self, txn: &Txn) -> TxnResult<()> {
+        txn.write(&self.uuid, self)
+    }
+}
+
 impl DB {
     pub fn get_access_code_by_code(
         &self,
-        username: &String,
+        username: &str,
     ) -> Result<Option<InternalAccessCode>, Box<dyn Error>> {
-        let uuid = self.read_index::<InternalAccessCode>("access_code.code", username)?;
+        let uuid = ACCESS_CODES_BY_CODE.get::<InternalAccessCode>(self, username)?;
         match uuid {
             Some(uuid) => uuid.load(self),
             None => Ok(None),
@@ -2,7 +2,11 @@ use std::error::Error;
 
 use crate::db::DB;
 use crate::models::api_models::api_message::ApiMessageWritable;
+use crate::push::PushEvent;
+use crate::transaction::{SaveTxn, Txn, TxnResult};
 
+use super::internal_message::ReadReceipt;
+use super::internal_user::Notification;
 use super::migration::migration::get_admin_uuid;
 use super::shared::{Insertable, InternalUuid, Save};
 use super::{internal_message::InternalMessage, internal_user::InternalUser};
@@ -13,7 +17,6 @@ pub struct InternalChat {
     pub uuid: InternalUuid<InternalChat>,
     pub users: Vec<InternalUuid<InternalUser>>,
     pub unread: Vec<u32>, //same order as users
-    pub messages: Vec<InternalUuid<InternalMessage>>,
     pub most_recent_message: String,
     pub most_recent_sender: Option<InternalUuid<InternalUser>>,
     pub most_recent_message_sent_at: i64,
@@ -27,7 +30,6 @@ impl InternalChat {
             uuid: InternalUuid::<InternalChat>::new(),
             users,
             unread: vec![0; users_len],
-            messages: vec![],
             most_recent_message: "No messages yet".to_string(),
             most_recent_sender: None,
             most_recent_message_sent_at: now,
@@ -45,14 +47,153 @@ impl InternalChat {
     }
 }
 
+/// Why marking a chat read was turned down.
+#[derive(Debug, PartialEq)]
+pub enum ReadError {
+    ChatNotFound,
+    NotInChat,
+    MessageNotInChat,
+}
+
+impl ReadError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ReadError::ChatNotFound => "Chat not found",
+            ReadError::NotInChat => "User not in chat",
+            ReadError::MessageNotInChat => "Message not in chat",
+        }
+    }
+}
+
+impl InternalChat {
+    /// Gives the user a read receipt on every message up to and including `up_to`
+    /// and clears their notifications for them. Returns how many are still unread.
+    pub fn mark_read(
+        db: &DB,
+        chat_uuid: &InternalUuid<InternalChat>,
+        user_uuid: &InternalUuid<InternalUser>,
+        up_to: &InternalUuid<InternalMessage>,
+    ) -> Result<Result<u32, ReadError>, Box<dyn Error>> {
+        let read_at = chrono::Utc::now().timestamp();
+        match chat_uuid.load(db)? {
+            Some(chat) if chat.users.contains(user_uuid) => {}
+            Some(_) => return Ok(Err(ReadError::NotInChat)),
+            None => return Ok(Err(ReadError::ChatNotFound)),
+        }
+        let up_to = match up_to.load(db)? {
+            Some(message) if &message.chat == chat_uuid => message,
+            _ => return Ok(Err(ReadError::MessageNotInChat)),
+        };
+
+        //receipts only ever move forward, so stop at the last message already read.
+        //the index can't be read inside the transaction, so the walk happens out here
+        let mut to_read = vec![];
+        let earlier = up_to.earlier(db)?;
+        for message_uuid in std::iter::once(Ok(up_to.uuid.clone())).chain(earlier) {
+            let message_uuid = message_uuid?;
+            let message = match message_uuid.load(db)? {
+                Some(message) => message,
+                None => continue,
+            };
+            if message.is_read_by(user_uuid) {
+                //their own messages are read from the start, they say nothing
+                if &message.author == user_uuid {
+                    continue;
+                }
+                break;
+            }
+            to_read.push(message_uuid);
+        }
+        let later = up_to.count_later(db)? as u32;
+
+        db.transaction(|txn| {
+            let mut chat = match txn.read(chat_uuid)? {
+                Some(chat) => chat,
+                None => return Ok(Err(ReadError::ChatNotFound)),
+            };
+            let user_idx = match chat.users.iter().position(|u| u == user_uuid) {
+                Some(user_idx) => user_idx,
+                None => return Ok(Err(ReadError::NotInChat)),
+            };
+
+            let mut newly_read = vec![];
+            for message_uuid in to_read.iter() {
+                let mut message: InternalMessage = match txn.read(message_uuid)? {
+                    Some(message) => message,
+                    None => continue,
+                };
+                if message.is_read_by(user_uuid) {
+                    continue;
+                }
+                message.read_by.push(ReadReceipt {
+                    user: user_uuid.clone(),
+                    read_at,
+                });
+                txn.write(message_uuid, &message)?;
+                newly_read.push(message_uuid.clone());
+            }
+
+            //whatever is still unread comes after the mark
+            let unread = chat.unread[user_idx].min(later);
+            if unread != chat.unread[user_idx] {
+                chat.unread[user_idx] = unread;
+                txn.write(chat_uuid, &chat)?;
+            }
+
+            if !newly_read.is_empty() {
+                if let Some(mut user) = txn.read::<InternalUser>(user_uuid)? {
+                    user.notifications.retain(|n| match n {
+                        Notification::UnreadMessage(message) => !newly_read.contains(message),
+                        _ => true,
+                    });
+                    txn.write(user_uuid, &user)?;
+                }
+            }
+
+            let any_read = !newly_read.is_empty();
+            let members = chat.users.clone();
+            let chat_uuid = chat_uuid.clone();
+            let user_uuid = user_uuid.clone();
+            let up_to = up_to.uuid.clone();
+            txn.after_commit(move |db| {
+                if any_read {
+                    let event = PushEvent::Read {
+                        chat: chat_uuid.clone().into(),
+                        user: user_uuid.clone().into(),
+                        message: up_to.into(),
+                        read_at,
+                    };
+                    for member in members.iter().filter(|m| *m != &user_uuid) {
+                        db.push.push(member, &event);
+                    }
+                }
+                //the user's other devices can update their badge
+                let event = PushEvent::Unread {
+                    chat: chat_uuid.into(),
+                    unread,
+                };
+                db.push.push(&user_uuid, &event);
+                Ok(())
+            });
+            Ok(Ok(unread))
+        })
+    }
+}
+
 impl Save for InternalChat {
     fn save(self, db: &DB) -> Result<InternalUuid<InternalChat>, Box<dyn Error>> {
         self.uuid.write(&self, db)
     }
 }
 
+impl SaveTxn for InternalChat {
+    fn save_txn(&self, txn: &Txn) -> TxnResult<()> {
+        txn.write(&self.uuid, self)
+    }
+}
+
 impl Insertable for InternalChat {
     fn version() -> u64 {
-        0
+        1
     }
 }
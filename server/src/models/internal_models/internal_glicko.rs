@@ -0,0 +1,110 @@
+use std::error::Error;
+
+use super::{
+    internal_user::InternalUser,
+    shared::{Insertable, InternalUuid, Save},
+};
+
+use crate::{
+    db::DB,
+    glicko::Glicko,
+    transaction::{SaveTxn, Txn, TxnResult, Versioned, ABSENT_VERSION},
+};
+
+/// A user's Glicko-2 rating, stored under the same id as the user.
+#[derive(
+    Debug,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    serde::Serialize,
+    paperclip::actix::Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct InternalGlicko {
+    pub uuid: InternalUuid<InternalGlicko>,
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub games: u32,
+}
+
+impl InternalGlicko {
+    pub fn new(user: &InternalUuid<InternalUser>) -> InternalGlicko {
+        InternalGlicko::from_glicko(user, &Glicko::default(), 0)
+    }
+
+    pub fn from_glicko(
+        user: &InternalUuid<InternalUser>,
+        glicko: &Glicko,
+        games: u32,
+    ) -> InternalGlicko {
+        InternalGlicko {
+            uuid: InternalGlicko::uuid_for(user),
+            rating: glicko.rating,
+            deviation: glicko.deviation,
+            volatility: glicko.volatility,
+            games,
+        }
+    }
+
+    pub fn uuid_for(user: &InternalUuid<InternalUser>) -> InternalUuid<InternalGlicko> {
+        user.id.clone().into()
+    }
+
+    pub fn glicko(&self) -> Glicko {
+        Glicko {
+            rating: self.rating,
+            deviation: self.deviation,
+            volatility: self.volatility,
+        }
+    }
+
+    pub fn record_game(&mut self, glicko: &Glicko) {
+        self.rating = glicko.rating;
+        self.deviation = glicko.deviation;
+        self.volatility = glicko.volatility;
+        self.games += 1;
+    }
+}
+
+impl Save for InternalGlicko {
+    fn save(self, db: &DB) -> Result<InternalUuid<InternalGlicko>, Box<dyn Error>> {
+        self.uuid.write(&self, db)
+    }
+}
+
+impl SaveTxn for InternalGlicko {
+    fn save_txn(&self, txn: &Txn) -> TxnResult<()> {
+        txn.write(&self.uuid, self)
+    }
+}
+
+impl Insertable for InternalGlicko {
+    fn version() -> u64 {
+        0
+    }
+}
+
+impl DB {
+    /// The user's rating, users that were never rated get a fresh one.
+    pub fn get_glicko(
+        &self,
+        user: &InternalUuid<InternalUser>,
+    ) -> Result<InternalGlicko, Box<dyn Error>> {
+        Ok(self.get_glicko_versioned(user)?.value)
+    }
+
+    pub fn get_glicko_versioned(
+        &self,
+        user: &InternalUuid<InternalUser>,
+    ) -> Result<Versioned<InternalGlicko>, Box<dyn Error>> {
+        match self.load_versioned(&InternalGlicko::uuid_for(user))? {
+            Some(glicko) => Ok(glicko),
+            None => Ok(Versioned {
+                value: InternalGlicko::new(user),
+                version: ABSENT_VERSION,
+            }),
+        }
+    }
+}
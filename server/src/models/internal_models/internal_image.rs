@@ -1,13 +1,20 @@
-use std::error::Error;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+};
 
 use super::{
     internal_user::InternalUser,
     shared::{Insertable, InternalUuid, Save},
 };
 
+use crate::blobs::IMAGES_BY_BLOB;
 use crate::db::DB;
+use crate::index::Index;
+use crate::renditions::{hamming_distance, Renditions};
+use crate::transaction::{SaveTxn, Txn, TxnResult};
 
-#[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, serde::Serialize, paperclip::actix::Apiv2Schema)]
+#[derive(Debug, Clone, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, serde::Serialize, paperclip::actix::Apiv2Schema)]
 #[archive(compare(PartialEq), check_bytes)]
 pub enum Access {
     Everyone,
@@ -23,22 +30,237 @@ impl Access {
     }
 }
 
-#[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, serde::Serialize, paperclip::actix::Apiv2Schema)]
+/// Which rendition of an image to send.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, paperclip::actix::Apiv2Schema)]
+pub enum ImageSize {
+    Thumbnail,
+    Card,
+    #[default]
+    Full,
+}
+
+//under "{band}/{byte of the dhash in that band}" for every band, see `near_duplicates`
+const IMAGES_BY_DHASH: Index = Index::multi("images.dhash");
+const DHASH_BANDS: u32 = 8;
+//photos this close are taken to be the same one
+pub const MAX_DHASH_DISTANCE: u32 = 6;
+
+fn dhash_band(dhash: u64, band: u32) -> String {
+    format!("{}/{:02x}", band, (dhash >> (band * 8)) as u8)
+}
+
+#[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
 #[archive(compare(PartialEq), check_bytes)]
 pub struct InternalImage {
     pub uuid: InternalUuid<InternalImage>,
-    pub content: Vec<u8>,
+    //content hash of the renditions in the blob store
+    pub blob: String,
     pub access: Access,
+    //profile slots, uploader's library included, and messages showing it
+    pub refs: u32,
+    pub created_at: i64,
+    //perceptual hash, None for the odd image kept as uploaded because it didn't decode
+    pub dhash: Option<u64>,
+}
+
+impl InternalImage {
+    /// A new image with no references yet, its renditions go to the blob store right away.
+    pub fn new(
+        db: &DB,
+        renditions: &Renditions,
+        access: Access,
+    ) -> Result<InternalImage, Box<dyn Error>> {
+        Ok(InternalImage {
+            uuid: InternalUuid::new(),
+            blob: db.put_blob(renditions)?,
+            access,
+            refs: 0,
+            created_at: chrono::Utc::now().timestamp(),
+            dhash: renditions.dhash(),
+        })
+    }
+
+    /// Every other image within `MAX_DHASH_DISTANCE` of `dhash`. Images that far apart
+    /// still agree on at least one of the bands, so only those have to be compared.
+    pub fn near_duplicates(db: &DB, dhash: u64) -> Result<Vec<InternalImage>, Box<dyn Error>> {
+        let mut seen = HashSet::new();
+        let mut images = vec![];
+        for band in 0..DHASH_BANDS {
+            let candidates: Vec<InternalUuid<InternalImage>> =
+                IMAGES_BY_DHASH.prefix(db, &format!("{}/", dhash_band(dhash, band)))?;
+            for uuid in candidates {
+                if !seen.insert(uuid.clone()) {
+                    continue;
+                }
+                //it may have been deleted since the scan
+                let image = match uuid.load(db)? {
+                    Some(image) => image,
+                    None => continue,
+                };
+                match image.dhash {
+                    Some(other) if hamming_distance(dhash, other) <= MAX_DHASH_DISTANCE => {
+                        images.push(image)
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(images)
+    }
+
+    pub fn rendition(&self, db: &DB, size: ImageSize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let renditions = db
+            .read_blob(&self.blob)?
+            .ok_or(format!("Blob of image {} not found", self.uuid.id))?;
+        Ok(match size {
+            ImageSize::Thumbnail => renditions.thumbnail,
+            ImageSize::Card => renditions.card,
+            ImageSize::Full => renditions.full,
+        })
+    }
+
+    /// Moves the refs of the image by `delta`, a missing image is left alone.
+    pub fn add_refs_txn(
+        txn: &Txn,
+        uuid: &InternalUuid<InternalImage>,
+        delta: i32,
+    ) -> TxnResult<()> {
+        if let Some(mut image) = txn.read(uuid)? {
+            image.refs = image.refs.saturating_add_signed(delta);
+            txn.write(uuid, &image)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the image if nothing shows it, returns whether it did.
+    pub fn delete_if_unreferenced(
+        db: &DB,
+        uuid: &InternalUuid<InternalImage>,
+    ) -> Result<bool, Box<dyn Error>> {
+        db.transaction(|txn| match txn.read(uuid)? {
+            Some(image) if image.refs == 0 => txn.delete(uuid),
+            _ => Ok(false),
+        })
+    }
 }
 
 impl Save for InternalImage {
     fn save(self, db: &DB) -> Result<InternalUuid<InternalImage>, Box<dyn Error>> {
-        self.uuid.write(&self, db)
+        db.transaction(|txn| self.save_txn(txn))?;
+        Ok(self.uuid)
+    }
+}
+
+impl SaveTxn for InternalImage {
+    fn save_txn(&self, txn: &Txn) -> TxnResult<()> {
+        txn.write(&self.uuid, self)
     }
 }
 
 impl Insertable for InternalImage {
     fn version() -> u64 {
-        0
+        3
+    }
+
+    fn indexes() -> &'static [Index] {
+        &[IMAGES_BY_BLOB, IMAGES_BY_DHASH]
+    }
+
+    fn index_values(&self) -> Vec<(&'static Index, String)> {
+        let mut values = vec![(&IMAGES_BY_BLOB, self.blob.clone())];
+        if let Some(dhash) = self.dhash {
+            for band in 0..DHASH_BANDS {
+                values.push((&IMAGES_BY_DHASH, dhash_band(dhash, band)));
+            }
+        }
+        values
+    }
+}
+
+/// Users whose profiles show near identical photos, and the photos.
+#[derive(Debug)]
+pub struct PhotoCluster {
+    pub users: Vec<InternalUuid<InternalUser>>,
+    pub images: Vec<InternalUuid<InternalImage>>,
+}
+
+//union find over the photos, each points towards its cluster's root
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Groups the users showing near identical photos, biggest groups first. A photo
+/// only one user shows, however often, isn't a group.
+pub fn photo_clusters(
+    db: &DB,
+    users: &[InternalUser],
+) -> Result<Vec<PhotoCluster>, Box<dyn Error>> {
+    //(user, image, dhash) for every photo on a profile
+    let mut photos: Vec<(usize, InternalUuid<InternalImage>, u64)> = vec![];
+    let mut dhashes: HashMap<InternalUuid<InternalImage>, Option<u64>> = HashMap::new();
+    for (i, user) in users.iter().enumerate() {
+        let shown: HashSet<_> = user
+            .images
+            .iter()
+            .chain(user.preview_image.iter())
+            .collect();
+        for image in shown {
+            let dhash = match dhashes.get(image) {
+                Some(dhash) => *dhash,
+                None => {
+                    let dhash = image.load(db)?.and_then(|image| image.dhash);
+                    dhashes.insert(image.clone(), dhash);
+                    dhash
+                }
+            };
+            if let Some(dhash) = dhash {
+                photos.push((i, image.clone(), dhash));
+            }
+        }
+    }
+
+    //same as `near_duplicates`, only photos sharing a band are compared
+    let mut bands: HashMap<String, Vec<usize>> = HashMap::new();
+    for (p, (_, _, dhash)) in photos.iter().enumerate() {
+        for band in 0..DHASH_BANDS {
+            bands.entry(dhash_band(*dhash, band)).or_default().push(p);
+        }
+    }
+    let mut parents: Vec<usize> = (0..photos.len()).collect();
+    for members in bands.values() {
+        for (n, &a) in members.iter().enumerate() {
+            for &b in &members[n + 1..] {
+                if hamming_distance(photos[a].2, photos[b].2) <= MAX_DHASH_DISTANCE {
+                    let root = find(&mut parents, a);
+                    parents[root] = find(&mut parents, b);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, PhotoCluster> = HashMap::new();
+    for (p, (user, image, _)) in photos.iter().enumerate() {
+        let group = groups
+            .entry(find(&mut parents, p))
+            .or_insert_with(|| PhotoCluster {
+                users: vec![],
+                images: vec![],
+            });
+        if !group.users.contains(&users[*user].uuid) {
+            group.users.push(users[*user].uuid.clone());
+        }
+        if !group.images.contains(image) {
+            group.images.push(image.clone());
+        }
     }
+    let mut clusters: Vec<PhotoCluster> = groups
+        .into_values()
+        .filter(|cluster| cluster.users.len() > 1)
+        .collect();
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.users.len()));
+    Ok(clusters)
 }
@@ -1,7 +1,16 @@
 use std::error::Error;
 //TODO: encryption at rest
 
-use crate::{db::DB, models::internal_models::internal_user::Notification};
+use crate::{
+    db::DB,
+    index::Index,
+    models::{
+        api_models::api_message::ApiMessage,
+        internal_models::internal_user::{Action, Notification, TimestampedAction},
+    },
+    push::PushEvent,
+    transaction::{Txn, ABSENT_VERSION},
+};
 
 use super::{
     internal_chat::InternalChat,
@@ -11,8 +20,45 @@ use super::{
     shared::{Insertable, InternalUuid},
 };
 
+/// Who read a message and when.
 #[derive(
     Debug,
+    Clone,
+    PartialEq,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    serde::Serialize,
+    paperclip::actix::Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct ReadReceipt {
+    pub user: InternalUuid<InternalUser>,
+    pub read_at: i64,
+}
+
+//indexed under "{chat}/{sent_at}", zero padded so a chat's messages sort by time
+const MESSAGES_BY_CHAT: Index = Index::multi("messages.chat");
+
+fn chat_prefix(chat: &InternalUuid<InternalChat>) -> String {
+    format!("{}/", chat.id)
+}
+
+//sorts right after every key of the chat, '0' comes after '/'
+fn chat_end(chat: &InternalUuid<InternalChat>) -> String {
+    format!("{}0", chat.id)
+}
+
+/// A page of a chat's messages, newest first.
+pub struct MessagePage {
+    pub messages: Vec<InternalMessage>,
+    //pass back to get the page before this one, None when there is nothing older
+    pub next_cursor: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
     rkyv::Serialize,
     rkyv::Deserialize,
     rkyv::Archive,
@@ -27,7 +73,7 @@ pub struct InternalMessage {
     pub author: InternalUuid<InternalUser>,
     pub content: String,
     pub image: Option<InternalUuid<InternalImage>>,
-    pub read_by: Vec<InternalUuid<InternalUser>>,
+    pub read_by: Vec<ReadReceipt>,
     pub chat: InternalUuid<InternalChat>,
 }
 
@@ -40,57 +86,287 @@ impl InternalMessage {
         ))
     }
 
+    pub fn is_read_by(&self, user: &InternalUuid<InternalUser>) -> bool {
+        self.read_by.iter().any(|receipt| &receipt.user == user)
+    }
+
+    fn index_value(&self) -> String {
+        format!("{}{:020}", chat_prefix(&self.chat), self.sent_at)
+    }
+
+    fn index_key(&self) -> String {
+        MESSAGES_BY_CHAT.key(&self.index_value(), &self.uuid)
+    }
+
+    /// The chat's messages, oldest first.
+    pub fn in_chat(
+        db: &DB,
+        chat: &InternalUuid<InternalChat>,
+    ) -> Result<Vec<InternalUuid<InternalMessage>>, Box<dyn Error>> {
+        Ok(MESSAGES_BY_CHAT.prefix(db, &chat_prefix(chat))?)
+    }
+
+    /// Up to `limit` messages older than the `before` cursor, or the newest ones
+    /// without a cursor.
+    pub fn page(
+        db: &DB,
+        chat: &InternalUuid<InternalChat>,
+        before: Option<&str>,
+        limit: usize,
+    ) -> Result<MessagePage, Box<dyn Error>> {
+        let prefix = chat_prefix(chat);
+        let to = match before {
+            Some(cursor) => format!("{}{}", prefix, cursor),
+            None => chat_end(chat),
+        };
+        let mut entries = MESSAGES_BY_CHAT
+            .range::<InternalMessage>(db, &prefix, &to)?
+            .rev();
+        let mut messages = vec![];
+        let mut last = None;
+        for entry in entries.by_ref().take(limit) {
+            let (key, uuid) = entry?;
+            if let Some(message) = uuid.load(db)? {
+                messages.push(message);
+            }
+            last = Some(key);
+        }
+        let next_cursor = match entries.next() {
+            Some(_) => last.map(|key| key[prefix.len()..].to_string()),
+            None => None,
+        };
+        Ok(MessagePage {
+            messages,
+            next_cursor,
+        })
+    }
+
+    /// The messages of the chat sent before this one, newest first.
+    pub fn earlier(
+        &self,
+        db: &DB,
+    ) -> Result<impl Iterator<Item = Result<InternalUuid<InternalMessage>, kv::Error>>, kv::Error>
+    {
+        Ok(MESSAGES_BY_CHAT
+            .range(db, &chat_prefix(&self.chat), &self.index_key())?
+            .rev()
+            .map(|entry| entry.map(|(_, uuid)| uuid)))
+    }
+
+    /// How many messages of the chat were sent after this one.
+    pub fn count_later(&self, db: &DB) -> Result<usize, kv::Error> {
+        let key = self.index_key();
+        let mut count = 0;
+        for entry in MESSAGES_BY_CHAT.range::<InternalMessage>(db, &key, &chat_end(&self.chat))? {
+            if entry?.0 != key {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Removes the message and its place in the chat.
+    pub fn delete(&self, db: &DB) -> Result<bool, Box<dyn Error>> {
+        db.transaction(|txn| {
+            let existed = txn.delete(&self.uuid)?;
+            if let (true, Some(image)) = (existed, &self.image) {
+                InternalImage::add_refs_txn(txn, image, -1)?;
+            }
+            Ok(existed)
+        })
+    }
+
+    /// Removes every message of the chat, returns the ones that were there.
+    pub fn delete_all_in_chat(
+        db: &DB,
+        chat: &InternalUuid<InternalChat>,
+    ) -> Result<Vec<InternalUuid<InternalMessage>>, Box<dyn Error>> {
+        let entries = MESSAGES_BY_CHAT
+            .range::<InternalMessage>(db, &chat_prefix(chat), &chat_end(chat))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut deleted = vec![];
+        for (key, uuid) in entries {
+            let existed = db.transaction(|txn| {
+                let message: InternalMessage = match txn.read(&uuid)? {
+                    Some(message) => message,
+                    //left behind by a message deleted before its keys were tracked
+                    None => return txn.delete_index(MESSAGES_BY_CHAT.name, &key).map(|_| false),
+                };
+                if let Some(image) = &message.image {
+                    InternalImage::add_refs_txn(txn, image, -1)?;
+                }
+                txn.delete(&uuid)
+            })?;
+            if existed {
+                deleted.push(uuid);
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Adds an existing message to the index, for migrating chats that listed them.
+    pub fn index(&self, db: &DB) -> Result<(), Box<dyn Error>> {
+        db.transaction(|txn| txn.write_index_keys(&self.uuid, self))
+    }
+
+    fn add_to_chat(&self, chat: &mut InternalChat) {
+        chat.most_recent_message = match self.content.len() {
+            0 => match self.image {
+                Some(_) => "Sent an image".to_string(),
+                None => "Sent an empty message (somehow)".to_string(),
+            },
+            _ => self.content.clone(),
+        };
+        chat.most_recent_sender = Some(self.author.clone());
+        chat.most_recent_message_sent_at = self.sent_at;
+        chat.unread = chat
+            .users
+            .iter()
+            .enumerate()
+            .map(|(i, u)| {
+                if u == &self.author {
+                    0
+                } else {
+                    chat.unread[i] + 1
+                }
+            })
+            .collect();
+    }
+
+    //tells the members' open sockets once the message is really stored
+    fn push_after_commit(&self, chat: &InternalChat, is_new: bool, txn: &Txn) {
+        let chat_uuid = chat.uuid.clone();
+        let members = chat
+            .users
+            .iter()
+            .cloned()
+            .zip(chat.unread.iter().copied())
+            .collect::<Vec<_>>();
+        let message = self.clone();
+        txn.after_commit(move |db| {
+            let author = message.author.clone();
+            let api_message = ApiMessage::from(message);
+            let event = if is_new {
+                PushEvent::NewMessage {
+                    chat: chat_uuid.clone().into(),
+                    message: api_message,
+                }
+            } else {
+                PushEvent::EditedMessage {
+                    chat: chat_uuid.clone().into(),
+                    message: api_message,
+                }
+            };
+            for (user, unread) in members {
+                db.push.push(&user, &event);
+                if is_new && user != author {
+                    let unread = PushEvent::Unread {
+                        chat: chat_uuid.clone().into(),
+                        unread,
+                    };
+                    db.push.push(&user, &unread);
+                }
+            }
+            Ok(())
+        });
+    }
+
     pub fn save(
         self,
         chat: &mut InternalChat,
         db: &DB,
     ) -> Result<InternalUuid<InternalMessage>, Box<dyn Error>> {
-        //does the chat already have this message?
-        if !chat.messages.contains(&self.uuid) {
-            chat.messages.push(self.uuid.clone());
-            chat.most_recent_message = match self.content.len() {
-                0 => match self.image {
-                    Some(_) => "Sent an image".to_string(),
-                    None => "Sent an empty message (somehow)".to_string(),
-                },
-                _ => self.content.clone(),
+        self.save_with_action(chat, None, db)
+    }
+
+    /// Saves the message, its chat and everyone's notifications in one transaction,
+    /// `author_action` is recorded on the author in the same transaction.
+    pub fn save_with_action(
+        self,
+        chat: &mut InternalChat,
+        author_action: Option<Action>,
+        db: &DB,
+    ) -> Result<InternalUuid<InternalMessage>, Box<dyn Error>> {
+        db.retry_on_conflict(|| {
+            //the caller's chat may be stale, only use it if it hasn't been stored yet
+            let chat_version = match db.load_versioned(&chat.uuid)? {
+                Some(stored) => {
+                    *chat = stored.value;
+                    stored.version
+                }
+                None => ABSENT_VERSION,
             };
-            chat.most_recent_sender = Some(self.author.clone());
-            chat.most_recent_message_sent_at = self.sent_at;
-            chat.unread = chat
-                .users
-                .iter()
-                .enumerate()
-                .map(|(i, u)| {
-                    if u == &self.author {
-                        0
-                    } else {
-                        chat.unread[i] + 1
-                    }
-                })
-                .collect();
-            chat.uuid.write(&chat, db)?;
-        };
 
-        let any_is_admin = chat.users.iter().map(|u| u == &get_admin_uuid()).any(|x| x);
+            //is this a new message, or an edit of a stored one?
+            let stored = db.load_versioned(&self.uuid)?;
+            let is_new = stored.is_none();
+            if is_new {
+                self.add_to_chat(chat);
+            }
+
+            let any_is_admin = chat.users.iter().any(|u| u == &get_admin_uuid());
 
-        //notify all other users in chat
-        if !any_is_admin {
+            //notify all other users in chat
+            let mut users = vec![];
             for user in chat.users.iter() {
-                if user != &self.author {
-                    let user: InternalUuid<InternalUser> = user.clone();
-                    let mut user = user.load(db)?.ok_or(format!(
-                        "User not found in save internal message {}",
-                        user.id
-                    ))?;
-                    user.notifications
+                let is_author = user == &self.author;
+                let notify = !any_is_admin && !is_author;
+                let act = author_action.is_some() && is_author;
+                if !notify && !act {
+                    continue;
+                }
+                let mut user = db.load_versioned(user)?.ok_or(format!(
+                    "User not found in save internal message {}",
+                    user.id
+                ))?;
+                if notify {
+                    user.value
+                        .notifications
                         .push(Notification::UnreadMessage(self.uuid.clone()));
-                    user.uuid.write(&user, db)?;
                 }
+                if let (true, Some(action)) = (act, &author_action) {
+                    user.value.actions.push(TimestampedAction {
+                        action: action.clone(),
+                        timestamp: chrono::Utc::now().timestamp(),
+                    });
+                }
+                users.push(user);
             }
-        }
 
-        self.uuid.write(&self, db)
+            let chat: &InternalChat = chat;
+            db.transaction(|txn| {
+                if is_new {
+                    txn.expect_version(&chat.uuid, chat_version)?;
+                    txn.write(&chat.uuid, chat)?;
+                }
+                for user in &users {
+                    txn.expect_version(&user.value.uuid, user.version)?;
+                    txn.write(&user.value.uuid, &user.value)?;
+                }
+                let stored_image = match &stored {
+                    Some(stored) => {
+                        txn.expect_version(&self.uuid, stored.version)?;
+                        stored.value.image.as_ref()
+                    }
+                    None => {
+                        txn.expect_version(&self.uuid, ABSENT_VERSION)?;
+                        None
+                    }
+                };
+                //an edit may swap the image
+                if stored_image != self.image.as_ref() {
+                    if let Some(image) = stored_image {
+                        InternalImage::add_refs_txn(txn, image, -1)?;
+                    }
+                    if let Some(image) = &self.image {
+                        InternalImage::add_refs_txn(txn, image, 1)?;
+                    }
+                }
+                self.push_after_commit(chat, is_new, txn);
+                txn.write(&self.uuid, &self)
+            })?;
+            Ok(self.uuid.clone())
+        })
     }
 }
 
@@ -107,6 +383,14 @@ impl InternalMessage {
 
 impl Insertable for InternalMessage {
     fn version() -> u64 {
-        0
+        1
+    }
+
+    fn indexes() -> &'static [Index] {
+        &[MESSAGES_BY_CHAT]
+    }
+
+    fn index_values(&self) -> Vec<(&'static Index, String)> {
+        vec![(&MESSAGES_BY_CHAT, self.index_value())]
     }
 }
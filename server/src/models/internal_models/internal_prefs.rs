@@ -1,9 +1,11 @@
 use crate::test::fake::Gen;
 use crate::vec::shared::VectorSearch;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+use super::internal_prefs_config::find_by_name;
+use super::internal_prefs_config::prefs_config;
+use super::internal_prefs_config::prefs_schema;
 use super::internal_prefs_config::PREFS_CARDINALITY;
-use super::internal_prefs_config::PREFS_CONFIG;
 use super::internal_prefs_config::P_NONE;
 use super::internal_prefs_config::P_NONE_PROP;
 use super::internal_user::InternalUser;
@@ -17,7 +19,11 @@ use rand_distr::Distribution;
 use rand_distr::Normal;
 use serde::{Deserialize, Serialize};
 
-use crate::{db::DB, vec::shared::Bbox};
+use crate::{
+    db::DB,
+    geo::distance_km,
+    vec::shared::{Bbox, UNKNOWN},
+};
 
 #[derive(
     Debug,
@@ -49,6 +55,205 @@ pub struct PreferenceRange {
 pub struct LabeledPreferenceRange {
     pub name: String,
     pub range: PreferenceRange,
+    //clients that don't send these keep the old behaviour, every pref is a dealbreaker
+    #[serde(default)]
+    pub strictness: Strictness,
+    #[serde(default = "default_pref_weight")]
+    pub weight: f32,
+    #[serde(default)]
+    pub unknown: UnknownPolicy,
+}
+
+/// Dealbreakers filter candidates out through the vec index, nice-to-haves only
+/// change how candidates are ranked.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    Serialize,
+    Deserialize,
+    Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub enum Strictness {
+    #[default]
+    Dealbreaker,
+    NiceToHave,
+}
+
+/// What a pref does with someone who never answered its question.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    Serialize,
+    Deserialize,
+    Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub enum UnknownPolicy {
+    Accept,
+    Reject,
+    //kept, but ranked as if they were as far off as possible
+    #[default]
+    Penalize,
+}
+
+impl UnknownPolicy {
+    //ranking happens above the index, so to it penalizing is accepting
+    fn accepts(self) -> bool {
+        self != UnknownPolicy::Reject
+    }
+}
+
+pub const DEFAULT_PREF_WEIGHT: f32 = 1.0;
+
+fn default_pref_weight() -> f32 {
+    DEFAULT_PREF_WEIGHT
+}
+
+impl LabeledPreferenceRange {
+    pub fn dealbreaker(name: String, range: PreferenceRange) -> LabeledPreferenceRange {
+        LabeledPreferenceRange {
+            name,
+            range,
+            strictness: Strictness::Dealbreaker,
+            weight: DEFAULT_PREF_WEIGHT,
+            unknown: UnknownPolicy::default(),
+        }
+    }
+
+    //fake users make about half of their prefs nice-to-haves
+    pub fn sample(
+        name: String,
+        range: PreferenceRange,
+        rng: &mut ThreadRng,
+    ) -> LabeledPreferenceRange {
+        LabeledPreferenceRange {
+            name,
+            range,
+            strictness: if rng.gen_bool(0.5) {
+                Strictness::Dealbreaker
+            } else {
+                Strictness::NiceToHave
+            },
+            weight: rng.gen_range(0.5..2.0),
+            unknown: UnknownPolicy::default(),
+        }
+    }
+
+    pub fn is_dealbreaker(&self) -> bool {
+        self.strictness == Strictness::Dealbreaker
+    }
+
+    fn accepts_anything(&self) -> bool {
+        self.range.min == i16::MIN && self.range.max == i16::MAX
+    }
+
+    //1 inside the range, falling linearly to 0 at a whole `span` outside of it
+    fn closeness(&self, value: f32, span: f32) -> f32 {
+        let distance = if value < self.range.min as f32 {
+            self.range.min as f32 - value
+        } else if value > self.range.max as f32 {
+            value - self.range.max as f32
+        } else {
+            0.0
+        };
+        (1.0 - distance / span).max(0.0)
+    }
+}
+
+/// How well `their_props` fit the nice-to-have ranges of `prefs`, the weighted mean of
+/// each range's closeness from 0 to 1. Ranges that accept anything are left out, and
+/// with nothing left to compare every candidate fits perfectly. Unset props count as
+/// 0 for any range, dealbreaker or not, that doesn't accept unknowns.
+/// A nice-to-have radius compares the distance between `my_props` and `their_props`,
+/// and stands in for the latitude and longitude ranges.
+pub fn soft_match_score(
+    prefs: &[LabeledPreferenceRange],
+    my_props: &[LabeledProperty],
+    their_props: &[LabeledProperty],
+) -> f32 {
+    let mut score = 0.0;
+    let mut weights = 0.0;
+    let geo = prefs_schema().geo();
+    let radius = geo.and_then(|geo| geo.radius_pref(prefs));
+    for (index, config) in prefs_config().iter().enumerate() {
+        if let Some(geo) = geo {
+            let is_location = index == geo.latitude || index == geo.longitude;
+            if index == geo.radius || (is_location && radius.is_some()) {
+                continue;
+            }
+        }
+        let pref = match find_by_name(prefs, index, &config.name, |p| &p.name) {
+            Some(pref) if !pref.accepts_anything() => pref,
+            _ => continue,
+        };
+        let prop = find_by_name(their_props, index, &config.name, |p| &p.name)
+            .filter(|prop| prop.value != UNKNOWN);
+        match prop {
+            //answered dealbreakers already got through the index
+            Some(_) if pref.is_dealbreaker() => {}
+            Some(prop) => {
+                let span = (config.max as f32 - config.min as f32).max(1.0);
+                score += pref.weight * pref.closeness(prop.value as f32, span);
+                weights += pref.weight;
+            }
+            None if pref.unknown != UnknownPolicy::Accept => weights += pref.weight,
+            None => {}
+        }
+    }
+
+    //a nice-to-have radius is worth nothing at twice the distance
+    if let (Some(geo), Some(radius)) = (geo, radius.filter(|r| !r.is_dealbreaker())) {
+        let locations = (
+            geo.props_location(my_props),
+            geo.props_location(their_props),
+        );
+        match locations {
+            (Some(mine), Some(theirs)) => {
+                let span = (radius.range.max as f32).max(1.0);
+                score += radius.weight * radius.closeness(distance_km(mine, theirs) as f32, span);
+                weights += radius.weight;
+            }
+            (Some(_), None) if radius.unknown != UnknownPolicy::Accept => weights += radius.weight,
+            _ => {}
+        }
+    }
+    if weights > 0.0 {
+        score / weights
+    } else {
+        1.0
+    }
+}
+
+/// Orders `candidates` best match for `user` first, by how well each side fits the
+/// other's nice-to-haves. Ties go to the higher elo.
+pub fn rank_by_soft_prefs(user: &InternalUser, candidates: Vec<InternalUser>) -> Vec<InternalUser> {
+    let mut scored = candidates
+        .into_iter()
+        .map(|candidate| {
+            let score = soft_match_score(&user.prefs, &user.props, &candidate.props)
+                + soft_match_score(&candidate.prefs, &candidate.props, &user.props);
+            (score, candidate)
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .total_cmp(a_score)
+            .then_with(|| b.elo.total_cmp(&a.elo))
+    });
+    scored.into_iter().map(|(_, candidate)| candidate).collect()
 }
 
 #[derive(
@@ -69,14 +274,19 @@ pub struct LabeledProperty {
 
 impl GetVector for Vec<LabeledProperty> {
     fn get_vector(&self) -> [i16; PREFS_CARDINALITY] {
-        let mut vector = [-32768 as i16; PREFS_CARDINALITY];
+        let mut vector = [UNKNOWN; PREFS_CARDINALITY];
 
-        for index in 0..PREFS_CARDINALITY {
-            if let Some(preference) = self.get(index) {
+        for (index, config) in prefs_config().iter().enumerate() {
+            if let Some(preference) = find_by_name(self, index, &config.name, |p| &p.name) {
                 vector[index] = preference.value;
             }
         }
 
+        //the radius is a pref only, see `search_bbox`
+        if let Some(geo) = prefs_schema().geo() {
+            vector[geo.radius] = i16::MIN;
+        }
+
         vector
     }
 }
@@ -85,18 +295,18 @@ impl Gen<'_, Vec<LabeledProperty>> for Vec<LabeledPreferenceRange> {
     fn gen(props: &Vec<LabeledProperty>) -> Self {
         let mut rng = rand::thread_rng();
 
-        let prefs = (0..(PREFS_CARDINALITY))
-            .map(|i| {
-                let preference = &PREFS_CONFIG[i];
+        let prefs = prefs_config()
+            .iter()
+            .enumerate()
+            .map(|(i, preference)| {
                 let range = sample_range_from_preference_and_prop(
                     preference,
-                    props.get(i).unwrap().value,
+                    find_by_name(props, i, &preference.name, |p| &p.name)
+                        .unwrap()
+                        .value,
                     &mut rng,
                 );
-                LabeledPreferenceRange {
-                    name: preference.name.to_string(),
-                    range,
-                }
+                LabeledPreferenceRange::sample(preference.name.clone(), range, &mut rng)
             })
             .collect();
         prefs
@@ -107,27 +317,93 @@ impl GetBbox for Vec<LabeledPreferenceRange> {
     fn get_bbox(&self) -> Bbox<PREFS_CARDINALITY> {
         let mut min_vals = [-32768 as i16; PREFS_CARDINALITY];
         let mut max_vals = [32767 as i16; PREFS_CARDINALITY];
+        let mut accept_unknown = [true; PREFS_CARDINALITY];
 
-        for index in 0..PREFS_CARDINALITY {
-            if let Some(preference) = self.get(index) {
+        for (index, config) in prefs_config().iter().enumerate() {
+            //nice-to-haves never filter, they're left open so the index only checks dealbreakers
+            let preference = find_by_name(self, index, &config.name, |p| &p.name)
+                .filter(|preference| preference.is_dealbreaker());
+            if let Some(preference) = preference {
                 min_vals[index] = preference.range.min;
                 max_vals[index] = preference.range.max;
+                accept_unknown[index] = preference.unknown.accepts();
             }
         }
 
         Bbox {
             min: min_vals,
             max: max_vals,
+            accept_unknown,
         }
     }
 }
 
+/// The box a user's prefs take in the vec index. With a radius, the latitude and
+/// longitude ranges give way to the box around the user's location, and a dealbreaker
+/// radius is carried in the max of its own slot so hits can be checked against the
+/// real distance with `within_radius`, the corners of the box are too far away.
+pub fn search_bbox(
+    prefs: &Vec<LabeledPreferenceRange>,
+    props: &Vec<LabeledProperty>,
+) -> Bbox<PREFS_CARDINALITY> {
+    let mut bbox = prefs.get_bbox();
+    let geo = match prefs_schema().geo() {
+        Some(geo) => geo,
+        None => return bbox,
+    };
+    bbox.min[geo.radius] = i16::MIN;
+    bbox.max[geo.radius] = i16::MAX;
+    bbox.accept_unknown[geo.radius] = true;
+
+    let radius = match geo.radius_pref(prefs) {
+        Some(radius) => radius,
+        None => return bbox,
+    };
+    for index in [geo.latitude, geo.longitude] {
+        bbox.min[index] = i16::MIN;
+        bbox.max[index] = i16::MAX;
+        bbox.accept_unknown[index] = true;
+    }
+    //without a location of our own there is nothing to measure from
+    if let (true, Some(center)) = (radius.is_dealbreaker(), geo.location(&props.get_vector())) {
+        let (latitude, longitude) = geo.bounds(center, radius.range.max as f64);
+        bbox.min[geo.latitude] = latitude.min;
+        bbox.max[geo.latitude] = latitude.max;
+        bbox.min[geo.longitude] = longitude.min;
+        bbox.max[geo.longitude] = longitude.max;
+        bbox.accept_unknown[geo.latitude] = radius.unknown.accepts();
+        bbox.accept_unknown[geo.longitude] = radius.unknown.accepts();
+        bbox.max[geo.radius] = radius.range.max;
+    }
+    bbox
+}
+
+/// Whether `to` is within the radius `bbox` carries of `from`, see `search_bbox`.
+/// An unknown location is let through when the box accepts unknowns.
+pub fn within_radius(
+    bbox: &Bbox<PREFS_CARDINALITY>,
+    from: &[i16; PREFS_CARDINALITY],
+    to: &[i16; PREFS_CARDINALITY],
+) -> bool {
+    match prefs_schema().geo() {
+        Some(geo) if bbox.max[geo.radius] != i16::MAX => match geo.location(to) {
+            Some(_) => geo.within(from, to, bbox.max[geo.radius] as f64),
+            None => bbox.accept_unknown[geo.latitude],
+        },
+        _ => true,
+    }
+}
+
 impl DB {
+    //their boxes, their own radius still has to be checked against their location
     fn get_users_who_prefer_me_direct(
         &self,
         props: &Vec<LabeledProperty>,
         seen: &Vec<InternalUuid<InternalUser>>,
-    ) -> Result<HashSet<InternalUuid<InternalUser>>, Box<dyn std::error::Error>> {
+    ) -> Result<
+        HashMap<InternalUuid<InternalUser>, Bbox<PREFS_CARDINALITY>>,
+        Box<dyn std::error::Error>,
+    > {
         let arc_clone = self.vec_index.clone();
         let lock = arc_clone.lock().map_err(|_| "Error getting lock")?;
         let inv = {
@@ -137,31 +413,51 @@ impl DB {
             )
             .collect::<Vec<_>>()
         };
-        Ok(inv
-            .iter()
-            .map(|u| u.label.clone())
-            .map(|u| u.into())
-            .collect())
+        Ok(inv.into_iter().map(|u| (u.label.into(), u.bbox)).collect())
     }
 
+    //their props vectors, already within my radius
     fn get_users_who_i_prefer_direct(
         &self,
         prefs: &Vec<LabeledPreferenceRange>,
+        props: &Vec<LabeledProperty>,
         seen: &Vec<InternalUuid<InternalUser>>,
-    ) -> Result<HashSet<InternalUuid<InternalUser>>, Box<dyn std::error::Error>> {
+    ) -> Result<
+        HashMap<InternalUuid<InternalUser>, [i16; PREFS_CARDINALITY]>,
+        Box<dyn std::error::Error>,
+    > {
+        let bbox = search_bbox(prefs, props);
+        let vector = props.get_vector();
         let arc_clone = self.vec_index.clone();
         let lock = arc_clone.lock().map_err(|_| "Error getting lock")?;
         let inv = {
-            lock.search(
-                &prefs.get_bbox(),
-                Some(&seen.iter().map(|u| u.id.clone()).collect()),
-            )
-            .collect::<Vec<_>>()
+            lock.search(&bbox, Some(&seen.iter().map(|u| u.id.clone()).collect()))
+                .collect::<Vec<_>>()
         };
         Ok(inv
-            .iter()
-            .map(|u| u.label.clone())
-            .map(|u| u.into())
+            .into_iter()
+            .filter(|u| within_radius(&bbox, &vector, &u.vec))
+            .map(|u| (u.label.into(), u.vec))
+            .collect())
+    }
+
+    fn get_mutual_preference_uuids_direct(
+        &self,
+        props: &Vec<LabeledProperty>,
+        prefs: &Vec<LabeledPreferenceRange>,
+        seen: &Vec<InternalUuid<InternalUser>>,
+    ) -> Result<Vec<InternalUuid<InternalUser>>, Box<dyn std::error::Error>> {
+        let users_who_prefer_me = self.get_users_who_prefer_me_direct(props, seen)?;
+        let users_who_i_prefer = self.get_users_who_i_prefer_direct(prefs, props, seen)?;
+        let vector = props.get_vector();
+
+        Ok(users_who_i_prefer
+            .into_iter()
+            .filter(|(uuid, their_vector)| match users_who_prefer_me.get(uuid) {
+                Some(their_bbox) => within_radius(their_bbox, their_vector, &vector),
+                None => false,
+            })
+            .map(|(uuid, _)| uuid)
             .collect())
     }
 
@@ -171,11 +467,9 @@ impl DB {
         prefs: &Vec<LabeledPreferenceRange>,
         seen: &Vec<InternalUuid<InternalUser>>,
     ) -> Result<Vec<InternalUser>, Box<dyn std::error::Error>> {
-        let users_who_prefer_me = self.get_users_who_prefer_me_direct(props, &seen)?;
-        let users_who_i_prefer = self.get_users_who_i_prefer_direct(prefs, &seen)?;
-
-        let user_options = users_who_prefer_me
-            .intersection(&users_who_i_prefer)
+        let user_options = self
+            .get_mutual_preference_uuids_direct(props, prefs, seen)?
+            .iter()
             .map(|u| u.load(self))
             .collect::<Result<Option<Vec<_>>, _>>()?;
 
@@ -191,41 +485,51 @@ impl DB {
         preference: &Vec<LabeledPreferenceRange>,
         seen: &Vec<InternalUuid<InternalUser>>,
     ) -> Result<usize, Box<dyn std::error::Error>> {
-        let users_who_prefer_me = self.get_users_who_prefer_me_direct(props, &seen)?;
-        let users_who_i_prefer = self.get_users_who_i_prefer_direct(preference, &seen)?;
-
-        Ok(users_who_prefer_me
-            .intersection(&users_who_i_prefer)
-            .count())
+        Ok(self
+            .get_mutual_preference_uuids_direct(props, preference, seen)?
+            .len())
     }
 
     pub fn get_users_i_prefer_count_direct(
         &self,
         preference: &Vec<LabeledPreferenceRange>,
+        props: &Vec<LabeledProperty>,
         seen: &Vec<InternalUuid<InternalUser>>,
     ) -> Result<usize, Box<dyn std::error::Error>> {
-        Ok(self.get_users_who_i_prefer_direct(preference, &seen)?.len())
+        Ok(self
+            .get_users_who_i_prefer_direct(preference, props, seen)?
+            .len())
     }
 
+    /// Matches on their boxes only, with a radius that's up to the corners of the box.
     pub fn get_users_who_prefer_me(
         &self,
         user: &InternalUser,
     ) -> Result<HashSet<InternalUuid<InternalUser>>, Box<dyn std::error::Error>> {
-        self.get_users_who_prefer_me_direct(&user.props, &user.seen)
+        Ok(self
+            .get_users_who_prefer_me_direct(&user.props, &user.seen)?
+            .into_keys()
+            .collect())
     }
 
     pub fn get_users_who_i_prefer(
         &self,
         user: &InternalUser,
     ) -> Result<HashSet<InternalUuid<InternalUser>>, Box<dyn std::error::Error>> {
-        self.get_users_who_i_prefer_direct(&user.prefs, &user.seen)
+        Ok(self
+            .get_users_who_i_prefer_direct(&user.prefs, &user.props, &user.seen)?
+            .into_keys()
+            .collect())
     }
 
     pub fn get_mutual_preference_users(
         &self,
         user: &InternalUser,
     ) -> Result<Vec<InternalUser>, Box<dyn std::error::Error>> {
-        self.get_mutual_preference_users_direct(&user.props, &user.prefs, &user.seen)
+        //blocked users are left out like the ones already seen
+        let mut excluded = user.seen.clone();
+        excluded.extend(self.blocked_by(&user.uuid)?);
+        self.get_mutual_preference_users_direct(&user.props, &user.prefs, &excluded)
     }
 
     pub fn get_mutual_preference_users_count(
@@ -239,11 +543,11 @@ impl DB {
         &self,
         user: &InternalUser,
     ) -> Result<usize, Box<dyn std::error::Error>> {
-        self.get_users_i_prefer_count_direct(&user.prefs, &user.seen)
+        self.get_users_i_prefer_count_direct(&user.prefs, &user.props, &user.seen)
     }
 }
 
-#[derive(Debug, Serialize, Apiv2Schema, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Apiv2Schema, Clone, PartialEq)]
 pub enum UIElement {
     Slider,
     GenderPicker,
@@ -252,7 +556,7 @@ pub enum UIElement {
     NumberInput,
 }
 
-#[derive(Debug, Serialize, Apiv2Schema, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Apiv2Schema, Clone, PartialEq)]
 pub enum Category {
     Mandatory,
     Financial,
@@ -270,15 +574,17 @@ pub enum Category {
     Misc,
 }
 
-#[derive(Debug, Apiv2Schema, Clone, PartialEq)]
+//fields left out of the schema file take the values from `Default`
+#[derive(Debug, Deserialize, Apiv2Schema, Clone, PartialEq)]
+#[serde(default)]
 pub struct PreferenceConfig {
-    pub name: &'static str,
-    pub display: &'static str,
+    pub name: String,
+    pub display: String,
     pub category: Category,
-    pub group: &'static str,
+    pub group: String,
     pub ui_element: UIElement,
-    pub value_question: &'static str,
-    pub range_question: &'static str,
+    pub value_question: String,
+    pub range_question: String,
     pub min: i16,
     pub max: i16,
     pub mean: f64,
@@ -286,53 +592,111 @@ pub struct PreferenceConfig {
     pub mean_alteration: MeanAlteration,
     pub std_dev_alteration: StdDevAlteration,
     pub linear_mapping: Option<LinearMapping>,
-    pub non_optional_message: Option<&'static str>,
+    pub non_optional_message: Option<String>,
     pub default: Option<i16>,
     pub probability_to_be_none: f64,
-    pub labels: Option<&'static [&'static str]>,
-}
-
-pub const fn default_preference_config() -> PreferenceConfig {
-    PreferenceConfig {
-        name: "default",
-        display: "default",
-        category: Category::Misc,
-        group: "default",
-        ui_element: UIElement::Slider,
-        value_question: "default",
-        range_question: "default",
-        min: 0,
-        max: 4,
-        mean: 2.0,
-        std_dev: 1.0,
-        mean_alteration: MeanAlteration::Set,
-        std_dev_alteration: StdDevAlteration::None,
-        linear_mapping: None,
-        non_optional_message: None,
-        default: None,
-        probability_to_be_none: P_NONE,
-        labels: None,
+    pub labels: Option<Vec<String>>,
+    pub geo: Option<GeoRole>,
+}
+
+/// The part a preference plays in geo matching. Latitude and longitude hold the
+/// user's location, the radius pref's max is how far away they'll go.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Apiv2Schema, PartialEq)]
+pub enum GeoRole {
+    Latitude,
+    Longitude,
+    RadiusKm,
+}
+
+impl Default for PreferenceConfig {
+    fn default() -> Self {
+        PreferenceConfig {
+            name: "".to_string(),
+            display: "default".to_string(),
+            category: Category::Misc,
+            group: "default".to_string(),
+            ui_element: UIElement::Slider,
+            value_question: "default".to_string(),
+            range_question: "default".to_string(),
+            min: 0,
+            max: 4,
+            mean: 2.0,
+            std_dev: 1.0,
+            mean_alteration: MeanAlteration::Set,
+            std_dev_alteration: StdDevAlteration::None,
+            linear_mapping: None,
+            non_optional_message: None,
+            default: None,
+            probability_to_be_none: P_NONE,
+            labels: None,
+            geo: None,
+        }
     }
 }
 
 impl PreferenceConfig {
     pub fn get_public(&self) -> PreferenceConfigPublic {
         PreferenceConfigPublic {
-            name: self.name.to_string(),
-            display: self.display.to_string(),
+            name: self.name.clone(),
+            display: self.display.clone(),
             category: self.category.clone(),
-            group: self.group.to_string(),
+            group: self.group.clone(),
             ui_element: self.ui_element.clone(),
-            value_question: self.value_question.to_string(),
-            range_question: self.range_question.to_string(),
+            value_question: self.value_question.clone(),
+            range_question: self.range_question.clone(),
             min: self.min,
             max: self.max,
             linear_mapping: self.linear_mapping.clone(),
-            labels: self
-                .labels
-                .map(|l| l.to_vec().iter().map(|s| s.to_string()).collect()),
-            non_optional_message: self.non_optional_message.map(|s| s.to_string()),
+            labels: self.labels.clone(),
+            non_optional_message: self.non_optional_message.clone(),
+            geo: self.geo,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min > self.max {
+            return Err(format!("min {} is above max {}", self.min, self.max));
+        }
+        match &self.linear_mapping {
+            Some(mapping) => {
+                if mapping.real_min >= mapping.real_max {
+                    return Err("linear_mapping real_min has to be below real_max".to_string());
+                }
+                if self.mean < mapping.real_min || self.mean > mapping.real_max {
+                    return Err(format!("mean {} is outside of the mapped range", self.mean));
+                }
+            }
+            None => {
+                if self.mean < self.min as f64 || self.mean > self.max as f64 {
+                    return Err(format!("mean {} is outside of min..max", self.mean));
+                }
+            }
+        }
+        if !self.std_dev.is_finite() || self.std_dev < 0.0 {
+            return Err(format!(
+                "std_dev {} has to be a positive number",
+                self.std_dev
+            ));
+        }
+        if let Some(default) = self.default {
+            if default < self.min || default > self.max {
+                return Err(format!("default {} is outside of min..max", default));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.probability_to_be_none) {
+            return Err("probability_to_be_none has to be between 0 and 1".to_string());
+        }
+        if let Some(labels) = &self.labels {
+            let values = self.max as i32 - self.min as i32 + 1;
+            if labels.len() as i32 != values {
+                return Err(format!(
+                    "{} labels for the {} values in min..max",
+                    labels.len(),
+                    values
+                ));
+            }
         }
+        Ok(())
     }
 }
 
@@ -350,6 +714,7 @@ pub struct PreferenceConfigPublic {
     pub linear_mapping: Option<LinearMapping>,
     pub labels: Option<Vec<String>>,
     pub non_optional_message: Option<String>,
+    pub geo: Option<GeoRole>,
 }
 
 fn f64_to_i16(value: f64, preference: &PreferenceConfig) -> i16 {
@@ -0,0 +1,416 @@
+use std::error::Error;
+
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    internal_chat::InternalChat,
+    internal_image::{ImageSize, InternalImage},
+    internal_message::InternalMessage,
+    internal_user::{InternalUser, Notification},
+    shared::{Insertable, InternalUuid},
+};
+use crate::{
+    db::DB,
+    index::Index,
+    transaction::{SaveTxn, Txn, TxnResult},
+};
+
+//indexed under "{status}/{created_at}", so each status lists oldest first
+const REPORTS_BY_STATUS: Index = Index::multi("reports.status");
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    Serialize,
+    Deserialize,
+    Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub enum ReportKind {
+    Bug,
+    Violation,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    Serialize,
+    Deserialize,
+    Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub enum ReportStatus {
+    Open,
+    Assigned,
+    Resolved,
+}
+
+/// What a moderator did about a report, applied to its target user.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    Serialize,
+    Deserialize,
+    Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub enum ModAction {
+    Dismiss,
+    Warn,
+    Unpublish,
+    Ban,
+}
+
+#[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct ReportedMessage {
+    pub author: InternalUuid<InternalUser>,
+    pub sent_at: i64,
+    pub content: String,
+    pub image: Option<Vec<u8>>,
+}
+
+/// Copies of what the report was about, taken when it was filed. The user may
+/// change their profile or delete the chat afterwards, this stays.
+#[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct Evidence {
+    //jpeg, sent along by the reporter
+    pub screenshot: Vec<u8>,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub user_images: Vec<Vec<u8>>,
+    pub messages: Vec<ReportedMessage>,
+}
+
+#[derive(Debug, Clone, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct ModeratorNote {
+    pub author: InternalUuid<InternalUser>,
+    pub written_at: i64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct Resolution {
+    pub by: InternalUuid<InternalUser>,
+    pub at: i64,
+    pub action: ModAction,
+}
+
+/// A bug or violation report, queued for moderators by its status.
+#[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct InternalReport {
+    pub uuid: InternalUuid<InternalReport>,
+    pub kind: ReportKind,
+    pub status: ReportStatus,
+    pub created_at: i64,
+    //left out when the reporter wasn't logged in
+    pub reporter: Option<InternalUuid<InternalUser>>,
+    pub target_user: Option<InternalUuid<InternalUser>>,
+    pub target_chat: Option<InternalUuid<InternalChat>>,
+    pub content: String,
+    pub platform: String,
+    pub evidence: Evidence,
+    pub assignee: Option<InternalUuid<InternalUser>>,
+    pub notes: Vec<ModeratorNote>,
+    pub resolution: Option<Resolution>,
+}
+
+/// Why a change to a report was turned down.
+#[derive(Debug, PartialEq)]
+pub enum ReportError {
+    NotFound,
+    AlreadyResolved,
+    NoTarget,
+}
+
+impl ReportError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ReportError::NotFound => "Report not found",
+            ReportError::AlreadyResolved => "Report already resolved",
+            ReportError::NoTarget => "Report has no user to act on",
+        }
+    }
+}
+
+fn status_prefix(status: ReportStatus) -> String {
+    format!("{:?}/", status)
+}
+
+impl Evidence {
+    /// Copies the user's profile and the chat's messages, images included. Images
+    /// that are already gone are skipped.
+    pub fn snapshot(
+        db: &DB,
+        screenshot: Vec<u8>,
+        user: Option<&InternalUser>,
+        chat: Option<&InternalChat>,
+    ) -> Result<Evidence, Box<dyn Error>> {
+        let mut evidence = Evidence {
+            screenshot,
+            display_name: None,
+            description: None,
+            user_images: vec![],
+            messages: vec![],
+        };
+        if let Some(user) = user {
+            evidence.display_name = Some(user.display_name.clone());
+            evidence.description = Some(user.description.clone());
+            for image in user.images.iter() {
+                if let Some(image) = image.load(db)? {
+                    evidence
+                        .user_images
+                        .push(image.rendition(db, ImageSize::Full)?);
+                }
+            }
+        }
+        if let Some(chat) = chat {
+            for message in InternalMessage::in_chat(db, &chat.uuid)? {
+                let message = match message.load(db)? {
+                    Some(message) => message,
+                    None => continue,
+                };
+                let image = match &message.image {
+                    Some(image) => match image.load(db)? {
+                        Some(image) => Some(image.rendition(db, ImageSize::Full)?),
+                        None => None,
+                    },
+                    None => None,
+                };
+                evidence.messages.push(ReportedMessage {
+                    author: message.author,
+                    sent_at: message.sent_at,
+                    content: message.content,
+                    image,
+                });
+            }
+        }
+        Ok(evidence)
+    }
+}
+
+impl InternalReport {
+    fn index_value(&self) -> String {
+        format!("{}{:020}", status_prefix(self.status), self.created_at)
+    }
+
+    pub fn save(&self, db: &DB) -> Result<(), Box<dyn Error>> {
+        db.transaction(|txn| self.save_txn(txn))
+    }
+
+    /// Files a report against the uploader when the image looks like a photo of a
+    /// banned user, the usual sign of a banned account coming back. Returns it if so.
+    pub fn flag_banned_photo(
+        db: &DB,
+        uploader: &InternalUser,
+        image: &InternalImage,
+    ) -> Result<Option<InternalReport>, Box<dyn Error>> {
+        let dhash = match image.dhash {
+            Some(dhash) => dhash,
+            None => return Ok(None),
+        };
+        let matches: Vec<_> = InternalImage::near_duplicates(db, dhash)?
+            .into_iter()
+            .map(|other| other.uuid)
+            .filter(|other| other != &image.uuid)
+            .collect();
+        //near duplicates are rare, the banned are only looked at when there are some
+        if matches.is_empty() {
+            return Ok(None);
+        }
+        for banned in db.banned_users()? {
+            if banned == uploader.uuid {
+                continue;
+            }
+            let banned = match banned.load(db)? {
+                Some(banned) => banned,
+                None => continue,
+            };
+            let matched = banned
+                .owned_images
+                .iter()
+                .chain(banned.images.iter())
+                .chain(banned.preview_image.iter())
+                .find(|photo| matches.contains(photo));
+            let matched = match matched {
+                Some(matched) => matched,
+                None => continue,
+            };
+            let screenshot = image.rendition(db, ImageSize::Full)?;
+            let report = InternalReport {
+                uuid: InternalUuid::new(),
+                kind: ReportKind::Violation,
+                status: ReportStatus::Open,
+                created_at: chrono::Utc::now().timestamp(),
+                reporter: None,
+                target_user: Some(uploader.uuid.clone()),
+                target_chat: None,
+                content: format!(
+                    "Uploaded image {} looks like image {} of banned user {}",
+                    image.uuid.id, matched.id, banned.uuid.id
+                ),
+                platform: "server".to_string(),
+                evidence: Evidence::snapshot(db, screenshot, Some(uploader), None)?,
+                assignee: None,
+                notes: vec![],
+                resolution: None,
+            };
+            report.save(db)?;
+            return Ok(Some(report));
+        }
+        Ok(None)
+    }
+
+    /// Reports with the given status, oldest first.
+    pub fn list(
+        db: &DB,
+        status: ReportStatus,
+        limit: usize,
+    ) -> Result<Vec<InternalReport>, Box<dyn Error>> {
+        let prefix = status_prefix(status);
+        let end = format!("{}0", &prefix[..prefix.len() - 1]);
+        let mut reports = vec![];
+        for entry in REPORTS_BY_STATUS.range::<InternalReport>(db, &prefix, &end)? {
+            if reports.len() >= limit {
+                break;
+            }
+            let (_, uuid) = entry?;
+            if let Some(report) = uuid.load(db)? {
+                reports.push(report);
+            }
+        }
+        Ok(reports)
+    }
+
+    /// Changes an unresolved report, the status index follows on save.
+    fn modify(
+        db: &DB,
+        uuid: &InternalUuid<InternalReport>,
+        f: impl Fn(&mut InternalReport) -> Result<(), ReportError>,
+    ) -> Result<Result<InternalReport, ReportError>, Box<dyn Error>> {
+        db.retry_on_conflict(|| {
+            let mut report = match db.load_versioned(uuid)? {
+                Some(report) => report,
+                None => return Ok(Err(ReportError::NotFound)),
+            };
+            if report.value.status == ReportStatus::Resolved {
+                return Ok(Err(ReportError::AlreadyResolved));
+            }
+            if let Err(e) = f(&mut report.value) {
+                return Ok(Err(e));
+            }
+            db.transaction(|txn| {
+                txn.expect_version(uuid, report.version)?;
+                report.value.save_txn(txn)
+            })?;
+            Ok(Ok(report.value))
+        })
+    }
+
+    /// Hands the report to a moderator, taking it out of the open queue.
+    pub fn assign(
+        db: &DB,
+        uuid: &InternalUuid<InternalReport>,
+        assignee: &InternalUuid<InternalUser>,
+    ) -> Result<Result<InternalReport, ReportError>, Box<dyn Error>> {
+        InternalReport::modify(db, uuid, |report| {
+            report.assignee = Some(assignee.clone());
+            report.status = ReportStatus::Assigned;
+            Ok(())
+        })
+    }
+
+    pub fn add_note(
+        db: &DB,
+        uuid: &InternalUuid<InternalReport>,
+        author: &InternalUuid<InternalUser>,
+        text: &str,
+    ) -> Result<Result<InternalReport, ReportError>, Box<dyn Error>> {
+        InternalReport::modify(db, uuid, |report| {
+            report.notes.push(ModeratorNote {
+                author: author.clone(),
+                written_at: chrono::Utc::now().timestamp(),
+                text: text.to_string(),
+            });
+            Ok(())
+        })
+    }
+
+    /// Carries out the action on the report's target user, then closes the report.
+    /// The action goes first so a failed one leaves the report open to try again,
+    /// once it's resolved it can't be applied twice.
+    pub fn resolve(
+        db: &DB,
+        uuid: &InternalUuid<InternalReport>,
+        moderator: &InternalUuid<InternalUser>,
+        action: ModAction,
+    ) -> Result<Result<InternalReport, ReportError>, Box<dyn Error>> {
+        let report = match uuid.load(db)? {
+            Some(report) => report,
+            None => return Ok(Err(ReportError::NotFound)),
+        };
+        if report.status == ReportStatus::Resolved {
+            return Ok(Err(ReportError::AlreadyResolved));
+        }
+        match (&report.target_user, action) {
+            (_, ModAction::Dismiss) => {}
+            (None, _) => return Ok(Err(ReportError::NoTarget)),
+            (Some(target), ModAction::Warn) => db.update(target, |user: &mut InternalUser| {
+                user.notifications.push(Notification::System(
+                    "You were reported for breaking the rules, and a moderator agreed. Further reports may get your account banned.".to_string(),
+                ));
+            })?,
+            (Some(target), ModAction::Unpublish) => db.unpublish_user(target)?,
+            (Some(target), ModAction::Ban) => db.ban_user(target)?,
+        }
+        InternalReport::modify(db, uuid, |report| {
+            report.status = ReportStatus::Resolved;
+            report.resolution = Some(Resolution {
+                by: moderator.clone(),
+                at: chrono::Utc::now().timestamp(),
+                action,
+            });
+            Ok(())
+        })
+    }
+}
+
+impl Insertable for InternalReport {
+    fn version() -> u64 {
+        0
+    }
+
+    fn indexes() -> &'static [Index] {
+        &[REPORTS_BY_STATUS]
+    }
+
+    fn index_values(&self) -> Vec<(&'static Index, String)> {
+        vec![(&REPORTS_BY_STATUS, self.index_value())]
+    }
+}
+
+impl SaveTxn for InternalReport {
+    fn save_txn(&self, txn: &Txn) -> TxnResult<()> {
+        txn.write(&self.uuid, self)
+    }
+}
@@ -0,0 +1,191 @@
+use std::error::Error;
+
+use rand::Rng;
+
+use super::{
+    internal_user::InternalUser,
+    shared::{Insertable, InternalUuid},
+};
+use crate::{
+    db::DB,
+    index::Index,
+    transaction::{SaveTxn, Txn, TxnResult},
+    util::hash_secret,
+};
+
+//how long a reset code works for
+pub const RESET_CODE_MINUTES: i64 = 60;
+
+const RESET_CODE_BY_HASH: Index = Index::unique("reset_code.hash");
+const RESET_CODE_BY_USER: Index = Index::unique("reset_code.user");
+
+/// A single use code that sets a new password without the old one. Only its hash
+/// is stored, the code itself goes to the user through an admin or the mailer. A
+/// user has at most one, issuing another replaces it.
+#[derive(
+    Debug,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    serde::Serialize,
+    paperclip::actix::Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct InternalResetCode {
+    pub uuid: InternalUuid<InternalResetCode>,
+    pub user: InternalUuid<InternalUser>,
+    pub code_hash: String,
+    pub expires: i64,
+}
+
+/// Why a reset code was turned down.
+#[derive(Debug, PartialEq)]
+pub enum ResetError {
+    Invalid,
+    Expired,
+}
+
+impl ResetError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ResetError::Invalid => "Invalid reset code",
+            ResetError::Expired => "Reset code expired",
+        }
+    }
+}
+
+//xxxx-xxxx-xxxx, typed in by hand so letters only, and case doesn't matter
+fn new_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..3)
+        .map(|_| {
+            (0..4)
+                .map(|_| rng.gen_range(b'A'..=b'Z') as char)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn hash_code(code: &str) -> String {
+    hash_secret(&code.trim().to_uppercase())
+}
+
+impl InternalResetCode {
+    /// Makes a new code for the user, replacing any earlier one. Returns the code.
+    pub fn issue(
+        db: &DB,
+        user: &InternalUuid<InternalUser>,
+    ) -> Result<(InternalResetCode, String), Box<dyn Error>> {
+        let code = new_code();
+        let reset_code = InternalResetCode {
+            uuid: InternalUuid::new(),
+            user: user.clone(),
+            code_hash: hash_code(&code),
+            expires: (chrono::Utc::now() + chrono::Duration::minutes(RESET_CODE_MINUTES))
+                .timestamp(),
+        };
+        db.transaction(|txn| {
+            if let Some(old) = RESET_CODE_BY_USER.get_txn::<InternalResetCode>(txn, &user.id)? {
+                if let Some(old) = txn.read(&old)? {
+                    old.delete_txn(txn)?;
+                }
+            }
+            reset_code.save_txn(txn)
+        })?;
+        Ok((reset_code, code))
+    }
+
+    /// Spends the code and sets the password it was issued for. Returns whose it was,
+    /// their sessions are left for the caller to end.
+    pub fn redeem(
+        db: &DB,
+        code: &str,
+        hashed_password: &str,
+    ) -> Result<Result<InternalUuid<InternalUser>, ResetError>, Box<dyn Error>> {
+        let code_hash = hash_code(code);
+        db.transaction(|txn| {
+            let uuid = match RESET_CODE_BY_HASH.get_txn::<InternalResetCode>(txn, &code_hash)? {
+                Some(uuid) => uuid,
+                None => return Ok(Err(ResetError::Invalid)),
+            };
+            let reset_code = match txn.read(&uuid)? {
+                Some(reset_code) => reset_code,
+                None => return Ok(Err(ResetError::Invalid)),
+            };
+            //used up either way
+            reset_code.delete_txn(txn)?;
+            if reset_code.expires < chrono::Utc::now().timestamp() {
+                return Ok(Err(ResetError::Expired));
+            }
+            let mut user: InternalUser = match txn.read(&reset_code.user)? {
+                Some(user) => user,
+                None => return Ok(Err(ResetError::Invalid)),
+            };
+            user.hashed_password = hashed_password.to_string();
+            user.save_txn(txn)?;
+            Ok(Ok(reset_code.user.clone()))
+        })
+    }
+
+    fn delete_txn(&self, txn: &Txn) -> TxnResult<bool> {
+        txn.delete(&self.uuid)
+    }
+}
+
+impl Insertable for InternalResetCode {
+    fn version() -> u64 {
+        0
+    }
+
+    fn indexes() -> &'static [Index] {
+        &[RESET_CODE_BY_HASH, RESET_CODE_BY_USER]
+    }
+
+    fn index_values(&self) -> Vec<(&'static Index, String)> {
+        vec![
+            (&RESET_CODE_BY_HASH, self.code_hash.clone()),
+            (&RESET_CODE_BY_USER, self.user.id.clone()),
+        ]
+    }
+}
+
+impl SaveTxn for InternalResetCode {
+    fn save_txn(&self, txn: &Txn) -> TxnResult<()> {
+        txn.write(&self.uuid, self)
+    }
+}
+
+impl DB {
+    /// Drops the user's reset code if they have one.
+    pub fn delete_reset_code(
+        &self,
+        user: &InternalUuid<InternalUser>,
+    ) -> Result<bool, Box<dyn Error>> {
+        self.transaction(|txn| {
+            let uuid = match RESET_CODE_BY_USER.get_txn::<InternalResetCode>(txn, &user.id)? {
+                Some(uuid) => uuid,
+                None => return Ok(false),
+            };
+            match txn.read(&uuid)? {
+                Some(reset_code) => reset_code.delete_txn(txn),
+                None => Ok(false),
+            }
+        })
+    }
+
+    /// Drops reset codes nobody used in time, returns how many.
+    pub fn delete_expired_reset_codes(&self) -> Result<usize, Box<dyn Error>> {
+        let now = chrono::Utc::now().timestamp();
+        let reset_codes = self
+            .iter_obj::<InternalResetCode>()?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut deleted = 0;
+        for reset_code in reset_codes {
+            if reset_code.expires < now && self.transaction(|txn| reset_code.delete_txn(txn))? {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+}
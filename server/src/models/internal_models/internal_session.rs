@@ -0,0 +1,219 @@
+use std::error::Error;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+
+use super::{
+    internal_user::InternalUser,
+    shared::{Insertable, InternalUuid},
+};
+use crate::{
+    db::DB,
+    index::Index,
+    transaction::{SaveTxn, Txn, TxnResult},
+    util::hash_secret,
+};
+
+//how long a device stays logged in without using its refresh token
+pub const REFRESH_TOKEN_DAYS: i64 = 30;
+
+const SESSIONS_BY_USER: Index = Index::multi("sessions.user");
+
+/// A logged in device. Access tokens name the session they were issued under, so
+/// deleting it logs the device out. The refresh token is single use, each refresh
+/// hands out the next one.
+#[derive(
+    Debug,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    serde::Serialize,
+    paperclip::actix::Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct InternalSession {
+    pub uuid: InternalUuid<InternalSession>,
+    pub user: InternalUuid<InternalUser>,
+    //only the hash is kept, the token itself is only ever seen by the client
+    pub refresh_hash: String,
+    pub expires: i64,
+}
+
+/// Why a refresh token was turned down.
+#[derive(Debug, PartialEq)]
+pub enum RefreshError {
+    Invalid,
+    Expired,
+    //an older token of the session came back, someone else may have it
+    Reused,
+}
+
+impl RefreshError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            RefreshError::Invalid => "Invalid refresh token",
+            RefreshError::Expired => "Refresh token expired",
+            RefreshError::Reused => "Refresh token already used, session revoked",
+        }
+    }
+}
+
+fn new_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn refresh_expiry() -> i64 {
+    (chrono::Utc::now() + chrono::Duration::days(REFRESH_TOKEN_DAYS)).timestamp()
+}
+
+impl InternalSession {
+    /// Logs a new device in, returns the session and its first refresh token.
+    pub fn start(
+        db: &DB,
+        user: &InternalUuid<InternalUser>,
+    ) -> Result<(InternalSession, String), Box<dyn Error>> {
+        let secret = new_secret();
+        let session = InternalSession {
+            uuid: InternalUuid::new(),
+            user: user.clone(),
+            refresh_hash: hash_secret(&secret),
+            expires: refresh_expiry(),
+        };
+        db.transaction(|txn| session.save_txn(txn))?;
+        let token = format!("{}.{}", session.uuid.id, secret);
+        Ok((session, token))
+    }
+
+    /// Trades a refresh token for the next one. A token that was already used
+    /// revokes its whole session.
+    pub fn refresh(
+        db: &DB,
+        token: &str,
+    ) -> Result<Result<(InternalSession, String), RefreshError>, Box<dyn Error>> {
+        let (id, secret) = match token.split_once('.') {
+            Some(parts) => parts,
+            None => return Ok(Err(RefreshError::Invalid)),
+        };
+        let uuid: InternalUuid<InternalSession> = id.to_string().into();
+        let next_secret = new_secret();
+
+        db.transaction(|txn| {
+            let mut session = match txn.read(&uuid)? {
+                Some(session) => session,
+                None => return Ok(Err(RefreshError::Invalid)),
+            };
+            if session.refresh_hash != hash_secret(secret) {
+                session.end_txn(txn)?;
+                return Ok(Err(RefreshError::Reused));
+            }
+            if session.expires < chrono::Utc::now().timestamp() {
+                session.end_txn(txn)?;
+                return Ok(Err(RefreshError::Expired));
+            }
+            session.refresh_hash = hash_secret(&next_secret);
+            session.expires = refresh_expiry();
+            session.save_txn(txn)?;
+            let token = format!("{}.{}", session.uuid.id, next_secret);
+            Ok(Ok((session, token)))
+        })
+    }
+
+    /// Whether access tokens issued under the session are still good.
+    pub fn is_active(
+        db: &DB,
+        uuid: &InternalUuid<InternalSession>,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(db.object_exists(uuid)?)
+    }
+
+    pub fn end(&self, db: &DB) -> Result<bool, Box<dyn Error>> {
+        db.transaction(|txn| self.end_txn(txn))
+    }
+
+    fn end_txn(&self, txn: &Txn) -> TxnResult<bool> {
+        txn.delete(&self.uuid)
+    }
+}
+
+impl Insertable for InternalSession {
+    fn version() -> u64 {
+        0
+    }
+
+    fn indexes() -> &'static [Index] {
+        &[SESSIONS_BY_USER]
+    }
+
+    fn index_values(&self) -> Vec<(&'static Index, String)> {
+        vec![(&SESSIONS_BY_USER, self.user.id.clone())]
+    }
+}
+
+impl SaveTxn for InternalSession {
+    fn save_txn(&self, txn: &Txn) -> TxnResult<()> {
+        txn.write(&self.uuid, self)
+    }
+}
+
+impl DB {
+    pub fn get_sessions(
+        &self,
+        user: &InternalUuid<InternalUser>,
+    ) -> Result<Vec<InternalSession>, Box<dyn Error>> {
+        let mut sessions = vec![];
+        for uuid in SESSIONS_BY_USER.prefix::<InternalSession>(self, &format!("{}/", user.id))? {
+            if let Some(session) = uuid.load(self)? {
+                sessions.push(session);
+            }
+        }
+        Ok(sessions)
+    }
+
+    /// Logs the user out everywhere, returns how many sessions were ended.
+    pub fn end_all_sessions(
+        &self,
+        user: &InternalUuid<InternalUser>,
+    ) -> Result<usize, Box<dyn Error>> {
+        self.end_sessions_except(user, None)
+    }
+
+    /// Logs the user out everywhere but the device they're using.
+    pub fn end_other_sessions(
+        &self,
+        user: &InternalUuid<InternalUser>,
+        keep: &InternalUuid<InternalSession>,
+    ) -> Result<usize, Box<dyn Error>> {
+        self.end_sessions_except(user, Some(keep))
+    }
+
+    fn end_sessions_except(
+        &self,
+        user: &InternalUuid<InternalUser>,
+        keep: Option<&InternalUuid<InternalSession>>,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut ended = 0;
+        for session in self.get_sessions(user)? {
+            if Some(&session.uuid) != keep && session.end(self)? {
+                ended += 1;
+            }
+        }
+        Ok(ended)
+    }
+
+    /// Drops sessions whose refresh token ran out, returns how many.
+    pub fn end_expired_sessions(&self) -> Result<usize, Box<dyn Error>> {
+        let now = chrono::Utc::now().timestamp();
+        let sessions = self
+            .iter_obj::<InternalSession>()?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut ended = 0;
+        for session in sessions {
+            if session.expires < now && session.end(self)? {
+                ended += 1;
+            }
+        }
+        Ok(ended)
+    }
+}
@@ -1,21 +1,30 @@
 use paperclip::{actix::Apiv2Schema, v2::schema::Apiv2Schema};
 
-use crate::vec::shared::VectorSearch;
+use crate::transaction::{SaveTxn, Txn, TxnResult};
+use crate::vec::shared::{Bbox, VectorSearch};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 
 //TODO: recalc age on day change
 
 use super::{
     internal_chat::InternalChat,
+    internal_glicko::InternalGlicko,
     internal_image::InternalImage,
     internal_message::InternalMessage,
-    internal_prefs::{LabeledPreferenceRange, LabeledProperty},
-    internal_prefs_config::PREFS_CONFIG,
+    internal_prefs::{search_bbox, LabeledPreferenceRange, LabeledProperty},
+    internal_prefs_config::{prefs_config, PREFS_CARDINALITY},
     migration::migration::get_admin_uuid,
-    shared::{GetBbox, GetVector, Insertable, InternalUuid, Save},
+    shared::{GetVector, Insertable, InternalUuid, Save},
 };
 
 use crate::db::DB;
+use crate::index::Index;
+use crate::push::PushEvent;
+
+const BLOCKS_BY_USER: &str = "blocks.user";
+const BANNED_USERS: &str = "users.banned";
+pub const USERS_BY_USERNAME: Index = Index::unique("users.username");
 
 #[derive(Debug, Clone, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
 #[archive(compare(PartialEq), check_bytes)]
@@ -85,6 +94,32 @@ impl Apiv2Schema for InternalRating {
     }
 }
 
+/// What a staff member may do, each role can do everything the ones before it can.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    serde::Serialize,
+    serde::Deserialize,
+    paperclip::actix::Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub enum Role {
+    //looks users up and issues reset codes
+    Support,
+    //works the report queue, unpublishes and bans
+    Moderator,
+    //everything, including handing out roles
+    Admin,
+}
+
 #[derive(Debug, Clone, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
 #[archive(compare(PartialEq), check_bytes)]
 pub enum Action {
@@ -303,6 +338,7 @@ pub struct InternalUser {
     pub notifications: Vec<Notification>,
     pub published: bool,
     pub bot_props: Option<BotProps>,
+    pub role: Option<Role>,
 }
 
 impl InternalUser {
@@ -314,7 +350,11 @@ impl InternalUser {
     }
 
     pub fn is_admin(&self) -> bool {
-        self.uuid == get_admin_uuid()
+        self.has_role(Role::Admin)
+    }
+
+    pub fn has_role(&self, role: Role) -> bool {
+        self.role.is_some_and(|own| own >= role)
     }
 
     pub fn add_chat(&mut self, chat: &InternalChat) {
@@ -341,21 +381,21 @@ impl InternalUser {
         if self.preview_image.is_none() {
             return "You must have a preview image".to_string();
         }
-        let mandatory_props = PREFS_CONFIG
+        let mandatory_props = prefs_config()
             .iter()
             .filter(|p| p.non_optional_message.is_some())
-            .map(|p| p.name)
+            .map(|p| &p.name)
             .collect::<Vec<_>>();
 
         for prop in mandatory_props {
-            if !self.props.iter().any(|p| p.name == prop) {
+            if !self.props.iter().any(|p| &p.name == prop) {
                 return format!("You must have the property {}", prop);
             }
             //are any of the props -32768?
             if self
                 .props
                 .iter()
-                .any(|p| p.value == -32768 && p.name == prop)
+                .any(|p| p.value == -32768 && &p.name == prop)
             {
                 return format!("You must set a value for the property {}", prop);
             }
@@ -363,46 +403,317 @@ impl InternalUser {
         "".to_string()
     }
 
-    pub fn delete(&self, db: &DB) -> Result<(), Box<dyn Error>> {
-        db.delete_index("users.username", &self.username)?;
-        self.uuid.clone().delete(db)?;
-        let arc_clone = db.vec_index.clone();
-        let mut lock = arc_clone.lock().map_err(|_| "Could not lock vec_index")?;
-        lock.remove(&self.uuid.id);
-        lock.remove_bbox(&self.uuid.id);
-        Ok(())
+    /// Deletes the user along with everything that only makes sense with them around:
+    /// the images they uploaded, their chats and the messages in them, the ratings they
+    /// left on other users and their username. Other chat members lose the chat too.
+    pub fn delete(&self, db: &DB) -> Result<UserDeleteReport, Box<dyn Error>> {
+        let mut report = UserDeleteReport::default();
+        //other users we touch, saved once at the end
+        let mut others: HashMap<InternalUuid<InternalUser>, InternalUser> = HashMap::new();
+        let mut deleted_messages: HashSet<InternalUuid<InternalMessage>> = HashSet::new();
+
+        let owned_images: HashSet<_> = self.owned_images.iter().cloned().collect();
+        for image in owned_images.iter().cloned() {
+            if image.delete(db)? {
+                report.images += 1;
+            }
+        }
+
+        for chat_uuid in self.chats.iter() {
+            let chat = match chat_uuid.load(db)? {
+                Some(chat) => chat,
+                None => continue,
+            };
+            let messages = InternalMessage::delete_all_in_chat(db, chat_uuid)?;
+            report.messages += messages.len();
+            deleted_messages.extend(messages);
+            for member in chat.users.iter().filter(|u| *u != &self.uuid) {
+                if let Some(other) = self.load_other(db, &mut others, member)? {
+                    other.chats.retain(|c| c != chat_uuid);
+                }
+            }
+            if chat_uuid.clone().delete(db)? {
+                report.chats += 1;
+            }
+        }
+
+        for rated in self.seen.iter().filter(|u| *u != &self.uuid) {
+            if let Some(other) = self.load_other(db, &mut others, rated)? {
+                let before = other.ratings.len();
+                other.ratings.retain(|rating| match rating {
+                    InternalRating::LikedBy(uuid) | InternalRating::PassedBy(uuid) => {
+                        uuid != &self.uuid
+                    }
+                });
+                report.ratings += before - other.ratings.len();
+            }
+        }
+
+        for (_, mut other) in others {
+            other
+                .notifications
+                .retain(|notification| match notification {
+                    Notification::Match(uuid) => uuid != &self.uuid,
+                    Notification::UnreadMessage(uuid) => !deleted_messages.contains(uuid),
+                    Notification::System(_) => true,
+                });
+            other.save(db)?;
+        }
+
+        report.blocks = db.delete_blocks(&self.uuid)?;
+        report.sessions = db.end_all_sessions(&self.uuid)?;
+        report.reset_code = db.delete_reset_code(&self.uuid)?;
+        //the user's index keys go with them below
+        report.username_index =
+            USERS_BY_USERNAME.get::<InternalUser>(db, &self.username)? == Some(self.uuid.clone());
+        InternalGlicko::uuid_for(&self.uuid).delete(db)?;
+        {
+            let arc_clone = db.vec_index.clone();
+            let mut lock = arc_clone.lock().map_err(|_| "Could not lock vec_index")?;
+            lock.remove(&self.uuid.id);
+            lock.remove_bbox(&self.uuid.id);
+        }
+        //someone else's images on the profile only lose a reference, along with the user
+        report.user = db.transaction(|txn| {
+            for image in self
+                .image_refs()
+                .filter(|image| !owned_images.contains(image))
+            {
+                InternalImage::add_refs_txn(txn, image, -1)?;
+            }
+            txn.delete(&self.uuid)
+        })?;
+
+        log::info!("Deleted user {}: {:?}", self.uuid.id, report);
+        Ok(report)
+    }
+
+    /// Undoes a match: their chat and its messages are deleted for both of them, and
+    /// the likes between them are dropped so a new rating starts over. Returns whether
+    /// they had a chat. Blocking ends up here too, so the other side can't tell the two apart.
+    pub fn unmatch(
+        db: &DB,
+        user: &InternalUuid<InternalUser>,
+        other: &InternalUuid<InternalUser>,
+    ) -> Result<bool, Box<dyn Error>> {
+        let me = user.load(db)?.ok_or("User not found while unmatching")?;
+        let mut chat = None;
+        for chat_uuid in me.chats.iter() {
+            match chat_uuid.load(db)? {
+                Some(found) if found.users.contains(other) => {
+                    chat = Some(found);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let messages: HashSet<_> = match &chat {
+            Some(chat) => InternalMessage::in_chat(db, &chat.uuid)?
+                .into_iter()
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        db.retry_on_conflict(|| {
+            let mut pair = vec![];
+            for (uuid, them) in [(user, other), (other, user)] {
+                if let Some(mut versioned) = db.load_versioned(uuid)? {
+                    let value: &mut InternalUser = &mut versioned.value;
+                    if let Some(chat) = &chat {
+                        value.chats.retain(|c| c != &chat.uuid);
+                    }
+                    value.ratings.retain(|rating| match rating {
+                        InternalRating::LikedBy(uuid) => uuid != them,
+                        InternalRating::PassedBy(_) => true,
+                    });
+                    value
+                        .notifications
+                        .retain(|notification| match notification {
+                            Notification::Match(uuid) => uuid != them,
+                            Notification::UnreadMessage(uuid) => !messages.contains(uuid),
+                            Notification::System(_) => true,
+                        });
+                    pair.push(versioned);
+                }
+            }
+            db.transaction(|txn| {
+                for versioned in pair.iter() {
+                    txn.expect_version(&versioned.value.uuid, versioned.version)?;
+                    versioned.value.save_txn(txn)?;
+                }
+                if let Some(chat) = &chat {
+                    txn.delete(&chat.uuid)?;
+                    let chat_uuid = chat.uuid.clone();
+                    let users = chat.users.clone();
+                    txn.after_commit(move |db| {
+                        let event = PushEvent::ChatEnded {
+                            chat: chat_uuid.into(),
+                        };
+                        for user in users.iter() {
+                            db.push.push(user, &event);
+                        }
+                        Ok(())
+                    });
+                }
+                Ok(())
+            })
+        })?;
+
+        match chat {
+            Some(chat) => {
+                InternalMessage::delete_all_in_chat(db, &chat.uuid)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn load_other<'a>(
+        &self,
+        db: &DB,
+        others: &'a mut HashMap<InternalUuid<InternalUser>, InternalUser>,
+        uuid: &InternalUuid<InternalUser>,
+    ) -> Result<Option<&'a mut InternalUser>, Box<dyn Error>> {
+        if !others.contains_key(uuid) {
+            match uuid.load(db)? {
+                Some(other) => {
+                    others.insert(uuid.clone(), other);
+                }
+                None => return Ok(None),
+            }
+        }
+        Ok(others.get_mut(uuid))
     }
 }
 
+#[derive(Debug, Default, serde::Serialize, paperclip::actix::Apiv2Schema)]
+pub struct UserDeleteReport {
+    pub images: usize,
+    pub chats: usize,
+    pub messages: usize,
+    pub ratings: usize,
+    pub blocks: usize,
+    pub sessions: usize,
+    pub reset_code: bool,
+    pub username_index: bool,
+    pub user: bool,
+}
+
 impl Insertable for InternalUser {
     fn version() -> u64 {
-        1
+        4
+    }
+
+    fn indexes() -> &'static [Index] {
+        &[USERS_BY_USERNAME]
+    }
+
+    fn index_values(&self) -> Vec<(&'static Index, String)> {
+        vec![(&USERS_BY_USERNAME, self.username.clone())]
     }
 }
 
-impl Save for InternalUser {
-    fn save(self, db: &DB) -> Result<InternalUuid<InternalUser>, Box<dyn Error>> {
-        db.write_index("users.username", &self.username, &self.uuid)?;
-        self.uuid.write(&self, db)?;
-        let arc_clone = db.vec_index.clone();
-        let mut lock = arc_clone.lock().map_err(|_| "Could not lock vec_index")?;
+fn add_to_vec_index(
+    db: &DB,
+    uuid: &InternalUuid<InternalUser>,
+    vec: Option<([i16; PREFS_CARDINALITY], Bbox<PREFS_CARDINALITY>)>,
+) -> Result<(), Box<dyn Error>> {
+    let arc_clone = db.vec_index.clone();
+    let mut lock = arc_clone.lock().map_err(|_| "Could not lock vec_index")?;
+
+    if let Some((vec, bbox)) = vec {
+        lock.add(&vec, &uuid.id);
+        lock.add_bbox(&bbox, &uuid.id);
+    } else {
+        //unpublished users drop out of search, only journal the removal if they were in it
+        if lock.contains_vec(&uuid.id) {
+            lock.remove(&uuid.id);
+        }
+        if lock.contains_bbox(&uuid.id) {
+            lock.remove_bbox(&uuid.id);
+        }
+    }
+    Ok(())
+}
 
+impl InternalUser {
+    fn vec_index_entry(&self) -> Option<([i16; PREFS_CARDINALITY], Bbox<PREFS_CARDINALITY>)> {
         if self.published {
-            lock.add(&self.props.get_vector(), &self.uuid.id);
-            lock.add_bbox(&self.prefs.get_bbox(), &self.uuid.id);
+            Some((
+                self.props.get_vector(),
+                search_bbox(&self.prefs, &self.props),
+            ))
         } else {
-            log::info!("User not published, not adding to vec index")
+            None
+        }
+    }
+}
+
+impl InternalUser {
+    /// Every place on the user holding a reference to an image, one shown twice counts twice.
+    pub fn image_refs(&self) -> impl Iterator<Item = &InternalUuid<InternalImage>> {
+        self.owned_images
+            .iter()
+            .chain(self.images.iter())
+            .chain(self.preview_image.iter())
+    }
+
+    /// Writes a migrated user over its old layout, which can't be read to see which
+    /// image refs moved. They're left to `count_image_refs`.
+    pub fn save_migrated(self, db: &DB) -> Result<InternalUuid<InternalUser>, Box<dyn Error>> {
+        self.uuid.write(&self, db)?;
+        add_to_vec_index(db, &self.uuid, self.vec_index_entry())?;
+        Ok(self.uuid)
+    }
+}
+
+//moves the refs of the images put on or taken off the user, in the same transaction
+fn move_image_refs_txn(
+    txn: &Txn,
+    stored: Option<&InternalUser>,
+    user: &InternalUser,
+) -> TxnResult<()> {
+    let mut deltas: HashMap<&InternalUuid<InternalImage>, i32> = HashMap::new();
+    for image in stored.into_iter().flat_map(|stored| stored.image_refs()) {
+        *deltas.entry(image).or_default() -= 1;
+    }
+    for image in user.image_refs() {
+        *deltas.entry(image).or_default() += 1;
+    }
+    for (image, delta) in deltas {
+        if delta != 0 {
+            InternalImage::add_refs_txn(txn, image, delta)?;
         }
+    }
+    Ok(())
+}
+
+impl Save for InternalUser {
+    fn save(self, db: &DB) -> Result<InternalUuid<InternalUser>, Box<dyn Error>> {
+        db.transaction(|txn| self.save_txn(txn))?;
         Ok(self.uuid)
     }
 }
 
+impl SaveTxn for InternalUser {
+    fn save_txn(&self, txn: &Txn) -> TxnResult<()> {
+        let stored: Option<InternalUser> = txn.read(&self.uuid)?;
+        move_image_refs_txn(txn, stored.as_ref(), self)?;
+        txn.write(&self.uuid, self)?;
+        //the vec index isn't in sled, so it only learns about the user once the write is in
+        let uuid = self.uuid.clone();
+        let vec = self.vec_index_entry();
+        txn.after_commit(move |db| add_to_vec_index(db, &uuid, vec));
+        Ok(())
+    }
+}
+
 impl DB {
     pub fn get_user_by_username(
         &self,
-        username: &String,
+        username: &str,
     ) -> Result<Option<InternalUser>, Box<dyn Error>> {
-        let uuid = self.read_index::<InternalUser>("users.username", username)?;
+        let uuid = USERS_BY_USERNAME.get::<InternalUser>(self, username)?;
         match uuid {
             Some(uuid) => uuid.load(self),
             None => Ok(None),
@@ -412,4 +723,81 @@ impl DB {
         let uuid = get_admin_uuid();
         uuid.load(self)?.ok_or("Admin not found".into())
     }
+
+    /// Keeps `blocked` away from `blocker` for good, and ends any match between them.
+    pub fn block(
+        &self,
+        blocker: &InternalUuid<InternalUser>,
+        blocked: &InternalUuid<InternalUser>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.write_index(BLOCKS_BY_USER, &block_key(blocker, blocked), blocked)?;
+        InternalUser::unmatch(self, blocker, blocked)?;
+        Ok(())
+    }
+
+    pub fn has_blocked(
+        &self,
+        blocker: &InternalUuid<InternalUser>,
+        blocked: &InternalUuid<InternalUser>,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(self
+            .read_index::<InternalUser>(BLOCKS_BY_USER, &block_key(blocker, blocked))?
+            .is_some())
+    }
+
+    /// Everyone the user has blocked.
+    pub fn blocked_by(
+        &self,
+        blocker: &InternalUuid<InternalUser>,
+    ) -> Result<Vec<InternalUuid<InternalUser>>, Box<dyn Error>> {
+        Ok(self.read_index_prefix(BLOCKS_BY_USER, &format!("{}/", blocker.id))?)
+    }
+
+    /// Forgets the user's blocks, returns how many there were.
+    pub fn delete_blocks(
+        &self,
+        blocker: &InternalUuid<InternalUser>,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut deleted = 0;
+        for blocked in self.blocked_by(blocker)? {
+            if self.delete_index(BLOCKS_BY_USER, &block_key(blocker, &blocked))? {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+impl DB {
+    /// Takes the user out of search results until they publish their profile again.
+    pub fn unpublish_user(&self, user: &InternalUuid<InternalUser>) -> Result<(), Box<dyn Error>> {
+        self.update(user, |user: &mut InternalUser| user.published = false)
+    }
+
+    /// Unpublishes the user and logs them out everywhere, they can't log in again.
+    pub fn ban_user(&self, user: &InternalUuid<InternalUser>) -> Result<(), Box<dyn Error>> {
+        self.write_index(BANNED_USERS, &user.id, user)?;
+        self.unpublish_user(user)?;
+        self.end_all_sessions(user)?;
+        Ok(())
+    }
+
+    /// Lets the user log in again. Their profile stays unpublished until they publish it.
+    pub fn unban_user(&self, user: &InternalUuid<InternalUser>) -> Result<bool, Box<dyn Error>> {
+        Ok(self.delete_index(BANNED_USERS, &user.id)?)
+    }
+
+    pub fn is_banned(&self, user: &InternalUuid<InternalUser>) -> Result<bool, Box<dyn Error>> {
+        Ok(self
+            .read_index::<InternalUser>(BANNED_USERS, &user.id)?
+            .is_some())
+    }
+
+    pub fn banned_users(&self) -> Result<Vec<InternalUuid<InternalUser>>, Box<dyn Error>> {
+        Ok(self.read_index_prefix(BANNED_USERS, &"".to_string())?)
+    }
+}
+
+fn block_key(blocker: &InternalUuid<InternalUser>, blocked: &InternalUuid<InternalUser>) -> String {
+    format!("{}/{}", blocker.id, blocked.id)
 }
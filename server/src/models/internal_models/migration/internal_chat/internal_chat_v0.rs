@@ -0,0 +1,70 @@
+use crate::{
+    db::DB,
+    models::internal_models::{
+        internal_chat::InternalChat,
+        internal_message::InternalMessage,
+        internal_user::InternalUser,
+        migration::migration::Migratable,
+        shared::{Insertable, InternalUuid, Save},
+    },
+};
+
+/// A chat that listed every one of its messages.
+#[derive(
+    Debug,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    serde::Serialize,
+    paperclip::actix::Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct InternalChatV0 {
+    pub uuid: InternalUuid<InternalChat>,
+    pub users: Vec<InternalUuid<InternalUser>>,
+    pub unread: Vec<u32>,
+    pub messages: Vec<InternalUuid<InternalMessage>>,
+    pub most_recent_message: String,
+    pub most_recent_sender: Option<InternalUuid<InternalUser>>,
+    pub most_recent_message_sent_at: i64,
+}
+
+impl Migratable for InternalChatV0 {
+    type NextVersion = InternalChat;
+    type ExtraData = ();
+    fn migrate(
+        &self,
+        db: &DB,
+        _: (),
+    ) -> Result<InternalUuid<Self::NextVersion>, Box<dyn std::error::Error>> {
+        //messages are migrated first, so they're already in the current layout
+        for message_uuid in self.messages.iter() {
+            let mut message = match message_uuid.load(db)? {
+                Some(message) => message,
+                None => continue,
+            };
+            //the chat's list is what put a message in it
+            message.chat = self.uuid.clone();
+            message.index(db)?;
+        }
+        let chat = InternalChat {
+            uuid: self.uuid.clone(),
+            users: self.users.clone(),
+            unread: self.unread.clone(),
+            most_recent_message: self.most_recent_message.clone(),
+            most_recent_sender: self.most_recent_sender.clone(),
+            most_recent_message_sent_at: self.most_recent_message_sent_at,
+        };
+        chat.save(db)
+    }
+
+    fn migration_message() -> &'static str {
+        "Moving every chat's message list into the chat ordered message index"
+    }
+}
+
+impl Insertable for InternalChatV0 {
+    fn version() -> u64 {
+        0
+    }
+}
@@ -0,0 +1 @@
+pub mod internal_chat_v0;
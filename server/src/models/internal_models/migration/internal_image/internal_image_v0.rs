@@ -0,0 +1,65 @@
+use crate::{
+    db::DB,
+    models::internal_models::{
+        internal_image::{Access, InternalImage},
+        migration::migration::Migratable,
+        shared::{Insertable, InternalUuid, Save},
+    },
+    renditions::Renditions,
+};
+
+/// An image before it was processed, the bytes exactly as uploaded.
+#[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct InternalImageV0 {
+    pub uuid: InternalUuid<InternalImage>,
+    pub content: Vec<u8>,
+    pub access: Access,
+}
+
+impl Migratable for InternalImageV0 {
+    type NextVersion = InternalImage;
+    type ExtraData = ();
+    fn migrate(
+        &self,
+        db: &DB,
+        _: (),
+    ) -> Result<InternalUuid<Self::NextVersion>, Box<dyn std::error::Error>> {
+        let renditions = match Renditions::from_upload(&self.content) {
+            Ok(renditions) => renditions,
+            //nothing could show it before either, keep it as it was rather than lose it
+            Err(e) => {
+                log::warn!(
+                    "Keeping image {} unprocessed: {}",
+                    self.uuid.id,
+                    e.message()
+                );
+                Renditions {
+                    thumbnail: self.content.clone(),
+                    card: self.content.clone(),
+                    full: self.content.clone(),
+                }
+            }
+        };
+        //straight to the current layout, see `InternalImageV1`
+        let image = InternalImage {
+            uuid: self.uuid.clone(),
+            blob: db.put_blob(&renditions)?,
+            access: self.access.clone(),
+            refs: 0,
+            created_at: chrono::Utc::now().timestamp(),
+            dhash: renditions.dhash(),
+        };
+        image.save(db)
+    }
+
+    fn migration_message() -> &'static str {
+        "Re-encoding every existing image into renditions, dropping its metadata"
+    }
+}
+
+impl Insertable for InternalImageV0 {
+    fn version() -> u64 {
+        0
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{
+    db::DB,
+    models::internal_models::{
+        internal_image::{Access, InternalImage},
+        migration::migration::Migratable,
+        shared::{Insertable, InternalUuid, Save},
+    },
+    renditions::Renditions,
+};
+
+/// An image with its renditions stored inline, before the blob store.
+#[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct InternalImageV1 {
+    pub uuid: InternalUuid<InternalImage>,
+    pub renditions: Renditions,
+    pub access: Access,
+}
+
+impl Migratable for InternalImageV1 {
+    type NextVersion = InternalImage;
+    type ExtraData = ();
+    fn migrate(
+        &self,
+        db: &DB,
+        _: (),
+    ) -> Result<InternalUuid<Self::NextVersion>, Box<dyn std::error::Error>> {
+        //straight to the current layout, see `InternalImageV2`
+        let image = InternalImage {
+            uuid: self.uuid.clone(),
+            blob: db.put_blob(&self.renditions)?,
+            access: self.access.clone(),
+            //counted once every model is in the current layout, see `count_image_refs`
+            refs: 0,
+            created_at: chrono::Utc::now().timestamp(),
+            dhash: self.renditions.dhash(),
+        };
+        image.save(db)
+    }
+
+    fn migration_message() -> &'static str {
+        "Moving image renditions into the blob store, identical ones are stored once"
+    }
+}
+
+impl Insertable for InternalImageV1 {
+    fn version() -> u64 {
+        1
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{
+    db::DB,
+    models::internal_models::{
+        internal_image::{Access, InternalImage},
+        migration::migration::Migratable,
+        shared::{Insertable, InternalUuid, Save},
+    },
+};
+
+/// An image before it had a perceptual hash.
+#[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct InternalImageV2 {
+    pub uuid: InternalUuid<InternalImage>,
+    pub blob: String,
+    pub access: Access,
+    pub refs: u32,
+    pub created_at: i64,
+}
+
+impl Migratable for InternalImageV2 {
+    type NextVersion = InternalImage;
+    type ExtraData = ();
+    fn migrate(
+        &self,
+        db: &DB,
+        _: (),
+    ) -> Result<InternalUuid<Self::NextVersion>, Box<dyn std::error::Error>> {
+        let dhash = match db.read_blob(&self.blob)? {
+            Some(renditions) => renditions.dhash(),
+            None => {
+                log::warn!("Blob of image {} not found, not hashing it", self.uuid.id);
+                None
+            }
+        };
+        let image = InternalImage {
+            uuid: self.uuid.clone(),
+            blob: self.blob.clone(),
+            access: self.access.clone(),
+            refs: self.refs,
+            created_at: self.created_at,
+            dhash,
+        };
+        image.save(db)
+    }
+
+    fn migration_message() -> &'static str {
+        "Hashing every image so near duplicates can be found"
+    }
+}
+
+impl Insertable for InternalImageV2 {
+    fn version() -> u64 {
+        2
+    }
+}
@@ -0,0 +1,3 @@
+pub mod internal_image_v0;
+pub mod internal_image_v1;
+pub mod internal_image_v2;
@@ -0,0 +1,72 @@
+use crate::{
+    db::DB,
+    models::internal_models::{
+        internal_chat::InternalChat,
+        internal_image::InternalImage,
+        internal_message::{InternalMessage, ReadReceipt},
+        internal_user::InternalUser,
+        migration::migration::Migratable,
+        shared::{Insertable, InternalUuid},
+    },
+};
+
+/// A message before read receipts said when.
+#[derive(
+    Debug,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    serde::Serialize,
+    paperclip::actix::Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct InternalMessageV0 {
+    pub uuid: InternalUuid<InternalMessage>,
+    pub sent_at: i64,
+    pub edited: bool,
+    pub author: InternalUuid<InternalUser>,
+    pub content: String,
+    pub image: Option<InternalUuid<InternalImage>>,
+    pub read_by: Vec<InternalUuid<InternalUser>>,
+    pub chat: InternalUuid<InternalChat>,
+}
+
+impl Migratable for InternalMessageV0 {
+    type NextVersion = InternalMessage;
+    type ExtraData = ();
+    fn migrate(
+        &self,
+        db: &DB,
+        _: (),
+    ) -> Result<InternalUuid<Self::NextVersion>, Box<dyn std::error::Error>> {
+        let message = InternalMessage {
+            uuid: self.uuid.clone(),
+            sent_at: self.sent_at,
+            edited: self.edited,
+            author: self.author.clone(),
+            content: self.content.clone(),
+            image: self.image.clone(),
+            //when isn't known, sending is the earliest it could have been read
+            read_by: self
+                .read_by
+                .iter()
+                .map(|user| ReadReceipt {
+                    user: user.clone(),
+                    read_at: self.sent_at,
+                })
+                .collect(),
+            chat: self.chat.clone(),
+        };
+        message.uuid.write(&message, db)
+    }
+
+    fn migration_message() -> &'static str {
+        "Giving every existing read receipt the time its message was sent"
+    }
+}
+
+impl Insertable for InternalMessageV0 {
+    fn version() -> u64 {
+        0
+    }
+}
@@ -3,9 +3,12 @@ use crate::{
     models::internal_models::{
         internal_chat::InternalChat,
         internal_image::InternalImage,
-        internal_prefs::{LabeledPreferenceRange, LabeledProperty},
+        internal_prefs::LabeledProperty,
         internal_user::{BotProps, InternalRating, InternalUser, Notification, TimestampedAction},
-        migration::migration::{get_admin_uuid, Migratable},
+        migration::{
+            internal_user::internal_user_v1::LabeledPreferenceRangeV0,
+            migration::{get_admin_uuid, initial_role, Migratable},
+        },
         shared::{Insertable, InternalUuid, Save},
     },
 };
@@ -25,7 +28,7 @@ pub struct InternalUserV0 {
     pub display_name: String,
     pub description: String,
     pub birthdate: i64,
-    pub prefs: Vec<LabeledPreferenceRange>,
+    pub prefs: Vec<LabeledPreferenceRangeV0>,
     pub props: Vec<LabeledProperty>,
     pub owned_images: Vec<InternalUuid<InternalImage>>,
     pub actions: Vec<TimestampedAction>,
@@ -58,15 +61,16 @@ impl Migratable for InternalUserV0 {
                 display_name: self.display_name.clone(),
                 description: self.description.clone(),
                 birthdate: self.birthdate,
-                prefs: self.prefs.clone(),
+                prefs: self.prefs.iter().map(|pref| pref.migrate()).collect(),
                 props: self.props.clone(),
                 owned_images: self.owned_images.clone(),
                 actions: self.actions.clone(),
                 notifications: self.notifications.clone(),
                 published: self.published,
                 bot_props: self.bot_props.clone(),
+                role: initial_role(&self.uuid),
             };
-            return user.save(db);
+            return user.save_migrated(db);
         }
         let (chat, message) = InternalChat::new_admin_chat(&self.uuid);
         let chat_uuid = chat.save(db)?;
@@ -96,16 +100,17 @@ impl Migratable for InternalUserV0 {
             display_name: self.display_name.clone(),
             description: self.description.clone(),
             birthdate: self.birthdate,
-            prefs: self.prefs.clone(),
+            prefs: self.prefs.iter().map(|pref| pref.migrate()).collect(),
             props: self.props.clone(),
             owned_images: self.owned_images.clone(),
             actions: self.actions.clone(),
             notifications: self.notifications.clone(),
             published: self.published,
             bot_props: self.bot_props.clone(),
+            role: initial_role(&self.uuid),
         };
 
-        let user_uuid = user.save(db)?;
+        let user_uuid = user.save_migrated(db)?;
 
         message
             .into_internal(&get_admin_uuid(), &chat, db)?
@@ -0,0 +1,109 @@
+use crate::{
+    db::DB,
+    models::internal_models::{
+        internal_chat::InternalChat,
+        internal_image::InternalImage,
+        internal_prefs::{LabeledPreferenceRange, LabeledProperty, PreferenceRange},
+        internal_user::{BotProps, InternalRating, InternalUser, Notification, TimestampedAction},
+        migration::migration::{initial_role, Migratable},
+        shared::{Insertable, InternalUuid},
+    },
+};
+
+/// A pref before it had a strictness and a weight.
+#[derive(
+    Debug,
+    Clone,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    serde::Serialize,
+    paperclip::actix::Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct LabeledPreferenceRangeV0 {
+    pub name: String,
+    pub range: PreferenceRange,
+}
+
+impl LabeledPreferenceRangeV0 {
+    //every pref used to be a dealbreaker
+    pub fn migrate(&self) -> LabeledPreferenceRange {
+        LabeledPreferenceRange::dealbreaker(self.name.clone(), self.range.clone())
+    }
+}
+
+#[derive(
+    Debug,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    serde::Serialize,
+    paperclip::actix::Apiv2Schema,
+)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct InternalUserV1 {
+    pub uuid: InternalUuid<InternalUser>,
+    pub hashed_password: String,
+    pub elo: f32,
+    pub ratings: Vec<InternalRating>,
+    pub seen: Vec<InternalUuid<InternalUser>>,
+    pub chats: Vec<InternalUuid<InternalChat>>,
+    pub images: Vec<InternalUuid<InternalImage>>,
+    pub preview_image: Option<InternalUuid<InternalImage>>,
+    pub username: String,
+    pub display_name: String,
+    pub description: String,
+    pub birthdate: i64,
+    pub prefs: Vec<LabeledPreferenceRangeV0>,
+    pub props: Vec<LabeledProperty>,
+    pub owned_images: Vec<InternalUuid<InternalImage>>,
+    pub actions: Vec<TimestampedAction>,
+    pub notifications: Vec<Notification>,
+    pub published: bool,
+    pub bot_props: Option<BotProps>,
+}
+
+impl Migratable for InternalUserV1 {
+    type NextVersion = InternalUser;
+    type ExtraData = ();
+    fn migrate(
+        &self,
+        db: &DB,
+        _: (),
+    ) -> Result<InternalUuid<Self::NextVersion>, Box<dyn std::error::Error>> {
+        let user = InternalUser {
+            uuid: self.uuid.clone(),
+            hashed_password: self.hashed_password.clone(),
+            elo: self.elo,
+            ratings: self.ratings.clone(),
+            seen: self.seen.clone(),
+            chats: self.chats.clone(),
+            images: self.images.clone(),
+            preview_image: self.preview_image.clone(),
+            username: self.username.clone(),
+            display_name: self.display_name.clone(),
+            description: self.description.clone(),
+            birthdate: self.birthdate,
+            prefs: self.prefs.iter().map(|pref| pref.migrate()).collect(),
+            props: self.props.clone(),
+            owned_images: self.owned_images.clone(),
+            actions: self.actions.clone(),
+            notifications: self.notifications.clone(),
+            published: self.published,
+            bot_props: self.bot_props.clone(),
+            role: initial_role(&self.uuid),
+        };
+        user.save_migrated(db)
+    }
+
+    fn migration_message() -> &'static str {
+        "Making every existing pref a dealbreaker with the default weight"
+    }
+}
+
+impl Insertable for InternalUserV1 {
+    fn version() -> u64 {
+        1
+    }
+}
@@ -1 +1,4 @@
 pub mod internal_user_v0;
+pub mod internal_user_v1;
+pub mod internal_user_v2;
+pub mod internal_user_v3;
@@ -18,8 +18,19 @@ use crate::{
             internal_chat::InternalChat,
             internal_image::{Access, InternalImage},
             internal_message::InternalMessage,
-            internal_user::InternalUser,
-            migration::internal_user::internal_user_v0::InternalUserV0,
+            internal_user::{InternalUser, Role},
+            migration::{
+                internal_chat::internal_chat_v0::InternalChatV0,
+                internal_image::{
+                    internal_image_v0::InternalImageV0, internal_image_v1::InternalImageV1,
+                    internal_image_v2::InternalImageV2,
+                },
+                internal_message::internal_message_v0::InternalMessageV0,
+                internal_user::{
+                    internal_user_v0::InternalUserV0, internal_user_v1::InternalUserV1,
+                    internal_user_v2::InternalUserV2, internal_user_v3::InternalUserV3,
+                },
+            },
             shared::{Insertable, InternalUuid, Save},
         },
     },
@@ -64,35 +75,35 @@ impl DB {
         let current_version_message = InternalMessage::version();
         let db_version_access_code = self.get_version::<InternalAccessCode>()?;
         let current_version_access_code = InternalAccessCode::version();
-        if db_version_user < current_version_user {
+        //messages and chats first, migrating the oldest users writes new ones of both
+        if db_version_message < current_version_message {
             log::info!(
-                "Migrating InternalUser from version {} to {}",
-                db_version_user,
-                current_version_user
+                "Migrating InternalMessage from version {} to {}",
+                db_version_message,
+                current_version_message
             );
-
-            match db_version_user {
+            match db_version_message {
                 0 => {
-                    log::info!("{}", InternalUserV0::migration_message());
-                    log::info!("Db upgraded from version 0 to 1 for InternalUser");
-                    self.set_version::<InternalUser>(1)?;
-                    let admin_user = make_admin_user(self);
-                    admin_user.save(self)?;
-                    let users = self.iter_obj::<InternalUserV0>()?;
-                    for user in users {
-                        self.migrate_model(user?, ())?;
+                    log::info!("{}", InternalMessageV0::migration_message());
+                    let messages = self
+                        .iter_obj::<InternalMessageV0>()?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.set_version::<InternalMessage>(1)?;
+                    for message in messages {
+                        self.migrate_model(message, ())?;
                     }
-                    log::info!("Migrated InternalUser from version 0 to 1 successfully!");
+                    log::info!("Migrated InternalMessage from version 0 to 1 successfully!");
                 }
                 _ => {
                     return Err(format!(
-                        "Unknown version {} for InternalUser",
-                        db_version_user
+                        "Unknown version {} for InternalMessage",
+                        db_version_message
                     ))?;
                 }
             }
         }
 
+        //chats index their messages, so messages go first
         if db_version_chat < current_version_chat {
             log::info!(
                 "Migrating InternalChat from version {} to {}",
@@ -100,6 +111,17 @@ impl DB {
                 current_version_chat
             );
             match db_version_chat {
+                0 => {
+                    log::info!("{}", InternalChatV0::migration_message());
+                    let chats = self
+                        .iter_obj::<InternalChatV0>()?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.set_version::<InternalChat>(1)?;
+                    for chat in chats {
+                        self.migrate_model(chat, ())?;
+                    }
+                    log::info!("Migrated InternalChat from version 0 to 1 successfully!");
+                }
                 _ => {
                     return Err(format!(
                         "Unknown version {} for InternalChat",
@@ -109,6 +131,7 @@ impl DB {
             }
         }
 
+        //images before users, a new admin is saved with an image in the current layout
         if db_version_image < current_version_image {
             log::info!(
                 "Migrating InternalImage from version {} to {}",
@@ -116,6 +139,44 @@ impl DB {
                 current_version_image
             );
             match db_version_image {
+                0 => {
+                    log::info!("{}", InternalImageV0::migration_message());
+                    let images = self
+                        .iter_obj::<InternalImageV0>()?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    //v0 images are migrated straight to the current layout
+                    self.set_version::<InternalImage>(1)?;
+                    self.set_version::<InternalImage>(2)?;
+                    self.set_version::<InternalImage>(3)?;
+                    for image in images {
+                        self.migrate_model(image, ())?;
+                    }
+                    log::info!("Migrated InternalImage from version 0 to 3 successfully!");
+                }
+                1 => {
+                    log::info!("{}", InternalImageV1::migration_message());
+                    let images = self
+                        .iter_obj::<InternalImageV1>()?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    //v1 images are migrated straight to the current layout too
+                    self.set_version::<InternalImage>(2)?;
+                    self.set_version::<InternalImage>(3)?;
+                    for image in images {
+                        self.migrate_model(image, ())?;
+                    }
+                    log::info!("Migrated InternalImage from version 1 to 3 successfully!");
+                }
+                2 => {
+                    log::info!("{}", InternalImageV2::migration_message());
+                    let images = self
+                        .iter_obj::<InternalImageV2>()?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.set_version::<InternalImage>(3)?;
+                    for image in images {
+                        self.migrate_model(image, ())?;
+                    }
+                    log::info!("Migrated InternalImage from version 2 to 3 successfully!");
+                }
                 _ => {
                     return Err(format!(
                         "Unknown version {} for InternalImage",
@@ -125,17 +186,78 @@ impl DB {
             }
         }
 
-        if db_version_message < current_version_message {
+        if db_version_user < current_version_user {
             log::info!(
-                "Migrating InternalMessage from version {} to {}",
-                db_version_message,
-                current_version_message
+                "Migrating InternalUser from version {} to {}",
+                db_version_user,
+                current_version_user
             );
-            match db_version_message {
+
+            match db_version_user {
+                0 => {
+                    log::info!("{}", InternalUserV0::migration_message());
+                    //read everything first, the admin and the migrated users are written in
+                    //the current layout into the same bucket
+                    let users = self
+                        .iter_obj::<InternalUserV0>()?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    log::info!("Db upgraded from version 0 to 1 for InternalUser");
+                    self.set_version::<InternalUser>(1)?;
+                    //v0 users are migrated straight to the current layout, so there's nothing
+                    //left for the later migrations to do
+                    self.set_version::<InternalUser>(2)?;
+                    self.set_version::<InternalUser>(3)?;
+                    self.set_version::<InternalUser>(4)?;
+                    let admin_user = make_admin_user(self);
+                    admin_user.save(self)?;
+                    for user in users {
+                        self.migrate_model(user, ())?;
+                    }
+                    log::info!("Migrated InternalUser from version 0 to 1 successfully!");
+                }
+                1 => {
+                    log::info!("{}", InternalUserV1::migration_message());
+                    //read everything first, the bucket is rewritten in place
+                    let users = self
+                        .iter_obj::<InternalUserV1>()?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    //v1 users are migrated straight to the current layout too
+                    self.set_version::<InternalUser>(2)?;
+                    self.set_version::<InternalUser>(3)?;
+                    self.set_version::<InternalUser>(4)?;
+                    for user in users {
+                        self.migrate_model(user, ())?;
+                    }
+                    log::info!("Migrated InternalUser from version 1 to 4 successfully!");
+                }
+                2 => {
+                    log::info!("{}", InternalUserV2::migration_message());
+                    let users = self
+                        .iter_obj::<InternalUserV2>()?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    //v2 users are migrated straight to the current layout too
+                    self.set_version::<InternalUser>(3)?;
+                    self.set_version::<InternalUser>(4)?;
+                    for user in users {
+                        self.migrate_model(user, ())?;
+                    }
+                    log::info!("Migrated InternalUser from version 2 to 4 successfully!");
+                }
+                3 => {
+                    log::info!("{}", InternalUserV3::migration_message());
+                    let users = self
+                        .iter_obj::<InternalUserV3>()?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.set_version::<InternalUser>(4)?;
+                    for user in users {
+                        self.migrate_model(user, ())?;
+                    }
+                    log::info!("Migrated InternalUser from version 3 to 4 successfully!");
+                }
                 _ => {
                     return Err(format!(
-                        "Unknown version {} for InternalMessage",
-                        db_version_message
+                        "Unknown version {} for InternalUser",
+                        db_version_user
                     ))?;
                 }
             }
@@ -165,6 +287,15 @@ pub fn get_admin_uuid() -> InternalUuid<InternalUser> {
     InternalUuid::from_str(ADMIN_UUID)
 }
 
+//only the built in admin starts out with a role, the rest are handed out
+pub fn initial_role(uuid: &InternalUuid<InternalUser>) -> Option<Role> {
+    if *uuid == get_admin_uuid() {
+        Some(Role::Admin)
+    } else {
+        None
+    }
+}
+
 pub fn make_admin_user(db: &DB) -> InternalUser {
     let admin_password = "admin".to_string();
     // let admin_password = std::env::var("ADMIN_PASSWORD")
@@ -172,7 +303,7 @@ pub fn make_admin_user(db: &DB) -> InternalUser {
     //     .unwrap();
 
     let admin_image = ApiImageWritable::new_admin();
-    let internal_admin_image = admin_image.to_internal(Access::Everyone).unwrap();
+    let internal_admin_image = admin_image.to_internal(db, Access::Everyone).unwrap();
     let admin_image_uuid = internal_admin_image.save(db).unwrap();
 
     let admin = ApiUserWritable {
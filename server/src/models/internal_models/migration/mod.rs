@@ -1,2 +1,5 @@
-mod internal_user;
+pub mod internal_chat;
+pub mod internal_image;
+pub mod internal_message;
+pub mod internal_user;
 pub mod migration;
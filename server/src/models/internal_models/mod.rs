@@ -1,9 +1,13 @@
 pub mod internal_access_code;
 pub mod internal_chat;
+pub mod internal_glicko;
 pub mod internal_image;
 pub mod internal_message;
 pub mod internal_prefs;
 pub mod internal_prefs_config;
+pub mod internal_report;
+pub mod internal_reset_code;
+pub mod internal_session;
 pub mod internal_user;
 pub mod migration;
 pub mod shared;
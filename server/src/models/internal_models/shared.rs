@@ -1,4 +1,5 @@
 use crate::db::{DB, SCRATCH_SPACE_SIZE};
+use crate::index::{Index, RebuiltIndexes};
 use crate::vec::shared::Bbox;
 use paperclip::v2::schema::TypedData;
 use rkyv::ser::serializers::{
@@ -11,7 +12,13 @@ use std::error::Error;
 use std::marker::PhantomData;
 use uuid::Uuid;
 
-use super::internal_prefs_config::PREFS_CARDINALITY;
+use super::{
+    internal_access_code::InternalAccessCode, internal_chat::InternalChat,
+    internal_glicko::InternalGlicko, internal_image::InternalImage,
+    internal_message::InternalMessage, internal_prefs_config::PREFS_CARDINALITY,
+    internal_report::InternalReport, internal_reset_code::InternalResetCode,
+    internal_session::InternalSession, internal_user::InternalUser,
+};
 
 #[derive(Debug, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
 #[archive(compare(PartialEq), check_bytes)]
@@ -89,7 +96,7 @@ where
         Ok(exists)
     }
 
-    pub fn delete(self, db: &DB) -> Result<Option<std::convert::Infallible>, Box<dyn Error>> {
+    pub fn delete(self, db: &DB) -> Result<bool, Box<dyn Error>> {
         db.delete_object(&self)
     }
 
@@ -108,6 +115,14 @@ impl<InternalModel> InternalUuid<InternalModel> {
             _marker: PhantomData,
         }
     }
+
+    /// Like `new`, but later ones sort after earlier ones, down to the millisecond.
+    pub fn new_ordered() -> Self {
+        Self {
+            id: Uuid::now_v7().to_string(),
+            _marker: PhantomData,
+        }
+    }
 }
 
 impl<T> From<String> for InternalUuid<T> {
@@ -143,8 +158,55 @@ pub trait Insertable {
             "InternalUser" => "user",
             "InternalMessage" => "message",
             "InternalAccessCode" => "access_code",
+            "InternalGlicko" => "glicko",
+            "InternalSession" => "session",
+            "InternalResetCode" => "reset_code",
+            "InternalReport" => "report",
             _ => panic!("Unknown bucket"),
         }
     }
     fn version() -> u64;
+    /// The secondary indexes of the type, cleared and refilled by `DB::rebuild_indexes`.
+    fn indexes() -> &'static [Index] {
+        &[]
+    }
+    /// The values the object is indexed under, one of `indexes` each. Every write
+    /// stores these and drops whatever the previous write stored that isn't here.
+    fn index_values(&self) -> Vec<(&'static Index, String)> {
+        vec![]
+    }
 }
+
+/// A model with a bucket of its own. Transactions can touch the buckets and indexes
+/// of every model in `MODELS`, and `rebuild_indexes` goes over them too.
+pub struct Model {
+    pub bucket: fn() -> &'static str,
+    pub indexes: fn() -> &'static [Index],
+    pub rebuild_indexes: fn(&DB) -> Result<RebuiltIndexes, Box<dyn Error>>,
+}
+
+impl Model {
+    const fn of<T>() -> Self
+    where
+        T: rkyv::Archive + Insertable,
+        for<'a> T::Archived: rkyv::CheckBytes<DefaultValidator<'a>> + Deserialize<T, Infallible>,
+    {
+        Self {
+            bucket: T::bucket,
+            indexes: T::indexes,
+            rebuild_indexes: DB::rebuild_indexes::<T>,
+        }
+    }
+}
+
+pub const MODELS: [Model; 9] = [
+    Model::of::<InternalUser>(),
+    Model::of::<InternalChat>(),
+    Model::of::<InternalMessage>(),
+    Model::of::<InternalImage>(),
+    Model::of::<InternalAccessCode>(),
+    Model::of::<InternalGlicko>(),
+    Model::of::<InternalSession>(),
+    Model::of::<InternalResetCode>(),
+    Model::of::<InternalReport>(),
+];
@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+use serde::Serialize;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::models::{
+    api_models::{api_message::ApiMessage, shared::ApiUuid},
+    internal_models::{
+        internal_chat::InternalChat, internal_message::InternalMessage,
+        internal_user::InternalUser, shared::InternalUuid,
+    },
+};
+
+/// What gets pushed to a user's open sockets. Everything here can also be fetched
+/// from the polling routes, so a client that misses an event or has no socket just
+/// asks for it the old way.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum PushEvent {
+    NewMessage {
+        chat: ApiUuid<InternalChat>,
+        message: ApiMessage,
+    },
+    EditedMessage {
+        chat: ApiUuid<InternalChat>,
+        message: ApiMessage,
+    },
+    DeletedMessage {
+        chat: ApiUuid<InternalChat>,
+        message: ApiUuid<InternalMessage>,
+    },
+    Match {
+        user: ApiUuid<InternalUser>,
+        chat: ApiUuid<InternalChat>,
+    },
+    //the receiving user's unread count in the chat
+    Unread {
+        chat: ApiUuid<InternalChat>,
+        unread: u32,
+    },
+    //everything up to and including `message` has been read by `user`
+    Read {
+        chat: ApiUuid<InternalChat>,
+        user: ApiUuid<InternalUser>,
+        message: ApiUuid<InternalMessage>,
+        read_at: i64,
+    },
+    //the chat is gone, unmatched or blocked, the client drops it
+    ChatEnded {
+        chat: ApiUuid<InternalChat>,
+    },
+    //never stored, the client shows it for a few seconds unless it's repeated
+    Typing {
+        chat: ApiUuid<InternalChat>,
+        user: ApiUuid<InternalUser>,
+    },
+}
+
+//a user's sockets by id, keyed by user
+type Sockets = HashMap<String, Vec<(u64, UnboundedSender<String>)>>;
+
+/// The open sockets of every connected user, one user can have several devices.
+/// Pushing to someone without a socket does nothing.
+#[derive(Default)]
+pub struct PushHub {
+    next_id: AtomicU64,
+    connections: Mutex<Sockets>,
+}
+
+impl PushHub {
+    /// Registers a socket, returns its id and where its events arrive.
+    pub fn connect(&self, user: &InternalUuid<InternalUser>) -> (u64, UnboundedReceiver<String>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = unbounded_channel();
+        self.lock()
+            .entry(user.id.clone())
+            .or_default()
+            .push((id, sender));
+        (id, receiver)
+    }
+
+    pub fn disconnect(&self, user: &InternalUuid<InternalUser>, id: u64) {
+        let mut connections = self.lock();
+        if let Some(sockets) = connections.get_mut(&user.id) {
+            sockets.retain(|(socket, _)| *socket != id);
+            if sockets.is_empty() {
+                connections.remove(&user.id);
+            }
+        }
+    }
+
+    pub fn connected(&self, user: &InternalUuid<InternalUser>) -> usize {
+        self.lock().get(&user.id).map_or(0, |sockets| sockets.len())
+    }
+
+    pub fn push(&self, user: &InternalUuid<InternalUser>, event: &PushEvent) {
+        let mut connections = self.lock();
+        let sockets = match connections.get_mut(&user.id) {
+            Some(sockets) => sockets,
+            None => return,
+        };
+        let json = match serde_json::to_string(event) {
+            Ok(json) => json,
+            Err(e) => {
+                log::error!("Failed to serialize push event {:?}", e);
+                return;
+            }
+        };
+        //a failed send is a socket that closed without disconnecting
+        sockets.retain(|(_, sender)| sender.send(json.clone()).is_ok());
+        if sockets.is_empty() {
+            connections.remove(&user.id);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Sockets> {
+        //only senders in here, nothing a panic could leave half done
+        self.connections.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_reaches_every_socket_of_the_user() {
+        let hub = PushHub::default();
+        let alice: InternalUuid<InternalUser> = InternalUuid::new();
+        let bob: InternalUuid<InternalUser> = InternalUuid::new();
+        let (phone, mut phone_events) = hub.connect(&alice);
+        let (_, mut laptop_events) = hub.connect(&alice);
+        let (_, mut bob_events) = hub.connect(&bob);
+        assert_eq!(hub.connected(&alice), 2);
+
+        let event = PushEvent::Unread {
+            chat: InternalUuid::<InternalChat>::new().into(),
+            unread: 3,
+        };
+        hub.push(&alice, &event);
+        let json = phone_events.try_recv().unwrap();
+        assert!(json.contains(r#""type":"Unread""#));
+        assert_eq!(laptop_events.try_recv().unwrap(), json);
+        assert!(bob_events.try_recv().is_err());
+
+        //closed sockets are dropped, whether they said goodbye or not
+        hub.disconnect(&alice, phone);
+        drop(laptop_events);
+        hub.push(&alice, &event);
+        assert_eq!(hub.connected(&alice), 0);
+        assert_eq!(hub.connected(&bob), 1);
+    }
+}
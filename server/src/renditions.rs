@@ -0,0 +1,267 @@
+use std::io::Cursor;
+
+use image::{
+    codecs::jpeg::JpegEncoder,
+    imageops::FilterType,
+    io::{Limits, Reader as ImageReader},
+    DynamicImage,
+};
+
+//uploads bigger than this are turned away before decoding
+pub const MAX_UPLOAD_BYTES: usize = 15 * 1024 * 1024;
+//and so are images claiming to be bigger than this on either side
+const MAX_UPLOAD_DIMENSION: u32 = 12_000;
+
+//longest side of each rendition
+pub const THUMBNAIL_SIZE: u32 = 160;
+pub const CARD_SIZE: u32 = 640;
+pub const FULL_SIZE: u32 = 1600;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// Why an upload couldn't be turned into renditions.
+#[derive(Debug, PartialEq)]
+pub enum RenditionError {
+    TooLarge,
+    Unreadable,
+    EncodeFailed,
+}
+
+impl RenditionError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            RenditionError::TooLarge => "Image too large",
+            RenditionError::Unreadable => "Unreadable image",
+            RenditionError::EncodeFailed => "Failed to encode image",
+        }
+    }
+}
+
+/// Every size an image is kept in, all jpegs. They're encoded from the decoded
+/// pixels, so nothing from the upload's metadata, GPS included, survives.
+#[derive(Debug, Clone, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(compare(PartialEq), check_bytes)]
+pub struct Renditions {
+    pub thumbnail: Vec<u8>,
+    pub card: Vec<u8>,
+    pub full: Vec<u8>,
+}
+
+impl Renditions {
+    pub fn from_upload(bytes: &[u8]) -> Result<Renditions, RenditionError> {
+        if bytes.len() > MAX_UPLOAD_BYTES {
+            return Err(RenditionError::TooLarge);
+        }
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_UPLOAD_DIMENSION);
+        limits.max_image_height = Some(MAX_UPLOAD_DIMENSION);
+        let mut reader = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .map_err(|_| RenditionError::Unreadable)?;
+        reader.limits(limits);
+        let img = reader.decode().map_err(|e| match e {
+            image::ImageError::Limits(_) => RenditionError::TooLarge,
+            _ => RenditionError::Unreadable,
+        })?;
+        //phones store photos sideways and leave the turning to the exif, which is dropped
+        let img = match jpeg_orientation(bytes) {
+            Some(orientation) => orient(img, orientation),
+            None => img,
+        };
+
+        Ok(Renditions {
+            thumbnail: encode(&shrink(&img, THUMBNAIL_SIZE))?,
+            card: encode(&shrink(&img, CARD_SIZE))?,
+            full: encode(&shrink(&img, FULL_SIZE))?,
+        })
+    }
+
+    /// The perceptual hash of the image, None when the thumbnail doesn't decode.
+    pub fn dhash(&self) -> Option<u64> {
+        image::load_from_memory(&self.thumbnail)
+            .ok()
+            .map(|img| dhash(&img))
+    }
+}
+
+/// Difference hash: one bit per neighbouring pixel pair of a 9x8 grayscale copy,
+/// set where brightness drops left to right. Re-encoding, resizing and small edits
+/// only flip a few bits, so near identical photos end up a short hamming distance apart.
+pub fn dhash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+//never scales up
+fn shrink(img: &DynamicImage, size: u32) -> DynamicImage {
+    if img.width() <= size && img.height() <= size {
+        img.clone()
+    } else {
+        img.resize(size, size, FilterType::Triangle)
+    }
+}
+
+fn encode(img: &DynamicImage) -> Result<Vec<u8>, RenditionError> {
+    let rgb = img.to_rgb8();
+    let mut buf = Vec::new();
+    JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY)
+        .encode(
+            &rgb,
+            rgb.width(),
+            rgb.height(),
+            image::ExtendedColorType::Rgb8,
+        )
+        .map_err(|_| RenditionError::EncodeFailed)?;
+    Ok(buf)
+}
+
+fn orient(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// The exif orientation tag of a jpeg, if it has one.
+fn jpeg_orientation(bytes: &[u8]) -> Option<u16> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut pos = 2;
+    //walk the segments up to the image data, exif is in an APP1 near the start
+    while pos + 4 <= bytes.len() && bytes[pos] == 0xFF {
+        let marker = bytes[pos + 1];
+        let len = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let segment = bytes.get(pos + 4..pos + 2 + len)?;
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return tiff_orientation(&segment[6..]);
+        }
+        if marker == 0xDA {
+            return None;
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let little = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| -> Option<u16> {
+        let b = tiff.get(at..at + 2)?;
+        Some(if little {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        })
+    };
+    let u32_at = |at: usize| -> Option<u32> {
+        let b = tiff.get(at..at + 4)?;
+        Some(if little {
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        })
+    };
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    for i in 0..entries {
+        let entry = ifd + 2 + i * 12;
+        if u16_at(entry)? == 0x0112 {
+            return u16_at(entry + 8);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a jpeg header with just an exif segment holding the orientation
+    fn exif_jpeg(orientation: u16) -> Vec<u8> {
+        let mut tiff = b"MM\0\x2a\0\0\0\x08".to_vec();
+        tiff.extend_from_slice(&1u16.to_be_bytes());
+        tiff.extend_from_slice(&0x0112u16.to_be_bytes());
+        tiff.extend_from_slice(&3u16.to_be_bytes());
+        tiff.extend_from_slice(&1u32.to_be_bytes());
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        let mut segment = b"Exif\0\0".to_vec();
+        segment.extend(tiff);
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend_from_slice(&((segment.len() + 2) as u16).to_be_bytes());
+        jpeg.extend(segment);
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0, 2]);
+        jpeg
+    }
+
+    #[test]
+    fn reads_orientation() {
+        assert_eq!(jpeg_orientation(&exif_jpeg(6)), Some(6));
+        assert_eq!(jpeg_orientation(&exif_jpeg(1)), Some(1));
+        assert_eq!(jpeg_orientation(&[0xFF, 0xD8, 0xFF, 0xDA, 0, 2]), None);
+        assert_eq!(jpeg_orientation(b"not a jpeg"), None);
+    }
+
+    #[test]
+    fn near_identical_images_hash_close() {
+        let original = DynamicImage::ImageRgb8(image::RgbImage::from_fn(300, 200, |x, y| {
+            let v = 128.0 + 100.0 * (x as f32 / 40.0).sin() * (y as f32 / 30.0).cos();
+            image::Rgb([v as u8, v as u8 / 2, 255 - v as u8])
+        }));
+        let renditions = {
+            let mut png = Vec::new();
+            original
+                .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+                .unwrap();
+            Renditions::from_upload(&png).unwrap()
+        };
+        //re-encoded and shrunk, it still hashes about the same
+        let reencoded = renditions.dhash().unwrap();
+        assert!(hamming_distance(dhash(&original), reencoded) <= 4);
+        assert!(hamming_distance(dhash(&original), dhash(&original.fliph())) > 16);
+    }
+
+    #[test]
+    fn renditions_are_capped() {
+        let img = DynamicImage::new_rgb8(2000, 1000);
+        let mut png = Vec::new();
+        img.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let renditions = Renditions::from_upload(&png).unwrap();
+        let size = |bytes: &[u8]| {
+            let img = image::load_from_memory(bytes).unwrap();
+            (img.width(), img.height())
+        };
+        assert_eq!(size(&renditions.thumbnail), (160, 80));
+        assert_eq!(size(&renditions.card), (640, 320));
+        assert_eq!(size(&renditions.full), (1600, 800));
+        assert_eq!(
+            Renditions::from_upload(b"garbage").unwrap_err(),
+            RenditionError::Unreadable
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use actix_web::{Error, HttpRequest};
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::DB,
+    models::{
+        api_models::shared::ApiUuid,
+        internal_models::{
+            internal_image::{photo_clusters, InternalImage},
+            internal_user::{InternalUser, Notification, Role, TimestampedAction},
+            migration::migration::get_admin_uuid,
+            shared::InternalUuid,
+        },
+    },
+    routes::shared::{require_role, route_body_mut_db, write_error},
+    util,
+};
+
+const DEFAULT_SEARCH_RESULTS: usize = 50;
+const MAX_SEARCH_RESULTS: usize = 200;
+//a single request can't flood the db with codes
+const MAX_ACCESS_CODES: usize = 1000;
+
+/// A user as staff see them.
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct StaffUserView {
+    pub uuid: ApiUuid<InternalUser>,
+    pub username: String,
+    pub display_name: String,
+    pub description: String,
+    pub birthdate: i64,
+    pub elo: f32,
+    pub published: bool,
+    pub banned: bool,
+    pub role: Option<Role>,
+    pub chats: usize,
+    pub images: usize,
+}
+
+impl StaffUserView {
+    fn new(db: &DB, user: &InternalUser) -> Result<Self, Error> {
+        let banned = db
+            .is_banned(&user.uuid)
+            .map_err(|e| write_error(e, "Failed to check ban"))?;
+        Ok(Self {
+            uuid: user.uuid.clone().into(),
+            username: user.username.clone(),
+            display_name: user.display_name.clone(),
+            description: user.description.clone(),
+            birthdate: user.birthdate,
+            elo: user.elo,
+            published: user.published,
+            banned,
+            role: user.role,
+            chats: user.chats.len(),
+            images: user.images.len(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct StaffUserActivity {
+    pub user: StaffUserView,
+    pub actions: Vec<TimestampedAction>,
+    pub notifications: Vec<Notification>,
+    pub seen: usize,
+    pub ratings: usize,
+}
+
+fn all_users(db: &DB) -> Result<Vec<InternalUser>, Error> {
+    let users = db.iter_obj::<InternalUser>().map_err(|e| {
+        log::error!("Failed to read users {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to read users")
+    })?;
+    users.collect::<Result<Vec<_>, _>>().map_err(|e| {
+        log::error!("Failed to read users {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to read users")
+    })
+}
+
+fn load_user(db: &DB, user_uuid: ApiUuid<InternalUser>) -> Result<InternalUser, Error> {
+    let user_uuid: InternalUuid<InternalUser> = user_uuid.into();
+    user_uuid
+        .load(db)
+        .map_err(|e| write_error(e, "Failed to get user"))?
+        .ok_or(actix_web::error::ErrorNotFound("User not found"))
+}
+
+//moderators deal with users, staff are left to admins
+fn can_act_on(staff: &InternalUser, target: &InternalUser) -> Result<(), Error> {
+    if target.uuid == get_admin_uuid() || (target.role.is_some() && !staff.is_admin()) {
+        return Err(actix_web::error::ErrorForbidden("Can't act on this user"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct SearchUsersInput {
+    //matched against usernames and display names, ignoring case
+    query: String,
+    limit: Option<usize>,
+}
+
+/// Support and up. Users whose username or display name contains the query.
+#[api_v2_operation]
+#[post("/search_users")]
+pub fn search_users(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<SearchUsersInput>,
+) -> Result<Json<Vec<StaffUserView>>, Error> {
+    route_body_mut_db(db, req, body, |db, staff, body| {
+        require_role(&staff, Role::Support)?;
+        let limit = body
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_RESULTS)
+            .min(MAX_SEARCH_RESULTS);
+        let query = body.query.to_lowercase();
+        let mut found = vec![];
+        for user in all_users(db)? {
+            if found.len() >= limit {
+                break;
+            }
+            if user.username.to_lowercase().contains(&query)
+                || user.display_name.to_lowercase().contains(&query)
+            {
+                found.push(StaffUserView::new(db, &user)?);
+            }
+        }
+        Ok(found)
+    })
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct UserInput {
+    user_uuid: ApiUuid<InternalUser>,
+}
+
+/// Support and up. A user's profile along with what they've been doing.
+#[api_v2_operation]
+#[post("/get_user_activity")]
+pub fn get_user_activity(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<UserInput>,
+) -> Result<Json<StaffUserActivity>, Error> {
+    route_body_mut_db(db, req, body, |db, staff, body| {
+        require_role(&staff, Role::Support)?;
+        let user = load_user(db, body.user_uuid)?;
+        Ok(StaffUserActivity {
+            user: StaffUserView::new(db, &user)?,
+            seen: user.seen.len(),
+            ratings: user.ratings.len(),
+            actions: user.actions,
+            notifications: user.notifications,
+        })
+    })
+}
+
+/// Moderators and up. Takes the user out of search results.
+#[api_v2_operation]
+#[post("/unpublish_user")]
+pub fn unpublish_user(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<UserInput>,
+) -> Result<Json<bool>, Error> {
+    route_body_mut_db(db, req, body, |db, staff, body| {
+        require_role(&staff, Role::Moderator)?;
+        let user = load_user(db, body.user_uuid)?;
+        can_act_on(&staff, &user)?;
+        db.unpublish_user(&user.uuid)
+            .map_err(|e| write_error(e, "Failed to unpublish user"))?;
+        Ok(true)
+    })
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct BanUserInput {
+    user_uuid: ApiUuid<InternalUser>,
+    //false lifts the ban
+    banned: bool,
+}
+
+/// Moderators and up. Bans the user, logging them out everywhere, or lifts the ban.
+#[api_v2_operation]
+#[post("/ban_user")]
+pub fn ban_user(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<BanUserInput>,
+) -> Result<Json<bool>, Error> {
+    route_body_mut_db(db, req, body, |db, staff, body| {
+        require_role(&staff, Role::Moderator)?;
+        let user = load_user(db, body.user_uuid)?;
+        can_act_on(&staff, &user)?;
+        if body.banned {
+            db.ban_user(&user.uuid)
+                .map_err(|e| write_error(e, "Failed to ban user"))?;
+        } else {
+            db.unban_user(&user.uuid)
+                .map_err(|e| write_error(e, "Failed to unban user"))?;
+        }
+        Ok(body.banned)
+    })
+}
+
+/// Accounts showing near identical photos, and the photos.
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct StaffPhotoCluster {
+    pub users: Vec<StaffUserView>,
+    pub images: Vec<ApiUuid<InternalImage>>,
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct ListPhotoClustersInput {
+    limit: Option<usize>,
+}
+
+/// Moderators and up. Groups of accounts showing near identical photos, biggest first,
+/// catfish tend to reuse theirs.
+#[api_v2_operation]
+#[post("/list_photo_clusters")]
+pub fn list_photo_clusters(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<ListPhotoClustersInput>,
+) -> Result<Json<Vec<StaffPhotoCluster>>, Error> {
+    route_body_mut_db(db, req, body, |db, staff, body| {
+        require_role(&staff, Role::Moderator)?;
+        let limit = body
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_RESULTS)
+            .min(MAX_SEARCH_RESULTS);
+        let users = all_users(db)?;
+        let clusters =
+            photo_clusters(db, &users).map_err(|e| write_error(e, "Failed to cluster photos"))?;
+        let by_uuid: HashMap<_, _> = users.iter().map(|user| (&user.uuid, user)).collect();
+        clusters
+            .into_iter()
+            .take(limit)
+            .map(|cluster| {
+                Ok(StaffPhotoCluster {
+                    users: cluster
+                        .users
+                        .iter()
+                        .filter_map(|uuid| by_uuid.get(uuid))
+                        .map(|user| StaffUserView::new(db, user))
+                        .collect::<Result<_, _>>()?,
+                    images: cluster.images.into_iter().map(Into::into).collect(),
+                })
+            })
+            .collect()
+    })
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct SetRoleInput {
+    user_uuid: ApiUuid<InternalUser>,
+    //none takes the role away
+    role: Option<Role>,
+}
+
+/// Admin only. Hands out or takes away a staff role.
+#[api_v2_operation]
+#[post("/set_role")]
+pub fn set_role(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<SetRoleInput>,
+) -> Result<Json<bool>, Error> {
+    route_body_mut_db(db, req, body, |db, staff, body| {
+        require_role(&staff, Role::Admin)?;
+        let user = load_user(db, body.user_uuid)?;
+        //an admin can't lock themselves or the built in admin out
+        if user.uuid == staff.uuid || user.uuid == get_admin_uuid() {
+            return Err(actix_web::error::ErrorForbidden(
+                "Can't change this user's role",
+            ));
+        }
+        let role = body.role;
+        db.update(&user.uuid, |user: &mut InternalUser| user.role = role)
+            .map_err(|e| write_error(e, "Failed to set role"))?;
+        Ok(true)
+    })
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct BroadcastInput {
+    message: String,
+}
+
+/// Admin only. Sends a system notification to every user, returns how many got it.
+#[api_v2_operation]
+#[post("/broadcast")]
+pub fn broadcast(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<BroadcastInput>,
+) -> Result<Json<usize>, Error> {
+    route_body_mut_db(db, req, body, |db, staff, body| {
+        require_role(&staff, Role::Admin)?;
+        let users = all_users(db)?;
+        for user in users.iter() {
+            db.update(&user.uuid, |user: &mut InternalUser| {
+                user.notifications
+                    .push(Notification::System(body.message.clone()))
+            })
+            .map_err(|e| write_error(e, "Failed to send broadcast"))?;
+        }
+        Ok(users.len())
+    })
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct GenerateAccessCodesInput {
+    count: usize,
+}
+
+/// Admin only. Makes new signup access codes and returns them.
+#[api_v2_operation]
+#[post("/generate_access_codes")]
+pub fn generate_access_codes(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<GenerateAccessCodesInput>,
+) -> Result<Json<Vec<String>>, Error> {
+    route_body_mut_db(db, req, body, |db, staff, body| {
+        require_role(&staff, Role::Admin)?;
+        if body.count > MAX_ACCESS_CODES {
+            return Err(actix_web::error::ErrorBadRequest("Too many access codes"));
+        }
+        util::generate_access_codes(body.count, db).map_err(|e| {
+            log::error!("Failed to generate access codes {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to generate access codes")
+        })
+    })
+}
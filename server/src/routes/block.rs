@@ -0,0 +1,50 @@
+use actix_web::{Error, HttpRequest};
+
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::DB,
+    models::{
+        api_models::shared::ApiUuid,
+        internal_models::{
+            internal_user::InternalUser, migration::migration::get_admin_uuid, shared::InternalUuid,
+        },
+    },
+    routes::shared::{route_body_mut_db, write_error},
+};
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct BlockInput {
+    user_uuid: ApiUuid<InternalUser>,
+}
+
+/// Blocks the user: any match is ended and they're never shown to you again.
+/// To them it looks like an unmatch.
+#[api_v2_operation]
+#[post("/block")]
+pub fn block(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<BlockInput>,
+) -> Result<Json<bool>, Error> {
+    route_body_mut_db(db, req, body, |db, user, body| {
+        let other: InternalUuid<InternalUser> = body.user_uuid.into();
+        if other == user.uuid || other == get_admin_uuid() {
+            return Err(actix_web::error::ErrorBadRequest("Can't block this user"));
+        }
+        let exists = other
+            .load(db)
+            .map_err(|e| write_error(e, "Failed to get user"))?;
+        if exists.is_none() {
+            return Err(actix_web::error::ErrorNotFound("User not found"));
+        }
+        db.block(&user.uuid, &other)
+            .map_err(|e| write_error(e, "Failed to block user"))?;
+        Ok(true)
+    })
+}
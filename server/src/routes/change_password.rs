@@ -0,0 +1,77 @@
+use std::time::Instant;
+
+use actix_web::{Error, HttpMessage, HttpRequest};
+use bcrypt::verify;
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::DB,
+    middleware::rate_limit::{LoginLockouts, TooManyRequests},
+    models::internal_models::{
+        internal_session::InternalSession, internal_user::InternalUser, shared::InternalUuid,
+    },
+    routes::{
+        common::hash_new_password,
+        shared::{route_body_mut_db, write_error},
+    },
+};
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct ChangePasswordRequest {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+/// Sets a new password given the current one, and logs out every other device.
+/// Returns how many sessions were ended.
+#[api_v2_operation]
+#[post("/change_password")]
+async fn change_password(
+    db: web::Data<DB>,
+    lockouts: web::Data<LoginLockouts>,
+    req: HttpRequest,
+    body: Json<ChangePasswordRequest>,
+) -> Result<Json<usize>, Error> {
+    let session = req
+        .extensions()
+        .get::<InternalUuid<InternalSession>>()
+        .cloned()
+        .ok_or(actix_web::error::ErrorBadRequest("Session not in request"))?;
+    route_body_mut_db(db, req, body, |db, user, body| {
+        //wrong old passwords count as failed logins, or this would be a way around them
+        let now = Instant::now();
+        lockouts
+            .check(&user.username, now)
+            .map_err(|retry_after| TooManyRequests { retry_after })?;
+        let password_matches = verify(&body.old_password, &user.hashed_password).map_err(|e| {
+            log::error!("Failed to verify password {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to verify password")
+        })?;
+        if !password_matches {
+            lockouts.failed(&user.username, now);
+            return Err(actix_web::error::ErrorBadRequest("Incorrect password"));
+        }
+        lockouts.succeeded(&user.username);
+
+        let hashed_password = hash_new_password(&body.new_password)?;
+        db.update(&user.uuid, |user: &mut InternalUser| {
+            user.hashed_password = hashed_password.clone()
+        })
+        .map_err(|e| write_error(e, "Failed to change password"))?;
+
+        //a reset code sent before the change would still undo it
+        db.delete_reset_code(&user.uuid).map_err(|e| {
+            log::error!("Failed to delete reset code {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to delete reset code")
+        })?;
+        db.end_other_sessions(&user.uuid, &session).map_err(|e| {
+            log::error!("Failed to end sessions {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to end sessions")
+        })
+    })
+}
@@ -1,10 +1,67 @@
-use paperclip::actix::Apiv2Schema;
+use paperclip::actix::{web::Json, Apiv2Schema};
 use serde::{Deserialize, Serialize};
 
-use crate::models::{api_models::shared::ApiUuid, internal_models::internal_user::InternalUser};
+use crate::{
+    db::DB,
+    middleware::jwt::make_jwt,
+    models::{
+        api_models::shared::ApiUuid,
+        internal_models::{
+            internal_session::InternalSession, internal_user::InternalUser, shared::InternalUuid,
+        },
+    },
+};
 
 #[derive(Debug, Serialize, Deserialize, Apiv2Schema, Clone, PartialEq, Eq)]
 pub struct Jwt {
     pub jwt: String,
     pub uuid: ApiUuid<InternalUser>,
+    //single use, trade it at /refresh for a new jwt before this one expires
+    pub refresh_token: String,
+}
+
+impl Jwt {
+    pub fn for_session(
+        session: &InternalSession,
+        refresh_token: String,
+    ) -> Result<Jwt, actix_web::Error> {
+        let jwt = make_jwt(&session.user, &session.uuid).map_err(|e| {
+            log::error!("Failed to make jwt {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to make jwt")
+        })?;
+        Ok(Jwt {
+            jwt,
+            uuid: session.user.clone().into(),
+            refresh_token,
+        })
+    }
+}
+
+/// Logs a new device in.
+pub fn start_session(
+    db: &DB,
+    uuid: &InternalUuid<InternalUser>,
+) -> Result<Json<Jwt>, actix_web::Error> {
+    let (session, refresh_token) = InternalSession::start(db, uuid).map_err(|e| {
+        log::error!("Failed to start session {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to start session")
+    })?;
+    Jwt::for_session(&session, refresh_token).map(Json)
+}
+
+//shorter passwords are refused when changing or resetting one
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// Hashes a password the user is setting.
+pub fn hash_new_password(password: &str) -> Result<String, actix_web::Error> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LEN
+        )));
+    }
+    bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| {
+        log::error!("Failed to hash password {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to hash password")
+    })
 }
@@ -46,9 +46,8 @@ fn delete_image(
         //remove from pfp
         user.images.retain(|i| i != &img_uuid_internal);
 
-        img_uuid_internal.delete(db)?;
-
-        //update user
+        //update user, the refs go with every slot it's dropped from and the gc takes it
+        //once messages let go too
         user.save(db)?;
 
         Ok(true)
@@ -15,6 +15,7 @@ use crate::{
             internal_chat::InternalChat, internal_message::InternalMessage, shared::InternalUuid,
         },
     },
+    push::PushEvent,
     routes::shared::route_body_mut_db,
 };
 
@@ -42,7 +43,7 @@ pub fn delete_message(
             actix_web::error::ErrorInternalServerError("Failed to get chat")
         })?;
 
-        let mut chat = match chat {
+        let chat = match chat {
             Some(chat) => chat,
             None => return Err(actix_web::error::ErrorNotFound("Chat not found")),
         };
@@ -52,16 +53,34 @@ pub fn delete_message(
             return Err(actix_web::error::ErrorBadRequest("User not in chat"));
         }
 
-        //remove message from chat
+        //is the message in this chat, and is it theirs
         let internal_message_uuid: InternalUuid<InternalMessage> = message_uuid.into();
-        chat.messages.retain(|m| m != &internal_message_uuid);
+        let message = internal_message_uuid.load(db).map_err(|e| {
+            log::error!("Failed to get message {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to get message")
+        })?;
+        let message = match message {
+            Some(message) if message.chat == chat.uuid => message,
+            _ => return Err(actix_web::error::ErrorBadRequest("Message not in chat")),
+        };
+        if message.author != user.uuid {
+            return Err(actix_web::error::ErrorBadRequest("Not the author"));
+        }
 
-        //delete message
-        internal_message_uuid.delete(db).map_err(|e| {
+        //delete message, and its place in the chat
+        message.delete(db).map_err(|e| {
             log::error!("Failed to delete message {:?}", e);
             actix_web::error::ErrorInternalServerError("Failed to delete message")
         })?;
 
+        let event = PushEvent::DeletedMessage {
+            chat: chat.uuid.into(),
+            message: message.uuid.into(),
+        };
+        for member in &chat.users {
+            db.push.push(member, &event);
+        }
+
         Ok(true)
     })
 }
@@ -5,17 +5,21 @@ use paperclip::actix::{
     web::{self, Json},
 };
 
-use crate::{db::DB, routes::shared::route_body_mut_db};
+use crate::{
+    db::DB, models::internal_models::internal_user::UserDeleteReport,
+    routes::shared::route_body_mut_db,
+};
 
+/// Deletes the account and everything that goes with it, returns what was removed.
 #[api_v2_operation]
 #[post("/delete_user")]
 async fn delete_user(
     db: web::Data<DB>,
     req: HttpRequest,
     body: Json<bool>,
-) -> Result<Json<bool>, Error> {
+) -> Result<Json<UserDeleteReport>, Error> {
     route_body_mut_db(db, req, body, |db, user, _| {
-        user.delete(db).map_err(|e| {
+        let report = user.delete(db).map_err(|e| {
             log::error!("Failed to delete user {:?}", e);
             actix_web::error::ErrorInternalServerError("Failed to delete user")
         })?;
@@ -23,6 +27,6 @@ async fn delete_user(
             log::error!("Failed to flush db {:?}", e);
             actix_web::error::ErrorInternalServerError("Failed to flush db")
         })?;
-        Ok(true)
+        Ok(report)
     })
 }
@@ -49,7 +49,7 @@ pub fn get_chats(
 
         let api_chats: Vec<ApiChat> = chats
             .into_iter()
-            .map(|chat| ApiChat::from_internal(chat, &user))
+            .map(|chat| ApiChat::from_internal(chat, &user, db))
             .collect::<Result<Vec<_>, _>>()?;
         Ok(api_chats)
     })
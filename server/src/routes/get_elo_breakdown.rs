@@ -0,0 +1,49 @@
+use actix_web::Error;
+
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::Serialize;
+
+use crate::{
+    db::DB,
+    elo::{calc_elo, elo_to_label, next_tier, EloComponent},
+    routes::shared::route_body_mut_db,
+};
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ApiEloBreakdown {
+    pub elo: f32,
+    pub label: String,
+    pub components: Vec<EloComponent>,
+    pub next_label: Option<String>,
+    pub gap_to_next: Option<f32>,
+}
+
+#[api_v2_operation]
+#[post("/get_elo_breakdown")]
+pub fn get_elo_breakdown(
+    db: web::Data<DB>,
+    req: web::HttpRequest,
+    body: Json<bool>,
+) -> Result<Json<ApiEloBreakdown>, Error> {
+    route_body_mut_db(db, req, body, |db, user, _| {
+        let glicko = db.get_glicko(&user.uuid).map_err(|e| {
+            log::error!("Failed to get glicko rating {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to get glicko rating")
+        })?;
+
+        let breakdown = calc_elo(&glicko.glicko(), &user.actions, &user.props);
+        let next = next_tier(breakdown.elo);
+
+        Ok(ApiEloBreakdown {
+            elo: breakdown.elo,
+            label: elo_to_label(breakdown.elo),
+            components: breakdown.components,
+            next_label: next.as_ref().map(|(label, _)| label.clone()),
+            gap_to_next: next.map(|(_, gap)| gap),
+        })
+    })
+}
@@ -0,0 +1,81 @@
+use actix_web::{
+    http::header::{self, CacheControl, CacheDirective, ETag, EntityTag},
+    web, Error, HttpRequest, HttpResponse,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::DB,
+    middleware::jwt::{authenticate, bearer_token},
+    models::internal_models::{
+        internal_image::{ImageSize, InternalImage},
+        shared::InternalUuid,
+    },
+};
+
+//an image never changes under its uuid, so clients can hold on to it for good
+const CACHE_SECONDS: u32 = 60 * 60 * 24 * 365;
+
+#[derive(Debug, Deserialize)]
+pub struct ImageQuery {
+    size: Option<ImageSize>,
+    //for <img> tags, which can't set headers
+    token: Option<String>,
+}
+
+/// Sends one rendition of an image as plain jpeg bytes. GETs skip the jwt middleware,
+/// so the token is checked here.
+pub async fn get_image(
+    req: HttpRequest,
+    db: web::Data<DB>,
+    path: web::Path<String>,
+    query: web::Query<ImageQuery>,
+) -> Result<HttpResponse, Error> {
+    let token = bearer_token(&req)
+        .or(query.token.as_deref())
+        .ok_or(actix_web::error::ErrorUnauthorized("No token"))?;
+    let (user, _) = authenticate(Some(&db), token)?;
+
+    let uuid: InternalUuid<InternalImage> = path.into_inner().into();
+    let image = uuid.load(&db).map_err(|e| {
+        log::error!("Failed to get image {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to get image")
+    })?;
+    //not being allowed to see it looks the same as it not being there
+    let image = match image {
+        Some(image) if image.access.can_access(&user) => image,
+        _ => return Err(actix_web::error::ErrorNotFound("Image not found")),
+    };
+
+    let size = query.size.unwrap_or_default();
+    let etag = EntityTag::new_strong(format!("{}-{:?}", uuid.id, size));
+    let cache_control = CacheControl(vec![
+        CacheDirective::Private,
+        CacheDirective::MaxAge(CACHE_SECONDS),
+    ]);
+    let cached = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .any(|tag| tag.trim() == etag.to_string() || tag.trim() == "*")
+        });
+    if cached {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header(cache_control)
+            .finish());
+    }
+
+    let content = image.rendition(&db, size).map_err(|e| {
+        log::error!("Failed to get image {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to get image")
+    })?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/jpeg")
+        .insert_header(ETag(etag))
+        .insert_header(cache_control)
+        .body(content))
+}
@@ -3,25 +3,38 @@ use actix_web::Error;
 use paperclip::actix::{
     api_v2_operation, post,
     web::{self, Json},
+    Apiv2Schema,
 };
+use serde::Deserialize;
 
 use crate::{
     db::DB,
     models::{
         api_models::{api_image::ApiImage, shared::ApiUuid},
-        internal_models::{internal_image::InternalImage, shared::InternalUuid},
+        internal_models::{
+            internal_image::{ImageSize, InternalImage},
+            shared::InternalUuid,
+        },
     },
     routes::shared::route_body_mut_db,
 };
 
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct GetImagesQuery {
+    //the full rendition when left out
+    size: Option<ImageSize>,
+}
+
 #[api_v2_operation]
 #[post("/get_images")]
 pub fn get_images(
     db: web::Data<DB>,
     req: web::HttpRequest,
+    query: web::Query<GetImagesQuery>,
     body: Json<Vec<ApiUuid<InternalImage>>>,
 ) -> Result<Json<Vec<ApiImage>>, Error> {
     route_body_mut_db(db, req, body, |db, user, body| {
+        let size = query.size.unwrap_or_default();
         let images = body
             .into_iter()
             .map(|image_uuid| {
@@ -42,7 +55,7 @@ pub fn get_images(
 
         let api_images: Vec<ApiImage> = images
             .into_iter()
-            .map(|image| ApiImage::from_internal(image, Some(&user)))
+            .map(|image| ApiImage::from_internal(db, image, Some(&user), size))
             .collect::<Result<Vec<_>, _>>()?;
         Ok(api_images)
     })
@@ -18,6 +18,7 @@ use crate::{
             shared::{InternalUuid, Save},
         },
     },
+    push::PushEvent,
     routes::shared::route_body_mut_db,
 };
 
@@ -79,7 +80,16 @@ pub fn get_messages(
 
         let mut chat = chat;
         chat.unread[user_idx] = 0;
+        let chat_uuid = chat.uuid.clone();
         chat.save(db)?;
+        //the user's other devices can clear their badge
+        db.push.push(
+            user_uuid,
+            &PushEvent::Unread {
+                chat: chat_uuid.into(),
+                unread: 0,
+            },
+        );
 
         let mut user = user;
         //clear message from unread
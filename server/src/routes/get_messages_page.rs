@@ -0,0 +1,78 @@
+use actix_web::Error;
+
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::DB,
+    models::{
+        api_models::{api_message::ApiMessage, shared::ApiUuid},
+        internal_models::{
+            internal_chat::InternalChat, internal_message::InternalMessage, shared::InternalUuid,
+        },
+    },
+    routes::shared::route_body_mut_db,
+};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 200;
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct GetMessagesPageInput {
+    chat_uuid: ApiUuid<InternalChat>,
+    //the `next_cursor` of the previous page, leave it out for the newest messages
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ApiMessagePage {
+    //newest first
+    messages: Vec<ApiMessage>,
+    //None once there is nothing older
+    next_cursor: Option<String>,
+}
+
+/// A chat's history a page at a time, newest first.
+#[api_v2_operation]
+#[post("/get_messages_page")]
+pub fn get_messages_page(
+    db: web::Data<DB>,
+    req: web::HttpRequest,
+    body: Json<GetMessagesPageInput>,
+) -> Result<Json<ApiMessagePage>, Error> {
+    route_body_mut_db(db, req, body, |db, user, body| {
+        //is this user in this chat?
+        let chat_uuid: InternalUuid<InternalChat> = body.chat_uuid.into();
+        let chat = chat_uuid.load(db).map_err(|e| {
+            log::error!("Failed to get chat {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to get chat")
+        })?;
+        let chat = match chat {
+            Some(chat) => chat,
+            None => return Err(actix_web::error::ErrorNotFound("Chat not found")),
+        };
+        if !chat.users.contains(&user.uuid) {
+            return Err(actix_web::error::ErrorBadRequest("User not in chat"));
+        }
+
+        let limit = body
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let page =
+            InternalMessage::page(db, &chat.uuid, body.cursor.as_deref(), limit).map_err(|e| {
+                log::error!("Failed to get messages page {:?}", e);
+                actix_web::error::ErrorInternalServerError("Failed to get messages page")
+            })?;
+
+        Ok(ApiMessagePage {
+            messages: page.messages.into_iter().map(ApiMessage::from).collect(),
+            next_cursor: page.next_cursor,
+        })
+    })
+}
@@ -10,7 +10,7 @@ use crate::{
     db::DB,
     models::{
         api_models::{api_user::ApiUser, shared::ApiUuid},
-        internal_models::internal_user::InternalUser,
+        internal_models::{internal_prefs::rank_by_soft_prefs, internal_user::InternalUser},
     },
     routes::shared::route_body_mut_db,
 };
@@ -29,6 +29,9 @@ pub fn get_next_users(
         let users = users
             .into_iter()
             .filter(|u| !seen.contains(&u.uuid) && !body.contains(&u.uuid.clone().into()))
+            .collect();
+        let users = rank_by_soft_prefs(&user, users)
+            .into_iter()
             .take(USERS_PER_SET)
             .map(|internal_user| ApiUser::from_internal(internal_user, Some(&user)))
             .collect::<Result<Vec<_>, _>>()?;
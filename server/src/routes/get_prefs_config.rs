@@ -8,7 +8,7 @@ use paperclip::actix::{
 use crate::{
     db::DB,
     models::internal_models::{
-        internal_prefs::PreferenceConfigPublic, internal_prefs_config::PREFS_CONFIG,
+        internal_prefs::PreferenceConfigPublic, internal_prefs_config::prefs_config,
     },
     routes::shared::route_body_mut_db,
 };
@@ -21,10 +21,6 @@ pub fn get_prefs_config(
     body: Json<bool>,
 ) -> Result<Json<Vec<PreferenceConfigPublic>>, Error> {
     route_body_mut_db(db, req, body, |_, _, _| {
-        Ok(PREFS_CONFIG
-            .iter()
-            .cloned()
-            .map(|p| p.get_public())
-            .collect())
+        Ok(prefs_config().iter().map(|p| p.get_public()).collect())
     })
 }
@@ -22,10 +22,15 @@ pub fn get_users(
     body: Json<Vec<ApiUuid<InternalUser>>>,
 ) -> Result<Json<Vec<ApiUser>>, Error> {
     route_body_mut_db(db, req, body, |db, user, body| {
+        let blocked = db.blocked_by(&user.uuid).map_err(|e| {
+            log::error!("Failed to get blocked users {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to get blocked users")
+        })?;
         let users = body
             .into_iter()
-            .map(|user_uuid| {
-                let internal_uuid: InternalUuid<_> = user_uuid.into();
+            .map(|user_uuid| -> InternalUuid<InternalUser> { user_uuid.into() })
+            .filter(|user_uuid| !blocked.contains(user_uuid))
+            .map(|internal_uuid| {
                 internal_uuid
                     .load(db)
                     .map_err(|e| {
@@ -1,5 +1,7 @@
+use std::{sync::OnceLock, time::Instant};
+
 use actix_web::Error;
-use bcrypt::verify;
+use bcrypt::{hash, verify};
 use paperclip::actix::{
     api_v2_operation, post,
     web::{self, Json},
@@ -7,12 +9,25 @@ use paperclip::actix::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{db::DB, middleware::jwt::make_jwt, routes::common::Jwt};
+use crate::{
+    db::DB,
+    middleware::rate_limit::{LoginLockouts, TooManyRequests},
+    routes::common::{start_session, Jwt},
+};
+
+//the same answer for a wrong name and a wrong password, so names can't be probed
+const LOGIN_FAILED: &str = "Incorrect username or password";
 
 fn verify_password(password: &str, hash: &str) -> Result<bool, bcrypt::BcryptError> {
     verify(password, hash)
 }
 
+//checked against for unknown names so they take as long as a wrong password
+fn dummy_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| hash("not a password", bcrypt::DEFAULT_COST).unwrap())
+}
+
 #[derive(Debug, Serialize, Deserialize, Apiv2Schema, Clone, PartialEq, Eq)]
 pub struct LoginRequest {
     pub username: String,
@@ -21,34 +36,45 @@ pub struct LoginRequest {
 
 #[api_v2_operation]
 #[post("/login")]
-async fn login(db: web::Data<DB>, body: Json<LoginRequest>) -> Result<Json<Jwt>, Error> {
+async fn login(
+    db: web::Data<DB>,
+    lockouts: web::Data<LoginLockouts>,
+    body: Json<LoginRequest>,
+) -> Result<Json<Jwt>, Error> {
     let login_req = body.into_inner();
+    let now = Instant::now();
+    lockouts
+        .check(&login_req.username, now)
+        .map_err(|retry_after| TooManyRequests { retry_after })?;
+
     let user = db.get_user_by_username(&login_req.username).map_err(|e| {
         log::error!("Failed to get user by username {:?}", e);
         actix_web::error::ErrorInternalServerError("Failed to get user by username")
     })?;
-    let user = match user {
-        Some(user) => user,
-        None => return Err(actix_web::error::ErrorBadRequest("User not found")),
+    let hashed_password = match &user {
+        Some(user) => user.hashed_password.as_str(),
+        None => dummy_hash(),
     };
-    let hashed_password = user.hashed_password.clone();
-    let password_matches = verify_password(&login_req.password, &hashed_password).map_err(|e| {
+    let password_matches = verify_password(&login_req.password, hashed_password).map_err(|e| {
         log::error!("Failed to verify password {:?}", e);
         actix_web::error::ErrorInternalServerError("Failed to verify password")
     })?;
-    if !password_matches {
-        return Err(actix_web::error::ErrorBadRequest("Incorrect password"));
+    let user = match user {
+        Some(user) if password_matches => user,
+        _ => {
+            log::info!("Failed login for {}", login_req.username);
+            lockouts.failed(&login_req.username, now);
+            return Err(actix_web::error::ErrorBadRequest(LOGIN_FAILED));
+        }
+    };
+    lockouts.succeeded(&login_req.username);
+    let banned = db.is_banned(&user.uuid).map_err(|e| {
+        log::error!("Failed to check ban {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to check ban")
+    })?;
+    if banned {
+        return Err(actix_web::error::ErrorForbidden("Account banned"));
     }
 
-    make_jwt(&user.uuid)
-        .map(|jwt| {
-            Json(Jwt {
-                jwt,
-                uuid: user.uuid.into(),
-            })
-        })
-        .map_err(|e| {
-            log::error!("Failed to make jwt {:?}", e);
-            actix_web::error::ErrorInternalServerError("Failed to make jwt")
-        })
+    start_session(&db, &user.uuid)
 }
@@ -0,0 +1,56 @@
+use actix_web::{Error, HttpMessage, HttpRequest};
+
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+};
+
+use crate::{
+    db::DB,
+    models::internal_models::{internal_session::InternalSession, shared::InternalUuid},
+    routes::shared::route_body_mut_db,
+};
+
+/// Ends the session the request was made with.
+#[api_v2_operation]
+#[post("/logout")]
+async fn logout(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<bool>,
+) -> Result<Json<bool>, Error> {
+    let session = req
+        .extensions()
+        .get::<InternalUuid<InternalSession>>()
+        .cloned()
+        .ok_or(actix_web::error::ErrorBadRequest("Session not in request"))?;
+    route_body_mut_db(db, req, body, |db, _, _| {
+        let session = session.load(db).map_err(|e| {
+            log::error!("Failed to load session {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to load session")
+        })?;
+        match session {
+            Some(session) => session.end(db).map_err(|e| {
+                log::error!("Failed to end session {:?}", e);
+                actix_web::error::ErrorInternalServerError("Failed to end session")
+            }),
+            None => Ok(false),
+        }
+    })
+}
+
+/// Ends every session of the user, logging out all of their devices.
+#[api_v2_operation]
+#[post("/logout_all")]
+async fn logout_all(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<bool>,
+) -> Result<Json<usize>, Error> {
+    route_body_mut_db(db, req, body, |db, user, _| {
+        db.end_all_sessions(&user.uuid).map_err(|e| {
+            log::error!("Failed to end sessions {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to end sessions")
+        })
+    })
+}
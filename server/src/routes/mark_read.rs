@@ -0,0 +1,55 @@
+use actix_web::{Error, HttpRequest};
+
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::DB,
+    models::{
+        api_models::shared::ApiUuid,
+        internal_models::{
+            internal_chat::{InternalChat, ReadError},
+            internal_message::InternalMessage,
+            shared::InternalUuid,
+        },
+    },
+    routes::shared::route_body_mut_db,
+};
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct MarkReadInput {
+    chat_uuid: ApiUuid<InternalChat>,
+    //the newest message the user has seen, everything before it counts as read too
+    message_uuid: ApiUuid<InternalMessage>,
+}
+
+/// Records that the user has read the chat up to a message, the other members
+/// are told over their sockets. Returns the user's unread count in the chat.
+#[api_v2_operation]
+#[post("/mark_read")]
+pub fn mark_read(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<MarkReadInput>,
+) -> Result<Json<u32>, Error> {
+    route_body_mut_db(db, req, body, |db, user, body| {
+        let chat_uuid: InternalUuid<InternalChat> = body.chat_uuid.into();
+        let message_uuid: InternalUuid<InternalMessage> = body.message_uuid.into();
+        let result =
+            InternalChat::mark_read(db, &chat_uuid, &user.uuid, &message_uuid).map_err(|e| {
+                log::error!("Failed to mark chat read {:?}", e);
+                actix_web::error::ErrorInternalServerError("Failed to mark chat read")
+            })?;
+        match result {
+            Ok(unread) => Ok(unread),
+            Err(ReadError::ChatNotFound) => Err(actix_web::error::ErrorNotFound(
+                ReadError::ChatNotFound.message(),
+            )),
+            Err(e) => Err(actix_web::error::ErrorBadRequest(e.message())),
+        }
+    })
+}
@@ -1,3 +1,6 @@
+pub mod admin;
+pub mod block;
+pub mod change_password;
 pub mod check_username;
 pub mod common;
 pub mod delete_image;
@@ -5,21 +8,33 @@ pub mod delete_message;
 pub mod delete_user;
 pub mod fetch_notifications;
 pub mod get_chats;
+pub mod get_elo_breakdown;
+pub mod get_image;
 pub mod get_images;
 pub mod get_internal_me;
 pub mod get_me;
 pub mod get_message;
 pub mod get_messages;
+pub mod get_messages_page;
 pub mod get_next_users;
 pub mod get_prefs_config;
 pub mod get_users;
 pub mod get_users_i_perfer_count_dry_run;
 pub mod get_users_mutual_perfer_count_dry_run;
 pub mod login;
+pub mod logout;
+pub mod mark_read;
+pub mod moderation;
 pub mod put_image;
 pub mod put_message;
 pub mod put_user;
 pub mod rate;
+pub mod refresh;
 pub mod report;
+pub mod reset_password;
+pub mod rotate_signing_key;
 pub mod shared;
 pub mod signup;
+pub mod typing;
+pub mod unmatch;
+pub mod ws;
@@ -0,0 +1,158 @@
+use actix_web::{Error, HttpRequest};
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::DB,
+    models::{
+        api_models::{api_report::ApiReport, shared::ApiUuid},
+        internal_models::{
+            internal_report::{InternalReport, ModAction, ReportError, ReportStatus},
+            internal_user::{InternalUser, Role},
+            shared::InternalUuid,
+        },
+    },
+    routes::shared::{require_role, route_body_mut_db, write_error},
+};
+
+const DEFAULT_REPORTS: usize = 50;
+const MAX_REPORTS: usize = 200;
+
+fn report_error(e: ReportError) -> Error {
+    match e {
+        ReportError::NotFound => actix_web::error::ErrorNotFound(e.message()),
+        _ => actix_web::error::ErrorBadRequest(e.message()),
+    }
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct ListReportsInput {
+    status: ReportStatus,
+    limit: Option<usize>,
+}
+
+/// Moderators only. Reports with the given status, oldest first, without their evidence.
+#[api_v2_operation]
+#[post("/list_reports")]
+pub fn list_reports(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<ListReportsInput>,
+) -> Result<Json<Vec<ApiReport>>, Error> {
+    route_body_mut_db(db, req, body, |db, user, body| {
+        require_role(&user, Role::Moderator)?;
+        let limit = body.limit.unwrap_or(DEFAULT_REPORTS).min(MAX_REPORTS);
+        let reports = InternalReport::list(db, body.status, limit)
+            .map_err(|e| write_error(e, "Failed to list reports"))?;
+        Ok(reports
+            .into_iter()
+            .map(|report| ApiReport::from_internal(report, false))
+            .collect())
+    })
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct GetReportInput {
+    report_uuid: ApiUuid<InternalReport>,
+}
+
+/// Moderators only. The whole report, evidence included.
+#[api_v2_operation]
+#[post("/get_report")]
+pub fn get_report(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<GetReportInput>,
+) -> Result<Json<ApiReport>, Error> {
+    route_body_mut_db(db, req, body, |db, user, body| {
+        require_role(&user, Role::Moderator)?;
+        let report_uuid: InternalUuid<InternalReport> = body.report_uuid.into();
+        let report = report_uuid
+            .load(db)
+            .map_err(|e| write_error(e, "Failed to get report"))?
+            .ok_or_else(|| report_error(ReportError::NotFound))?;
+        Ok(ApiReport::from_internal(report, true))
+    })
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct AssignReportInput {
+    report_uuid: ApiUuid<InternalReport>,
+    //the caller when left out
+    assignee: Option<ApiUuid<InternalUser>>,
+}
+
+/// Moderators only. Hands an unresolved report to a moderator.
+#[api_v2_operation]
+#[post("/assign_report")]
+pub fn assign_report(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<AssignReportInput>,
+) -> Result<Json<ApiReport>, Error> {
+    route_body_mut_db(db, req, body, |db, user, body| {
+        require_role(&user, Role::Moderator)?;
+        let assignee = body.assignee.map(Into::into).unwrap_or(user.uuid);
+        let report = InternalReport::assign(db, &body.report_uuid.into(), &assignee)
+            .map_err(|e| write_error(e, "Failed to assign report"))?
+            .map_err(report_error)?;
+        Ok(ApiReport::from_internal(report, false))
+    })
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct AddReportNoteInput {
+    report_uuid: ApiUuid<InternalReport>,
+    text: String,
+}
+
+/// Moderators only. Leaves a note on an unresolved report for whoever picks it up next.
+#[api_v2_operation]
+#[post("/add_report_note")]
+pub fn add_report_note(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<AddReportNoteInput>,
+) -> Result<Json<ApiReport>, Error> {
+    route_body_mut_db(db, req, body, |db, user, body| {
+        require_role(&user, Role::Moderator)?;
+        let report = InternalReport::add_note(db, &body.report_uuid.into(), &user.uuid, &body.text)
+            .map_err(|e| write_error(e, "Failed to add note"))?
+            .map_err(report_error)?;
+        Ok(ApiReport::from_internal(report, false))
+    })
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct ResolveReportInput {
+    report_uuid: ApiUuid<InternalReport>,
+    action: ModAction,
+    note: Option<String>,
+}
+
+/// Moderators only. Closes the report, warning, unpublishing or banning its target user.
+#[api_v2_operation]
+#[post("/resolve_report")]
+pub fn resolve_report(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<ResolveReportInput>,
+) -> Result<Json<ApiReport>, Error> {
+    route_body_mut_db(db, req, body, |db, user, body| {
+        require_role(&user, Role::Moderator)?;
+        let report_uuid: InternalUuid<InternalReport> = body.report_uuid.into();
+        if let Some(note) = &body.note {
+            InternalReport::add_note(db, &report_uuid, &user.uuid, note)
+                .map_err(|e| write_error(e, "Failed to add note"))?
+                .map_err(report_error)?;
+        }
+        let report = InternalReport::resolve(db, &report_uuid, &user.uuid, body.action)
+            .map_err(|e| write_error(e, "Failed to resolve report"))?
+            .map_err(report_error)?;
+        Ok(ApiReport::from_internal(report, false))
+    })
+}
@@ -2,7 +2,7 @@ use actix_web::{Error, HttpRequest};
 
 use paperclip::actix::{
     api_v2_operation, post,
-    web::{self, Json},
+    web::{self, Bytes, Json},
     Apiv2Schema,
 };
 use serde::Deserialize;
@@ -13,13 +13,15 @@ use crate::{
         api_models::{api_image::ApiImageWritable, shared::ApiUuid},
         internal_models::{
             internal_image::{Access, InternalImage},
+            internal_report::InternalReport,
             internal_user::InternalUser,
+            shared::InternalUuid,
         },
     },
-    routes::shared::route_body_mut_db,
+    routes::shared::{route_body_mut_db, route_file_mut_db},
 };
 
-use crate::models::internal_models::shared::Save;
+use crate::transaction::SaveTxn;
 
 #[derive(Debug, Deserialize, Apiv2Schema)]
 struct PutImageInput {
@@ -27,6 +29,51 @@ struct PutImageInput {
     access: Option<Vec<ApiUuid<InternalUser>>>,
 }
 
+//no list, or an empty one, is everyone
+fn access_from(access: Option<Vec<InternalUuid<InternalUser>>>) -> Access {
+    match access {
+        Some(access) if !access.is_empty() => Access::UserList(access),
+        _ => Access::Everyone,
+    }
+}
+
+//processes the upload and hands it to the user
+fn store_image(
+    db: &DB,
+    mut user: InternalUser,
+    bytes: Vec<u8>,
+    access: Access,
+) -> Result<ApiUuid<InternalImage>, Error> {
+    let new_image = ApiImageWritable::new(bytes);
+
+    let new_image_internal = new_image.to_internal(db, access).map_err(|e| {
+        log::info!("Refused image upload: {}", e);
+        actix_web::error::ErrorBadRequest(e.to_string())
+    })?;
+
+    //the upload goes through either way, it's up to a moderator
+    match InternalReport::flag_banned_photo(db, &user, &new_image_internal) {
+        Ok(Some(report)) => log::warn!("Flagged upload by {}: {}", user.uuid.id, report.content),
+        Ok(None) => (),
+        Err(e) => log::error!("Failed to check upload against banned users {:?}", e),
+    }
+
+    //the uploader's library holds the first reference, counted as the user is saved
+    let uuid = new_image_internal.uuid.clone();
+    user.owned_images.push(uuid.clone());
+
+    db.transaction(|txn| {
+        new_image_internal.save_txn(txn)?;
+        user.save_txn(txn)
+    })
+    .map_err(|e| {
+        log::error!("Failed to save user {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to save user")
+    })?;
+
+    Ok(uuid.into())
+}
+
 #[api_v2_operation]
 #[post("/put_image")]
 fn put_image(
@@ -34,42 +81,45 @@ fn put_image(
     req: HttpRequest,
     body: Json<PutImageInput>,
 ) -> Result<Json<ApiUuid<InternalImage>>, Error> {
-    route_body_mut_db(db, req, body, |db, mut user, img_content| {
+    route_body_mut_db(db, req, body, |db, user, img_content| {
         #[allow(deprecated)]
         let img_content_bytes = base64::decode(img_content.content).map_err(|e| {
             log::error!("Failed to decode image {:?}", e);
             actix_web::error::ErrorBadRequest("Failed to decode image")
         })?;
-        let new_image = ApiImageWritable::new(img_content_bytes);
-
-        let access = match img_content.access {
-            Some(access) => {
-                if access.is_empty() {
-                    Access::Everyone
-                } else {
-                    Access::UserList(access.into_iter().map(|u| u.into()).collect::<Vec<_>>())
-                }
-            }
-            None => Access::Everyone,
-        };
-
-        let new_image_internal = new_image.to_internal(access).map_err(|e| {
-            log::error!("Failed to convert image {:?}", e);
-            actix_web::error::ErrorInternalServerError("Failed to convert image")
-        })?;
-
-        let uuid = new_image_internal.save(db).map_err(|e| {
-            log::error!("Failed to save user {:?}", e);
-            actix_web::error::ErrorInternalServerError("Failed to save user")
-        })?;
-
-        user.owned_images.push(uuid.clone());
+        let access = access_from(
+            img_content
+                .access
+                .map(|access| access.into_iter().map(Into::into).collect()),
+        );
+        store_image(db, user, img_content_bytes, access)
+    })
+}
 
-        user.save(db).map_err(|e| {
-            log::error!("Failed to save user {:?}", e);
-            actix_web::error::ErrorInternalServerError("Failed to save user")
-        })?;
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct UploadImageQuery {
+    //comma separated user uuids, a query string can't hold a list
+    access: Option<String>,
+}
 
-        Ok(uuid.into())
+/// Same as /put_image, but the body is the image file itself rather than base64 in json.
+#[api_v2_operation]
+#[post("/upload_image")]
+fn upload_image(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    query: web::Query<UploadImageQuery>,
+    body: Bytes,
+) -> Result<Json<ApiUuid<InternalImage>>, Error> {
+    let access = access_from(query.access.as_ref().map(|access| {
+        access
+            .split(',')
+            .map(str::trim)
+            .filter(|uuid| !uuid.is_empty())
+            .map(|uuid| uuid.to_string().into())
+            .collect()
+    }));
+    route_file_mut_db(db, req, body, |db, user, bytes| {
+        store_image(db, user, bytes, access)
     })
 }
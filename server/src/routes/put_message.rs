@@ -12,12 +12,10 @@ use crate::{
     models::{
         api_models::{api_message::ApiMessageWritable, shared::ApiUuid},
         internal_models::{
-            internal_chat::InternalChat,
-            internal_user::TimestampedAction,
-            shared::{InternalUuid, Save},
+            internal_chat::InternalChat, internal_user::Action, shared::InternalUuid,
         },
     },
-    routes::shared::route_body_mut_db,
+    routes::shared::{route_body_mut_db, write_error},
 };
 
 #[derive(Debug, Deserialize, Apiv2Schema)]
@@ -56,17 +54,9 @@ pub fn put_message(
 
         let internal_message = message.into_internal(&user.uuid, &chat, db)?;
 
-        internal_message.save(&mut chat, db).map_err(|e| {
-            log::error!("Failed to save message {:?}", e);
-            actix_web::error::ErrorInternalServerError("Failed to save message")
-        })?;
-
-        let mut user = user;
-        user.actions.push(TimestampedAction {
-            timestamp: chrono::Utc::now().timestamp(),
-            action: crate::models::internal_models::internal_user::Action::SendMessage,
-        });
-        user.save(db)?;
+        internal_message
+            .save_with_action(&mut chat, Some(Action::SendMessage), db)
+            .map_err(|e| write_error(e, "Failed to save message"))?;
 
         Ok(true)
     })
@@ -43,11 +43,16 @@ async fn put_user(
 
         let publishable = publish_message.is_empty();
 
+        let glicko = db.get_glicko(&new_user_internal.uuid).map_err(|e| {
+            log::error!("Failed to get glicko rating {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to get glicko rating")
+        })?;
         let elo = calc_elo(
-            &new_user_internal.ratings,
+            &glicko.glicko(),
             &new_user_internal.actions,
             &new_user_internal.props,
-        );
+        )
+        .elo;
         log::info!("New elo: {}", elo);
         new_user_internal.elo = elo;
 
@@ -9,6 +9,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     db::DB,
+    elo::calc_elo,
+    glicko::rate_pair,
     models::{
         api_models::{api_rating::ApiRating, shared::ApiUuid},
         internal_models::{
@@ -16,10 +18,12 @@ use crate::{
             internal_user::{
                 Action, InternalRating, InternalUser, Notification, TimestampedAction,
             },
-            shared::{InternalUuid, Save},
+            shared::InternalUuid,
         },
     },
-    routes::shared::route_body_mut_db,
+    push::PushEvent,
+    routes::shared::{route_body_mut_db, write_error},
+    transaction::{SaveTxn, Txn},
 };
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Apiv2Schema)]
@@ -28,6 +32,26 @@ pub struct RatingWithTarget {
     pub rating: ApiRating,
 }
 
+//both users hear about the match, each told who it's with
+fn push_match_after_commit(chat: &InternalChat, txn: &Txn) {
+    let chat_uuid = chat.uuid.clone();
+    let users = chat.users.clone();
+    txn.after_commit(move |db| {
+        for (user, other) in users.iter().zip(users.iter().rev()) {
+            let event = PushEvent::Match {
+                user: other.clone().into(),
+                chat: chat_uuid.clone().into(),
+            };
+            db.push.push(user, &event);
+        }
+        Ok(())
+    });
+}
+
+fn already_rated() -> Error {
+    actix_web::error::ErrorBadRequest("Already rated this user")
+}
+
 #[api_v2_operation]
 #[post("/rate")]
 pub fn rate(
@@ -36,74 +60,112 @@ pub fn rate(
     body: web::Json<RatingWithTarget>,
 ) -> Result<Json<bool>, Error> {
     route_body_mut_db(db, req, body, |db, user, body| {
-        let mut user = user;
         let rating = body.rating;
         let target = body.target;
         let target_internal_user_uuid: InternalUuid<InternalUser> = target.into();
-        let target = target_internal_user_uuid.load(db).map_err(|e| {
-            log::error!("Failed to get target user by uuid {:?}", e);
-            actix_web::error::ErrorInternalServerError("Failed to get target user by uuid")
-        })?;
+        //a replayed rate would move both ratings again
+        if target_internal_user_uuid == user.uuid {
+            return Err(actix_web::error::ErrorBadRequest("Can't rate yourself"));
+        }
+        if user.seen.contains(&target_internal_user_uuid) {
+            return Err(already_rated());
+        }
+        //a blocked user still rates as usual, the target just never hears about it
+        let blocked = db
+            .has_blocked(&target_internal_user_uuid, &user.uuid)
+            .map_err(|e| write_error(e, "Failed to save rating"))?;
 
-        let mut target = match target {
-            Some(target) => target,
-            None => return Err(actix_web::error::ErrorNotFound("Target user not found")),
-        };
+        let mutual = db
+            .retry_on_conflict(|| {
+                //both users are reread on every attempt, the previous one lost a race
+                let mut user = db
+                    .load_versioned(&user.uuid)?
+                    .ok_or("User not found while rating")?;
+                let mut target = match db.load_versioned(&target_internal_user_uuid)? {
+                    Some(target) => target,
+                    None => {
+                        return Ok(Err(actix_web::error::ErrorNotFound(
+                            "Target user not found",
+                        )))
+                    }
+                };
+                //the same rate sent twice at once, the other one won
+                if user.value.seen.contains(&target.value.uuid) {
+                    return Ok(Err(already_rated()));
+                }
 
-        let mut mutual = false;
+                let mut mutual = false;
+                let mut chat = None;
 
-        if rating == ApiRating::Like {
-            if user.is_liked_by(&target.uuid) {
-                mutual = true;
-                let chat = InternalChat::new(vec![user.uuid.clone(), target.uuid.clone()]);
-                target.add_chat(&chat);
-                user.add_chat(&chat);
-                chat.save(db)?;
-            }
-        }
+                if rating == ApiRating::Like
+                    && !blocked
+                    && user.value.is_liked_by(&target.value.uuid)
+                {
+                    mutual = true;
+                    let new_chat =
+                        InternalChat::new(vec![user.value.uuid.clone(), target.value.uuid.clone()]);
+                    target.value.add_chat(&new_chat);
+                    user.value.add_chat(&new_chat);
+                    chat = Some(new_chat);
+                }
 
-        let rated = match rating {
-            ApiRating::Like => InternalRating::LikedBy(user.uuid.clone()),
-            ApiRating::Pass => InternalRating::PassedBy(user.uuid.clone()),
-        };
+                let rated = match rating {
+                    ApiRating::Like => InternalRating::LikedBy(user.value.uuid.clone()),
+                    ApiRating::Pass => InternalRating::PassedBy(user.value.uuid.clone()),
+                };
 
-        let new_user = InternalUser {
-            seen: user
-                .seen
-                .into_iter()
-                .chain(std::iter::once(target.uuid.clone()))
-                .collect(),
-            actions: user
-                .actions
-                .into_iter()
-                .chain(std::iter::once(TimestampedAction {
+                user.value.seen.push(target.value.uuid.clone());
+                user.value.actions.push(TimestampedAction {
                     action: Action::Rate,
                     timestamp: chrono::Utc::now().timestamp(),
-                }))
-                .collect(),
-            ..user
-        };
+                });
+                if !blocked {
+                    target.value.ratings.push(rated);
+                }
+                if mutual {
+                    target
+                        .value
+                        .notifications
+                        .push(Notification::Match(user.value.uuid.clone()));
+                }
 
-        let new_target = InternalUser {
-            ratings: target
-                .ratings
-                .into_iter()
-                .chain(std::iter::once(rated))
-                .collect(),
-            notifications: if mutual {
-                target
-                    .notifications
-                    .into_iter()
-                    .chain(std::iter::once(Notification::Match(new_user.uuid.clone())))
-                    .collect()
-            } else {
-                target.notifications
-            },
-            ..target
-        };
+                //every rate is a game between the two, so both ratings move
+                let mut user_glicko = db.get_glicko_versioned(&user.value.uuid)?;
+                let mut target_glicko = db.get_glicko_versioned(&target.value.uuid)?;
+                let (new_user_glicko, new_target_glicko) = rate_pair(
+                    &user_glicko.value.glicko(),
+                    &target_glicko.value.glicko(),
+                    rating == ApiRating::Like,
+                );
+                user_glicko.value.record_game(&new_user_glicko);
+                target_glicko.value.record_game(&new_target_glicko);
+                user.value.elo =
+                    calc_elo(&new_user_glicko, &user.value.actions, &user.value.props).elo;
+                target.value.elo = calc_elo(
+                    &new_target_glicko,
+                    &target.value.actions,
+                    &target.value.props,
+                )
+                .elo;
+
+                db.transaction(|txn| {
+                    txn.expect_version(&user.value.uuid, user.version)?;
+                    txn.expect_version(&target.value.uuid, target.version)?;
+                    txn.expect_version(&user_glicko.value.uuid, user_glicko.version)?;
+                    txn.expect_version(&target_glicko.value.uuid, target_glicko.version)?;
+                    user_glicko.value.save_txn(txn)?;
+                    target_glicko.value.save_txn(txn)?;
+                    if let Some(chat) = &chat {
+                        chat.save_txn(txn)?;
+                        push_match_after_commit(chat, txn);
+                    }
+                    user.value.save_txn(txn)?;
+                    target.value.save_txn(txn)
+                })?;
 
-        new_user.save(db)?;
-        new_target.save(db)?;
+                Ok(Ok(mutual))
+            })
+            .map_err(|e| write_error(e, "Failed to save rating"))??;
 
         Ok(mutual)
     })
@@ -0,0 +1,32 @@
+use actix_web::Error;
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::DB, models::internal_models::internal_session::InternalSession, routes::common::Jwt,
+};
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[api_v2_operation]
+#[post("/refresh")]
+async fn refresh(db: web::Data<DB>, body: Json<RefreshRequest>) -> Result<Json<Jwt>, Error> {
+    let refreshed = InternalSession::refresh(&db, &body.refresh_token).map_err(|e| {
+        log::error!("Failed to refresh session {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to refresh session")
+    })?;
+    match refreshed {
+        Ok((session, refresh_token)) => Jwt::for_session(&session, refresh_token).map(Json),
+        Err(e) => {
+            log::info!("Refused refresh token: {}", e.message());
+            Err(actix_web::error::ErrorUnauthorized(e.message()))
+        }
+    }
+}
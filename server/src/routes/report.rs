@@ -1,21 +1,23 @@
-use std::fs;
-
 use paperclip::actix::{
     api_v2_operation, post,
     web::{self, Json},
     Apiv2Schema,
 };
 use serde::Deserialize;
-use uuid::Uuid;
 
 use crate::{
     db::DB,
+    middleware::jwt::{authenticate, bearer_token},
     models::{
         api_models::shared::ApiUuid,
         internal_models::{
-            internal_chat::InternalChat, internal_user::InternalUser, shared::InternalUuid,
+            internal_chat::InternalChat,
+            internal_report::{Evidence, InternalReport, ReportKind, ReportStatus},
+            internal_user::InternalUser,
+            shared::InternalUuid,
         },
     },
+    routes::shared::write_error,
 };
 
 #[derive(Debug, Deserialize, Apiv2Schema)]
@@ -32,7 +34,7 @@ struct ReportInput {
 #[post("/report")]
 fn report(
     db: web::Data<DB>,
-    _: web::HttpRequest,
+    req: web::HttpRequest,
     body: Json<ReportInput>,
 ) -> Result<Json<bool>, actix_web::Error> {
     //decode image64
@@ -60,123 +62,68 @@ fn report(
             log::error!("Failed to encode image {:?}", e);
             std::io::Error::new(std::io::ErrorKind::Other, "Failed to encode image")
         })?;
-    //save image
-    let report_uuid = Uuid::new_v4();
-    //save image and text content to folder report/report_uuid/*
-
-    let report_base_folder = if body.is_violation {
-        "report/violation"
-    } else {
-        "report/bug"
-    };
+    //the report works logged out too, it's kept anonymous then
+    let reporter = bearer_token(&req)
+        .and_then(|token| authenticate(Some(&db), token).ok())
+        .map(|(uuid, _)| uuid);
 
-    //if folder does not exist, create it
-    fs::create_dir_all(format!("{}/{}", report_base_folder, report_uuid))?; //create folder
-    fs::write(
-        format!("{}/{}/report.txt", report_base_folder, report_uuid),
-        body.content.clone(),
-    )?; //write text content
-    fs::write(
-        format!("{}/{}/report.jpg", report_base_folder, report_uuid),
-        buf,
-    )?; //write image content
-
-    //write platform type to report folder
-    fs::write(
-        format!("{}/{}/platform.txt", report_base_folder, report_uuid),
-        body.platform.clone().unwrap_or("unknown".to_string()),
-    )?;
-
-    if let Some(user_uuid) = &body.user_uuid {
-        let internal_uuid: InternalUuid<InternalUser> = user_uuid.clone().into();
-        let user = internal_uuid.load(&db)?;
-        let user = match user {
-            Some(user) => user,
-            None => {
-                return Err(actix_web::error::ErrorNotFound(
-                    "User target of report not found",
-                ));
+    let target_user = match &body.user_uuid {
+        Some(user_uuid) => {
+            let internal_uuid: InternalUuid<InternalUser> = user_uuid.clone().into();
+            match internal_uuid.load(&db)? {
+                Some(user) => Some(user),
+                None => {
+                    return Err(actix_web::error::ErrorNotFound(
+                        "User target of report not found",
+                    ));
+                }
             }
-        };
-        let images = user
-            .images
-            .clone()
-            .into_iter()
-            .map(|i| i.load(&db))
-            .collect::<Result<Option<Vec<_>>, _>>()?;
-        let images = match images {
-            Some(images) => images,
-            None => return Err(actix_web::error::ErrorNotFound("Images not found")),
-        };
-        //write each to report folder
-        for (i, image) in images.iter().enumerate() {
-            fs::write(
-                format!(
-                    "{}/{}/user_image_{}.jpg",
-                    report_base_folder, report_uuid, i
-                ),
-                image.content.clone(),
-            )?;
         }
+        None => None,
     };
-
-    //is there a chat?
-    if let Some(chat_uuid) = &body.chat {
-        let internal_uuid: InternalUuid<InternalChat> = chat_uuid.clone().into();
-        let chat = internal_uuid.load(&db)?;
-        let chat = match chat {
-            Some(chat) => chat,
-            None => {
-                return Err(actix_web::error::ErrorNotFound("Chat not found"));
-            }
-        };
-        let messages = chat
-            .messages
-            .clone()
-            .into_iter()
-            .map(|m| m.load(&db))
-            .collect::<Result<Option<Vec<_>>, _>>()?;
-        let messages = match messages {
-            Some(messages) => messages,
-            None => return Err(actix_web::error::ErrorNotFound("Messages not found")),
-        };
-        //write chat to chat.json, and make each message a line in messages.txt
-        fs::write(
-            format!("{}/{}/chat.json", report_base_folder, report_uuid),
-            serde_json::to_string(&chat)?,
-        )?;
-        fs::write(
-            format!("{}/{}/messages.txt", report_base_folder, report_uuid),
-            messages
-                .iter()
-                .map(|m| m.pprint())
-                .collect::<Result<Vec<_>, _>>()?
-                .join("\n"),
-        )?;
-        //write each message to message_{message_id}.json
-        for (i, message) in messages.iter().enumerate() {
-            fs::write(
-                format!("{}/{}/message_{}.json", report_base_folder, report_uuid, i),
-                serde_json::to_string(&message)?,
-            )?;
-        }
-        //for each message, if it has an image, write the image to message_{message_id}.jpg
-        for (i, message) in messages.iter().enumerate() {
-            if let Some(image) = &message.image {
-                let image = image.load(&db)?;
-                let image = match image {
-                    Some(image) => image,
-                    None => {
-                        return Err(actix_web::error::ErrorNotFound("Image not found"));
-                    }
-                };
-                fs::write(
-                    format!("{}/{}/message_{}.jpg", report_base_folder, report_uuid, i),
-                    image.content.clone(),
-                )?;
+    let target_chat = match &body.chat {
+        Some(chat_uuid) => {
+            let internal_uuid: InternalUuid<InternalChat> = chat_uuid.clone().into();
+            match internal_uuid.load(&db)? {
+                //the snapshot copies every message, so only someone in the chat gets one
+                Some(chat) if reporter.as_ref().is_some_and(|r| chat.users.contains(r)) => {
+                    Some(chat)
+                }
+                Some(_) => {
+                    return Err(actix_web::error::ErrorForbidden(
+                        "Only members of a chat can report it",
+                    ))
+                }
+                None => return Err(actix_web::error::ErrorNotFound("Chat not found")),
             }
         }
+        None => None,
+    };
+
+    let evidence = Evidence::snapshot(&db, buf, target_user.as_ref(), target_chat.as_ref())
+        .map_err(|e| write_error(e, "Failed to collect evidence"))?;
+    let report = InternalReport {
+        uuid: InternalUuid::new(),
+        kind: if body.is_violation {
+            ReportKind::Violation
+        } else {
+            ReportKind::Bug
+        },
+        status: ReportStatus::Open,
+        created_at: chrono::Utc::now().timestamp(),
+        reporter,
+        target_user: target_user.map(|user| user.uuid),
+        target_chat: target_chat.map(|chat| chat.uuid),
+        content: body.content.clone(),
+        platform: body.platform.clone().unwrap_or("unknown".to_string()),
+        evidence,
+        assignee: None,
+        notes: vec![],
+        resolution: None,
     };
+    report
+        .save(&db)
+        .map_err(|e| write_error(e, "Failed to save report"))?;
 
-    return Ok(Json(true));
+    Ok(Json(true))
 }
@@ -0,0 +1,116 @@
+use actix_web::{Error, HttpRequest};
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::DB,
+    mailer::{Mail, Mailer},
+    models::internal_models::{
+        internal_reset_code::{InternalResetCode, RESET_CODE_MINUTES},
+        internal_user::Role,
+    },
+    routes::{
+        common::hash_new_password,
+        shared::{require_role, route_body_mut_db},
+    },
+};
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct IssueResetCodeRequest {
+    pub username: String,
+    //users have no address on file, the admin passes on the one they were given
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct IssuedResetCode {
+    //left out when it was mailed, only the user should see it then
+    pub code: Option<String>,
+    pub expires: i64,
+}
+
+/// Support and up. Issues a reset code for a user who lost their password, mailing it
+/// if given an address and returning it otherwise.
+#[api_v2_operation]
+#[post("/issue_reset_code")]
+async fn issue_reset_code(
+    db: web::Data<DB>,
+    mailer: web::Data<Box<dyn Mailer>>,
+    req: HttpRequest,
+    body: Json<IssueResetCodeRequest>,
+) -> Result<Json<IssuedResetCode>, Error> {
+    route_body_mut_db(db, req, body, |db, staff, body| {
+        require_role(&staff, Role::Support)?;
+        let user = db
+            .get_user_by_username(&body.username)
+            .map_err(|e| {
+                log::error!("Failed to get user by username {:?}", e);
+                actix_web::error::ErrorInternalServerError("Failed to get user by username")
+            })?
+            .ok_or(actix_web::error::ErrorNotFound("User not found"))?;
+        let (reset_code, code) = InternalResetCode::issue(db, &user.uuid).map_err(|e| {
+            log::error!("Failed to issue reset code {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to issue reset code")
+        })?;
+
+        let code = match body.email {
+            Some(email) => {
+                let mail = Mail {
+                    to: email,
+                    subject: "Your password reset code".to_string(),
+                    body: format!(
+                        "Hi {},\n\nUse the code {} to set a new password. It works once, for the next {} minutes.",
+                        user.display_name, code, RESET_CODE_MINUTES
+                    ),
+                };
+                mailer.send(&mail).map_err(|e| {
+                    log::error!("Failed to send reset code {:?}", e);
+                    actix_web::error::ErrorInternalServerError("Failed to send reset code")
+                })?;
+                None
+            }
+            None => Some(code),
+        };
+        Ok(IssuedResetCode {
+            code,
+            expires: reset_code.expires,
+        })
+    })
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct ResetPasswordRequest {
+    pub code: String,
+    pub new_password: String,
+}
+
+/// Sets a new password with a reset code and logs the user out everywhere.
+#[api_v2_operation]
+#[post("/reset_password")]
+async fn reset_password(
+    db: web::Data<DB>,
+    body: Json<ResetPasswordRequest>,
+) -> Result<Json<bool>, Error> {
+    let body = body.into_inner();
+    let hashed_password = hash_new_password(&body.new_password)?;
+    let redeemed = InternalResetCode::redeem(&db, &body.code, &hashed_password).map_err(|e| {
+        log::error!("Failed to redeem reset code {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to reset password")
+    })?;
+    let user = match redeemed {
+        Ok(user) => user,
+        Err(e) => {
+            log::info!("Refused reset code: {}", e.message());
+            return Err(actix_web::error::ErrorBadRequest(e.message()));
+        }
+    };
+    db.end_all_sessions(&user).map_err(|e| {
+        log::error!("Failed to end sessions {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to end sessions")
+    })?;
+    Ok(Json(true))
+}
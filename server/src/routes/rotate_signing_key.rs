@@ -0,0 +1,37 @@
+use actix_web::{Error, HttpRequest};
+
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+};
+
+use crate::{
+    db::DB,
+    middleware::keyring::keyring,
+    models::internal_models::internal_user::Role,
+    routes::shared::{require_role, route_body_mut_db},
+};
+
+/// Admin only. Starts signing with a new key, tokens from the old one keep working
+/// until they expire. Returns the new key id.
+#[api_v2_operation]
+#[post("/rotate_signing_key")]
+async fn rotate_signing_key(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<bool>,
+) -> Result<Json<String>, Error> {
+    route_body_mut_db(db, req, body, |_, user, _| {
+        require_role(&user, Role::Admin)?;
+        let mut keyring = keyring().write().map_err(|e| {
+            log::error!("Keyring lock poisoned {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to rotate signing key")
+        })?;
+        let kid = keyring.rotate().map_err(|e| {
+            log::error!("Failed to rotate signing key {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to rotate signing key")
+        })?;
+        log::info!("Rotated signing key, now signing with {}", kid);
+        Ok(kid)
+    })
+}
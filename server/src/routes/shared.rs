@@ -5,11 +5,23 @@ use actix_web::{
 
 use crate::{
     db::DB,
-    models::internal_models::{internal_user::InternalUser, shared::InternalUuid},
+    models::internal_models::{
+        internal_user::{InternalUser, Role},
+        shared::InternalUuid,
+    },
+    transaction::WriteConflict,
 };
 
 use actix_web::HttpMessage;
 
+/// Turns away anyone without at least the given role, for staff only routes.
+pub fn require_role(user: &InternalUser, role: Role) -> Result<(), actix_web::Error> {
+    if !user.has_role(role) {
+        return Err(actix_web::error::ErrorForbidden(format!("{:?} only", role)));
+    }
+    Ok(())
+}
+
 pub fn route_body_mut_db<T, R>(
     db: web::Data<DB>,
     req: HttpRequest,
@@ -65,3 +77,13 @@ pub fn route_file_mut_db<R>(
     let result = fn_(&db, user, inner)?;
     Ok(Json(result))
 }
+
+/// Maps a failed write, a `WriteConflict` that outlived its retries becomes a 409.
+pub fn write_error(e: Box<dyn std::error::Error>, message: &'static str) -> actix_web::Error {
+    if e.is::<WriteConflict>() {
+        log::warn!("{} {}", message, e);
+        return actix_web::error::ErrorConflict("Concurrent update, please try again");
+    }
+    log::error!("{} {:?}", message, e);
+    actix_web::error::ErrorInternalServerError(message)
+}
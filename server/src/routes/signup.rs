@@ -9,14 +9,23 @@ use serde::Deserialize;
 
 use crate::{
     db::DB,
-    middleware::jwt::make_jwt,
     models::{
         api_models::{api_user::ApiUserWritable, shared::ApiUuid},
-        internal_models::{internal_user::InternalUser, shared::Save},
+        internal_models::{
+            internal_access_code::{InternalAccessCode, ACCESS_CODES_BY_CODE},
+            internal_user::{InternalUser, USERS_BY_USERNAME},
+        },
     },
-    routes::common::Jwt,
+    routes::{
+        common::{start_session, Jwt},
+        shared::write_error,
+    },
+    transaction::SaveTxn,
 };
 
+//unknown and spent codes look the same, guessing shouldn't learn anything
+const INVALID_ACCESS_CODE: &str = "Invalid access code";
+
 #[derive(Apiv2Schema, Deserialize)]
 struct SignupInput {
     access_code: String,
@@ -55,48 +64,48 @@ async fn signup(db: web::Data<DB>, body: Json<SignupInput>) -> Result<Json<Jwt>,
 
     let access_code = access_code.to_uppercase();
 
-    if access_code != "ANAK-AZAN" {
-        let access_code_internal = db.get_access_code_by_code(&access_code).map_err(|e| {
-            log::error!("Failed to get access code {:?}", e);
-            actix_web::error::ErrorInternalServerError("Failed to get access code")
-        })?;
-
-        //is the access code valid
-        match access_code_internal {
-            Some(mut access_code) => {
-                if access_code.used {
-                    Err(actix_web::error::ErrorBadRequest(
-                        "Access code already used",
-                    ))?;
-                } else {
-                    access_code.used = true;
-
-                    access_code.save(&db).map_err(|e| {
-                        log::error!("Failed to save access code {:?}", e);
-                        actix_web::error::ErrorInternalServerError("Failed to save access code")
-                    })?;
+    let saved = db
+        .retry_on_conflict(|| {
+            let access_code_internal = if access_code != "ANAK-AZAN" {
+                let uuid =
+                    match ACCESS_CODES_BY_CODE.get::<InternalAccessCode>(&db, &access_code)? {
+                        Some(uuid) => uuid,
+                        None => return Ok(Err(INVALID_ACCESS_CODE)),
+                    };
+                let mut access_code = match db.load_versioned(&uuid)? {
+                    Some(access_code) => access_code,
+                    None => return Ok(Err(INVALID_ACCESS_CODE)),
+                };
+                //is the access code valid
+                if access_code.value.used {
+                    return Ok(Err(INVALID_ACCESS_CODE));
                 }
-            }
-            None => {
-                Err(actix_web::error::ErrorBadRequest("Access code not found"))?;
-            }
-        }
-    }
-
-    let internal_uuid = internal_user.save(&db).map_err(|e| {
-        log::error!("Failed to save user {:?}", e);
-        actix_web::error::ErrorInternalServerError("Failed to save user")
-    })?;
+                access_code.value.used = true;
+                Some(access_code)
+            } else {
+                None
+            };
 
-    make_jwt(&internal_uuid)
-        .map(|jwt| {
-            Json(Jwt {
-                jwt,
-                uuid: internal_uuid.into(),
+            //the code is spent and the user created together, or neither happens
+            db.transaction(|txn| {
+                //a concurrent signup may have taken the username since it was checked
+                if USERS_BY_USERNAME
+                    .get_txn::<InternalUser>(txn, &internal_user.username)?
+                    .is_some()
+                {
+                    return Ok(Err("Username already taken"));
+                }
+                if let Some(access_code) = &access_code_internal {
+                    txn.expect_version(&access_code.value.uuid, access_code.version)?;
+                    access_code.value.save_txn(txn)?;
+                }
+                internal_user.save_txn(txn)?;
+                Ok(Ok(internal_user.uuid.clone()))
             })
         })
-        .map_err(|e| {
-            log::error!("Failed to make jwt {:?}", e);
-            actix_web::error::ErrorInternalServerError("Failed to make jwt")
-        })
+        .map_err(|e| write_error(e, "Failed to save user"))?;
+
+    let internal_uuid = saved.map_err(actix_web::error::ErrorBadRequest)?;
+
+    start_session(&db, &internal_uuid)
 }
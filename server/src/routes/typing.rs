@@ -0,0 +1,57 @@
+use actix_web::{Error, HttpRequest};
+
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::DB,
+    models::{
+        api_models::shared::ApiUuid,
+        internal_models::{internal_chat::InternalChat, shared::InternalUuid},
+    },
+    push::PushEvent,
+    routes::shared::route_body_mut_db,
+};
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct TypingInput {
+    chat_uuid: ApiUuid<InternalChat>,
+}
+
+/// Tells the other members of the chat that the user is typing. Nothing is stored,
+/// members without an open socket never hear about it.
+#[api_v2_operation]
+#[post("/typing")]
+pub fn typing(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<TypingInput>,
+) -> Result<Json<bool>, Error> {
+    route_body_mut_db(db, req, body, |db, user, body| {
+        let chat_uuid: InternalUuid<InternalChat> = body.chat_uuid.into();
+        let chat = chat_uuid.load(db).map_err(|e| {
+            log::error!("Failed to get chat {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to get chat")
+        })?;
+        let chat = match chat {
+            Some(chat) => chat,
+            None => return Err(actix_web::error::ErrorNotFound("Chat not found")),
+        };
+        if !chat.users.contains(&user.uuid) {
+            return Err(actix_web::error::ErrorBadRequest("User not in chat"));
+        }
+
+        let event = PushEvent::Typing {
+            chat: chat.uuid.into(),
+            user: user.uuid.clone().into(),
+        };
+        for member in chat.users.iter().filter(|m| *m != &user.uuid) {
+            db.push.push(member, &event);
+        }
+        Ok(true)
+    })
+}
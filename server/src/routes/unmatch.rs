@@ -0,0 +1,42 @@
+use actix_web::{Error, HttpRequest};
+
+use paperclip::actix::{
+    api_v2_operation, post,
+    web::{self, Json},
+    Apiv2Schema,
+};
+use serde::Deserialize;
+
+use crate::{
+    db::DB,
+    models::{
+        api_models::shared::ApiUuid,
+        internal_models::{
+            internal_user::InternalUser, migration::migration::get_admin_uuid, shared::InternalUuid,
+        },
+    },
+    routes::shared::{route_body_mut_db, write_error},
+};
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+struct UnmatchInput {
+    user_uuid: ApiUuid<InternalUser>,
+}
+
+/// Ends the match with the user, deleting the chat for both of you.
+#[api_v2_operation]
+#[post("/unmatch")]
+pub fn unmatch(
+    db: web::Data<DB>,
+    req: HttpRequest,
+    body: Json<UnmatchInput>,
+) -> Result<Json<bool>, Error> {
+    route_body_mut_db(db, req, body, |db, user, body| {
+        let other: InternalUuid<InternalUser> = body.user_uuid.into();
+        if other == user.uuid || other == get_admin_uuid() {
+            return Err(actix_web::error::ErrorBadRequest("Can't unmatch this user"));
+        }
+        InternalUser::unmatch(db, &user.uuid, &other)
+            .map_err(|e| write_error(e, "Failed to unmatch"))
+    })
+}
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+use actix_web::{rt, web, Error, HttpRequest, HttpResponse};
+use actix_ws::{CloseCode, CloseReason, Message};
+use serde::Deserialize;
+
+use crate::{
+    db::DB, middleware::jwt::authenticate,
+    models::internal_models::internal_session::InternalSession,
+};
+
+//how often the socket is pinged, and the session checked for a logout
+const HEARTBEAT: Duration = Duration::from_secs(30);
+//no answer for this long and the client is gone
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(90);
+
+#[derive(Debug, Deserialize)]
+pub struct WsQuery {
+    //browsers can't set headers on a socket, so the jwt comes in the url
+    token: String,
+}
+
+/// Pushes `PushEvent`s to the user as they happen. It only saves polling, a client
+/// that can't connect or drops out loses nothing by using the routes instead.
+pub async fn ws(
+    req: HttpRequest,
+    body: web::Payload,
+    db: web::Data<DB>,
+    query: web::Query<WsQuery>,
+) -> Result<HttpResponse, Error> {
+    let (user, session_uuid) = authenticate(Some(&db), &query.token)?;
+    let (response, mut session, mut messages) = actix_ws::handle(&req, body)?;
+    let (id, mut events) = db.push.connect(&user);
+    log::info!("Socket {} opened for {}", id, user.id);
+
+    rt::spawn(async move {
+        let mut heartbeat = rt::time::interval(HEARTBEAT);
+        let mut last_seen = Instant::now();
+        let reason = loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Some(json) => {
+                        if session.text(json).await.is_err() {
+                            break None;
+                        }
+                    }
+                    None => break None,
+                },
+                message = messages.recv() => match message {
+                    Some(Ok(Message::Ping(bytes))) => {
+                        last_seen = Instant::now();
+                        if session.pong(&bytes).await.is_err() {
+                            break None;
+                        }
+                    }
+                    Some(Ok(Message::Pong(_))) => last_seen = Instant::now(),
+                    Some(Ok(Message::Close(reason))) => break reason,
+                    //nothing to receive, everything the client sends goes through the routes
+                    Some(Ok(_)) => last_seen = Instant::now(),
+                    Some(Err(e)) => {
+                        log::info!("Socket {} failed {:?}", id, e);
+                        break None;
+                    }
+                    None => break None,
+                },
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() > CLIENT_TIMEOUT {
+                        break None;
+                    }
+                    //the socket outlives the jwt it was opened with, logging out still ends it
+                    match InternalSession::is_active(&db, &session_uuid) {
+                        Ok(true) => {}
+                        Ok(false) => break Some(CloseReason {
+                            code: CloseCode::Policy,
+                            description: Some("Session revoked".to_string()),
+                        }),
+                        Err(e) => log::error!("Failed to check session {:?}", e),
+                    }
+                    if session.ping(b"").await.is_err() {
+                        break None;
+                    }
+                }
+            }
+        };
+        db.push.disconnect(&user, id);
+        log::info!("Socket {} closed for {}", id, user.id);
+        let _ = session.close(reason).await;
+    });
+
+    Ok(response)
+}
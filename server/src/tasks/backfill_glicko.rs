@@ -0,0 +1,66 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+};
+
+use crate::{
+    db::DB,
+    glicko::{rate_pair, Glicko},
+    models::internal_models::{
+        internal_glicko::InternalGlicko,
+        internal_user::{InternalRating, InternalUser},
+        shared::{InternalUuid, Save},
+    },
+};
+
+const BACKFILLED_FLAG: &str = "glicko_backfilled";
+
+/// Rebuilds every user's rating by replaying the `ratings` they've received. The
+/// history has no timestamps, so games are replayed in the order they're stored.
+pub fn backfill_glicko(db: &DB) -> Result<usize, Box<dyn Error>> {
+    let mut glickos: HashMap<String, (Glicko, u32)> = HashMap::new();
+    let mut users = HashSet::new();
+
+    for user in db.iter_obj::<InternalUser>()? {
+        let user = user?;
+        for rating in &user.ratings {
+            let (rater, liked) = match rating {
+                InternalRating::LikedBy(rater) => (rater, true),
+                InternalRating::PassedBy(rater) => (rater, false),
+            };
+            let rater_glicko = glickos.get(&rater.id).map(|g| g.0).unwrap_or_default();
+            let target_glicko = glickos.get(&user.uuid.id).map(|g| g.0).unwrap_or_default();
+            let (new_rater, new_target) = rate_pair(&rater_glicko, &target_glicko, liked);
+
+            let entry = glickos.entry(rater.id.clone()).or_default();
+            *entry = (new_rater, entry.1 + 1);
+            let entry = glickos.entry(user.uuid.id.clone()).or_default();
+            *entry = (new_target, entry.1 + 1);
+        }
+        users.insert(user.uuid.id);
+    }
+
+    let mut saved = 0;
+    for (id, (glicko, games)) in glickos {
+        //deleted raters still count as opponents, but don't get a rating of their own
+        if !users.contains(&id) {
+            continue;
+        }
+        let user: InternalUuid<InternalUser> = id.into();
+        InternalGlicko::from_glicko(&user, &glicko, games).save(db)?;
+        saved += 1;
+    }
+    Ok(saved)
+}
+
+/// Runs the backfill once per db, ratings after that are kept up to date by `rate`.
+pub fn backfill_glicko_if_needed(db: &DB) -> Result<(), Box<dyn Error>> {
+    if db.get_flag(BACKFILLED_FLAG)? {
+        return Ok(());
+    }
+    log::info!("Backfilling glicko ratings from user ratings");
+    let saved = backfill_glicko(db)?;
+    db.set_flag(BACKFILLED_FLAG, true)?;
+    log::info!("Backfilled glicko ratings for {} users", saved);
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::{collections::HashMap, error::Error};
+
+use crate::{
+    db::DB,
+    models::internal_models::{
+        internal_image::InternalImage, internal_message::InternalMessage,
+        internal_user::InternalUser,
+    },
+};
+
+const REFS_COUNTED_FLAG: &str = "image_refs_counted";
+
+//an image is uploaded before anything shows it, so it gets this long to be put somewhere
+pub const IMAGE_GRACE_SECONDS: i64 = 60 * 60;
+
+/// How much a pass of `collect_images` removed.
+#[derive(Debug, Default, PartialEq)]
+pub struct CollectReport {
+    pub images: usize,
+    pub blobs: usize,
+    pub bytes: u64,
+}
+
+/// Deletes the images nothing shows anymore, then the blobs no image points at.
+/// Only what's older than `IMAGE_GRACE_SECONDS` before `now` is touched.
+pub fn collect_images(db: &DB, now: i64) -> Result<CollectReport, Box<dyn Error>> {
+    let cutoff = now - IMAGE_GRACE_SECONDS;
+    let mut report = CollectReport::default();
+
+    let unreferenced = db
+        .iter_obj::<InternalImage>()?
+        .filter(|image| match image {
+            Ok(image) => image.refs == 0 && image.created_at < cutoff,
+            Err(_) => true,
+        })
+        .map(|image| image.map(|image| image.uuid))
+        .collect::<Result<Vec<_>, _>>()?;
+    for image in unreferenced {
+        //it may have been put somewhere since it was read
+        if InternalImage::delete_if_unreferenced(db, &image)? {
+            report.images += 1;
+        }
+    }
+
+    let blobs = db.collect_blobs(cutoff)?;
+    report.blobs = blobs.blobs;
+    report.bytes = blobs.bytes;
+    Ok(report)
+}
+
+/// Sets the refs of every image from the profiles and messages showing it,
+/// returns how many were off.
+pub fn count_image_refs(db: &DB) -> Result<usize, Box<dyn Error>> {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for user in db.iter_obj::<InternalUser>()? {
+        let user = user?;
+        for image in user.image_refs() {
+            *counts.entry(image.id.clone()).or_default() += 1;
+        }
+    }
+    for message in db.iter_obj::<InternalMessage>()? {
+        if let Some(image) = message?.image {
+            *counts.entry(image.id).or_default() += 1;
+        }
+    }
+
+    let images = db
+        .iter_obj::<InternalImage>()?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut fixed = 0;
+    for image in images {
+        let refs = counts.get(&image.uuid.id).copied().unwrap_or_default();
+        if image.refs != refs {
+            db.update(&image.uuid, |image: &mut InternalImage| image.refs = refs)?;
+            fixed += 1;
+        }
+    }
+    Ok(fixed)
+}
+
+/// Counts refs once per db, images stored before they were counted start at zero.
+pub fn count_image_refs_if_needed(db: &DB) -> Result<(), Box<dyn Error>> {
+    if db.get_flag(REFS_COUNTED_FLAG)? {
+        return Ok(());
+    }
+    log::info!("Counting image references");
+    let fixed = count_image_refs(db)?;
+    db.set_flag(REFS_COUNTED_FLAG, true)?;
+    log::info!("Counted image references, {} images were off", fixed);
+    Ok(())
+}
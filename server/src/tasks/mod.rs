@@ -1,3 +1,7 @@
+pub mod backfill_glicko;
+pub mod collect_images;
+pub mod rebuild_indexes;
+pub mod remap_prefs;
 pub mod tasks;
 pub mod update_age;
 pub mod update_elo;
@@ -0,0 +1,33 @@
+use std::error::Error;
+
+use crate::{db::DB, index::RebuiltIndexes, models::internal_models::shared::MODELS};
+
+//indexes written before `Insertable::indexes` kept track of them may hold stale keys
+const INDEXES_TRACKED_FLAG: &str = "indexes_tracked";
+
+/// Rebuilds the declared indexes of every model that has any.
+pub fn rebuild_indexes(db: &DB) -> Result<RebuiltIndexes, Box<dyn Error>> {
+    let mut rebuilt = RebuiltIndexes::default();
+    for model in MODELS.iter().filter(|model| !(model.indexes)().is_empty()) {
+        let pass = (model.rebuild_indexes)(db)?;
+        rebuilt.objects += pass.objects;
+        rebuilt.conflicts += pass.conflicts;
+    }
+    Ok(rebuilt)
+}
+
+/// Rebuilds once per db, from then on every write keeps the indexes in step.
+pub fn rebuild_indexes_if_needed(db: &DB) -> Result<(), Box<dyn Error>> {
+    if db.get_flag(INDEXES_TRACKED_FLAG)? {
+        return Ok(());
+    }
+    log::info!("Rebuilding indexes");
+    let rebuilt = rebuild_indexes(db)?;
+    db.set_flag(INDEXES_TRACKED_FLAG, true)?;
+    log::info!(
+        "Rebuilt indexes of {} objects, {} left out for taken unique values",
+        rebuilt.objects,
+        rebuilt.conflicts
+    );
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+
+use crate::{
+    db::DB,
+    models::internal_models::{
+        internal_prefs_config::{prefs_schema, PrefsSchema},
+        internal_user::InternalUser,
+        shared::Save,
+    },
+};
+
+const SCHEMA_BUCKET: &str = "prefs_schema";
+
+/// The version and fingerprint of the schema the stored users were last laid out by.
+fn applied_schema(db: &DB) -> Result<Option<(u64, u64)>, Box<dyn Error>> {
+    let bucket = db.store.bucket::<String, String>(Some(SCHEMA_BUCKET))?;
+    let version = bucket.get(&"version".to_string())?;
+    let fingerprint = bucket.get(&"fingerprint".to_string())?;
+    match (version, fingerprint) {
+        (Some(version), Some(fingerprint)) => Ok(Some((version.parse()?, fingerprint.parse()?))),
+        _ => Ok(None),
+    }
+}
+
+fn set_applied_schema(db: &DB, schema: &PrefsSchema) -> Result<(), Box<dyn Error>> {
+    let bucket = db.store.bucket::<String, String>(Some(SCHEMA_BUCKET))?;
+    bucket.set(&"version".to_string(), &schema.version.to_string())?;
+    bucket.set(
+        &"fingerprint".to_string(),
+        &schema.fingerprint().to_string(),
+    )?;
+    Ok(())
+}
+
+/// Rewrites every user's props and prefs into the order of `schema`, matching them up
+/// by name. Returns how many users had to change.
+pub fn remap_prefs(db: &DB, schema: &PrefsSchema) -> Result<usize, Box<dyn Error>> {
+    let mut remapped = 0;
+    for user in db.iter_obj::<InternalUser>()? {
+        let mut user = user?;
+        if schema.is_laid_out(&user.props, &user.prefs) {
+            continue;
+        }
+        user.props = schema.remap_props(&user.props);
+        user.prefs = schema.remap_prefs(&user.prefs);
+        user.save(db)?;
+        remapped += 1;
+    }
+    Ok(remapped)
+}
+
+/// Remaps the stored users when the schema's questions changed since the last start.
+/// A schema whose questions changed without its version going up is refused, it's
+/// most likely an older file than the one the db was laid out by.
+pub fn remap_prefs_if_needed(db: &DB) -> Result<(), Box<dyn Error>> {
+    let schema = prefs_schema();
+    match applied_schema(db)? {
+        Some((_, fingerprint)) if fingerprint == schema.fingerprint() => {}
+        Some((version, _)) if version >= schema.version => {
+            return Err(format!(
+                "Prefs schema questions changed but its version {} is not above {}",
+                schema.version, version
+            )
+            .into());
+        }
+        _ => {
+            log::info!(
+                "Remapping props and prefs to prefs schema {}",
+                schema.version
+            );
+            let remapped = remap_prefs(db, schema)?;
+            log::info!("Remapped props and prefs of {} users", remapped);
+        }
+    }
+    set_applied_schema(db, schema)
+}
@@ -1,7 +1,7 @@
 use crate::{
     db::DB,
     models::internal_models::{internal_user::InternalUser, shared::Save},
-    tasks::{update_age::update_age, update_elo::update_elo},
+    tasks::{collect_images::collect_images, update_age::update_age, update_elo::update_elo},
 };
 
 pub fn run_all_tasks(db: &DB) -> Result<(), Box<dyn std::error::Error>> {
@@ -9,9 +9,26 @@ pub fn run_all_tasks(db: &DB) -> Result<(), Box<dyn std::error::Error>> {
     for user in db.iter_obj::<InternalUser>()? {
         let mut user = user?;
         update_age(&mut user);
-        update_elo(&mut user);
+        update_elo(db, &mut user)?;
         user.save(db)?;
     }
+    let ended = db.end_expired_sessions()?;
+    if ended > 0 {
+        log::info!("Ended {} expired sessions", ended);
+    }
+    let deleted = db.delete_expired_reset_codes()?;
+    if deleted > 0 {
+        log::info!("Deleted {} expired reset codes", deleted);
+    }
+    let collected = collect_images(db, chrono::Utc::now().timestamp())?;
+    if collected.images > 0 || collected.blobs > 0 {
+        log::info!(
+            "Collected {} unused images and {} blobs, reclaimed {} bytes",
+            collected.images,
+            collected.blobs,
+            collected.bytes
+        );
+    }
     Ok(())
 }
 
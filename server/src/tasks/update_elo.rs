@@ -1,6 +1,7 @@
-use crate::{elo::calc_elo, models::internal_models::internal_user::InternalUser};
+use crate::{db::DB, elo::calc_elo, models::internal_models::internal_user::InternalUser};
 
-pub fn update_elo(user: &mut InternalUser) {
-    let elo = calc_elo(&user.ratings, &user.actions, &user.props);
-    user.elo = elo;
+pub fn update_elo(db: &DB, user: &mut InternalUser) -> Result<(), Box<dyn std::error::Error>> {
+    let glicko = db.get_glicko(&user.uuid)?;
+    user.elo = calc_elo(&glicko.glicko(), &user.actions, &user.props).elo;
+    Ok(())
 }
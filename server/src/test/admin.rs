@@ -0,0 +1,103 @@
+#[actix_web::test]
+async fn staff_roles_and_bans() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        middleware::jwt::{make_jwt, Jwt},
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_prefs::PreferenceRange,
+                internal_session::InternalSession,
+                internal_user::{InternalUser, Role},
+                shared::{InternalUuid, Save},
+            },
+        },
+        routes::admin::{ban_user, unpublish_user},
+        test::fake::Gen,
+    };
+    use actix_web::{http::StatusCode, test, web, App};
+
+    DB::destroy_database_for_real_dangerous("test_admin");
+    let db = DB::new("test_admin").unwrap();
+    db.migrate_all().unwrap();
+    let db = web::Data::new(db);
+
+    //open to everyone, so searching as a user finds them
+    let user =
+        |role: Option<Role>| -> Result<InternalUuid<InternalUser>, Box<dyn std::error::Error>> {
+            let mut user: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+            user.role = role;
+            user.published = true;
+            for pref in user.prefs.iter_mut() {
+                pref.range = PreferenceRange {
+                    min: i16::MIN,
+                    max: i16::MAX,
+                };
+                pref.strictness = Default::default();
+            }
+            user.save(&db)
+        };
+    let support = user(Some(Role::Support))?;
+    let moderator = user(Some(Role::Moderator))?;
+    let mallory = user(None)?;
+    let eve = user(None)?;
+    let searchable = |uuid: &InternalUuid<InternalUser>| -> bool {
+        let user = uuid.load(&db).unwrap().unwrap();
+        db.get_mutual_preference_users_direct(&user.props, &user.prefs, &vec![])
+            .unwrap()
+            .iter()
+            .any(|found| &found.uuid == uuid)
+    };
+    let token = |uuid: &InternalUuid<InternalUser>| -> Result<String, Box<dyn std::error::Error>> {
+        let (session, _) = InternalSession::start(&db, uuid)?;
+        make_jwt(uuid, &session.uuid)
+    };
+    let (support_token, moderator_token) = (token(&support)?, token(&moderator)?);
+    assert!(searchable(&mallory));
+    assert!(searchable(&eve));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(db.clone())
+            .wrap(Jwt)
+            .service(ban_user)
+            .service(unpublish_user),
+    )
+    .await;
+    let post = |path: &str, token: &str, target: &InternalUuid<InternalUser>, ban: bool| {
+        test::TestRequest::post()
+            .uri(path)
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(serde_json::json!({ "user_uuid": target.id, "banned": ban }))
+            .to_request()
+    };
+
+    //support can look users up but not ban them
+    let res = test::call_service(&app, post("/ban_user", &support_token, &mallory, true)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert!(!db.is_banned(&mallory)?);
+
+    //moderators deal with users, not other staff
+    let res = test::call_service(&app, post("/ban_user", &moderator_token, &support, true)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = test::call_service(
+        &app,
+        post("/unpublish_user", &moderator_token, &support, false),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert!(!db.is_banned(&support)?);
+    assert!(searchable(&support));
+
+    //a banned user drops out of search, so does an unpublished one
+    let res = test::call_service(&app, post("/ban_user", &moderator_token, &mallory, true)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(db.is_banned(&mallory)?);
+    assert!(!searchable(&mallory));
+    let res =
+        test::call_service(&app, post("/unpublish_user", &moderator_token, &eve, false)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(!searchable(&eve));
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+#[test]
+fn backfill_glicko_replays_ratings() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_user::{InternalRating, InternalUser},
+                shared::Save,
+            },
+        },
+        tasks::backfill_glicko::backfill_glicko_if_needed,
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_backfill_glicko");
+    let db = DB::new("test_backfill_glicko").unwrap();
+    db.migrate_all().unwrap();
+
+    let alice: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let mut bob: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let mut carol: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+
+    //bob is liked, carol is passed on
+    bob.ratings
+        .push(InternalRating::LikedBy(alice.uuid.clone()));
+    carol
+        .ratings
+        .push(InternalRating::PassedBy(alice.uuid.clone()));
+    let alice = alice.save(&db)?;
+    let bob = bob.save(&db)?;
+    let carol = carol.save(&db)?;
+
+    backfill_glicko_if_needed(&db)?;
+
+    let alice = db.get_glicko(&alice)?;
+    let bob = db.get_glicko(&bob)?;
+    let carol = db.get_glicko(&carol)?;
+    assert_eq!(alice.games, 2);
+    assert_eq!(bob.games, 1);
+    assert_eq!(carol.games, 1);
+    assert!(bob.rating > carol.rating);
+    assert!(bob.glicko().score() > carol.glicko().score());
+
+    DB::destroy_database_for_real_dangerous("test_backfill_glicko");
+    Ok(())
+}
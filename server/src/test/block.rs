@@ -0,0 +1,94 @@
+#[test]
+fn block_ends_the_match() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_chat::InternalChat,
+                internal_message::{InternalMessage, ReadReceipt},
+                internal_user::{InternalRating, InternalUser, Notification},
+                shared::{InternalUuid, Save},
+            },
+        },
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_block");
+    let db = DB::new("test_block").unwrap();
+    db.migrate_all().unwrap();
+
+    let mut alice: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let mut bob: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let carol: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let mut chat = InternalChat::new(vec![alice.uuid.clone(), bob.uuid.clone()]);
+    alice.add_chat(&chat);
+    bob.add_chat(&chat);
+    alice
+        .ratings
+        .push(InternalRating::LikedBy(bob.uuid.clone()));
+    bob.ratings
+        .push(InternalRating::LikedBy(alice.uuid.clone()));
+    bob.notifications
+        .push(Notification::Match(alice.uuid.clone()));
+    let (alice_uuid, bob_uuid, carol_uuid) =
+        (alice.uuid.clone(), bob.uuid.clone(), carol.uuid.clone());
+    alice.save(&db)?;
+    bob.save(&db)?;
+    carol.save(&db)?;
+
+    let message = InternalMessage {
+        uuid: InternalUuid::new(),
+        sent_at: chrono::Utc::now().timestamp(),
+        edited: false,
+        author: alice_uuid.clone(),
+        content: "hi".to_string(),
+        image: None,
+        read_by: vec![ReadReceipt {
+            user: alice_uuid.clone(),
+            read_at: chrono::Utc::now().timestamp(),
+        }],
+        chat: chat.uuid.clone(),
+    };
+    let message_uuid = message.save(&mut chat, &db)?;
+    let chat_uuid = chat.uuid.clone();
+    let (_, mut bob_events) = db.push.connect(&bob_uuid);
+
+    //nothing to unmatch with carol
+    assert!(!InternalUser::unmatch(&db, &alice_uuid, &carol_uuid)?);
+
+    db.block(&alice_uuid, &bob_uuid)?;
+    assert!(db.has_blocked(&alice_uuid, &bob_uuid)?);
+    assert!(!db.has_blocked(&bob_uuid, &alice_uuid)?);
+    assert_eq!(db.blocked_by(&alice_uuid)?, vec![bob_uuid.clone()]);
+
+    //the chat, its messages and the likes are gone for both
+    assert!(chat_uuid.load(&db)?.is_none());
+    assert!(message_uuid.load(&db)?.is_none());
+    assert!(InternalMessage::in_chat(&db, &chat_uuid)?.is_empty());
+    let alice = alice_uuid.load(&db)?.unwrap();
+    let bob = bob_uuid.load(&db)?.unwrap();
+    assert!(!alice.chats.contains(&chat_uuid) && !bob.chats.contains(&chat_uuid));
+    assert!(alice.ratings.is_empty() && bob.ratings.is_empty());
+    assert!(!bob
+        .notifications
+        .iter()
+        .any(|n| matches!(n, Notification::Match(_) | Notification::UnreadMessage(_))));
+
+    //bob is told the chat ended, the same as an unmatch
+    let event = bob_events.try_recv()?;
+    assert!(event.contains(r#""type":"ChatEnded""#));
+
+    //bob stays out of alice's results
+    assert!(!db
+        .get_mutual_preference_users(&alice)?
+        .iter()
+        .any(|user| user.uuid == bob_uuid));
+
+    //deleting alice forgets her blocks
+    let report = alice.delete(&db)?;
+    assert_eq!(report.blocks, 1);
+    assert!(!db.has_blocked(&alice_uuid, &bob_uuid)?);
+
+    Ok(())
+}
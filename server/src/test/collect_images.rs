@@ -0,0 +1,86 @@
+#[test]
+fn collect_images_keeps_what_messages_show() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::{api_image::ApiImageWritable, api_user::ApiUserWritable},
+            internal_models::{
+                internal_chat::InternalChat,
+                internal_image::Access,
+                internal_message::InternalMessage,
+                internal_user::InternalUser,
+                shared::{InternalUuid, Save},
+            },
+        },
+        tasks::collect_images::{collect_images, count_image_refs, IMAGE_GRACE_SECONDS},
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_collect_images");
+    let db = DB::new("test_collect_images").unwrap();
+    db.migrate_all().unwrap();
+
+    let mut alice: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let mut bob: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let mut chat = InternalChat::new(vec![alice.uuid.clone(), bob.uuid.clone()]);
+    alice.add_chat(&chat);
+    bob.add_chat(&chat);
+    let alice_uuid = alice.uuid.clone();
+    alice.save(&db)?;
+    bob.save(&db)?;
+
+    //nobody else has this one, the profiles above all use the test jpeg
+    let mut png = vec![];
+    image::DynamicImage::new_rgb8(40, 30)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    //the same upload twice is stored once
+    let sent = ApiImageWritable::new(png.clone()).to_internal(&db, Access::Everyone)?;
+    let unused = ApiImageWritable::new(png).to_internal(&db, Access::Everyone)?;
+    assert_eq!(sent.blob, unused.blob);
+    let blob = sent.blob.clone();
+    let sent = sent.save(&db)?;
+    let unused = unused.save(&db)?;
+
+    let message = InternalMessage {
+        uuid: InternalUuid::new(),
+        sent_at: chrono::Utc::now().timestamp(),
+        edited: false,
+        author: alice_uuid.clone(),
+        content: "".to_string(),
+        image: Some(sent.clone()),
+        read_by: vec![],
+        chat: chat.uuid.clone(),
+    };
+    let message_uuid = message.uuid.clone();
+    message.save(&mut chat, &db)?;
+    assert_eq!(sent.load(&db)?.unwrap().refs, 1);
+    //what was counted along the way matches a full count
+    assert_eq!(count_image_refs(&db)?, 0);
+
+    //nothing is collected while it's new
+    let now = chrono::Utc::now().timestamp();
+    assert_eq!(collect_images(&db, now)?.images, 0);
+
+    let later = now + IMAGE_GRACE_SECONDS + 1;
+    let report = collect_images(&db, later)?;
+    assert_eq!(report.images, 1);
+    assert_eq!(report.blobs, 0);
+    assert!(!unused.exists(&db)?);
+    assert!(sent.exists(&db)?);
+
+    message_uuid.load(&db)?.unwrap().delete(&db)?;
+    assert_eq!(sent.load(&db)?.unwrap().refs, 0);
+    let report = collect_images(&db, later)?;
+    assert_eq!(report.images, 1);
+    assert_eq!(report.blobs, 1);
+    assert!(report.bytes > 0);
+    assert!(!sent.exists(&db)?);
+    assert!(!db.blob_exists(&blob)?);
+
+    //profiles move their refs in the same write, so deleting one keeps the count right
+    alice_uuid.load(&db)?.unwrap().delete(&db)?;
+    assert_eq!(count_image_refs(&db)?, 0);
+
+    DB::destroy_database_for_real_dangerous("test_collect_images");
+    Ok(())
+}
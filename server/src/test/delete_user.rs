@@ -0,0 +1,87 @@
+#[test]
+fn delete_user_cascades() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::{api_image::ApiImageWritable, api_user::ApiUserWritable},
+            internal_models::{
+                internal_chat::InternalChat,
+                internal_image::Access,
+                internal_message::InternalMessage,
+                internal_session::InternalSession,
+                internal_user::{InternalRating, InternalUser, Notification},
+                shared::{InternalUuid, Save},
+            },
+        },
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_delete_user");
+    let db = DB::new("test_delete_user").unwrap();
+    db.migrate_all().unwrap();
+
+    let mut alice: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let mut bob: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+
+    let image = ApiImageWritable::gen(&true).to_internal(&db, Access::Everyone)?;
+    let image_uuid = image.save(&db)?;
+    alice.owned_images.push(image_uuid.clone());
+
+    //alice liked bob and they matched
+    alice.seen.push(bob.uuid.clone());
+    bob.ratings
+        .push(InternalRating::LikedBy(alice.uuid.clone()));
+    let mut chat = InternalChat::new(vec![alice.uuid.clone(), bob.uuid.clone()]);
+    alice.add_chat(&chat);
+    bob.add_chat(&chat);
+    bob.notifications
+        .push(Notification::Match(alice.uuid.clone()));
+    let alice_uuid = alice.uuid.clone();
+    let bob_uuid = bob.uuid.clone();
+    alice.save(&db)?;
+    bob.save(&db)?;
+
+    let message = InternalMessage {
+        uuid: InternalUuid::new(),
+        sent_at: chrono::Utc::now().timestamp(),
+        edited: false,
+        author: alice_uuid.clone(),
+        content: "hi".to_string(),
+        image: None,
+        read_by: vec![],
+        chat: chat.uuid.clone(),
+    };
+    let message_uuid = message.uuid.clone();
+    message.save(&mut chat, &db)?;
+    let chat_uuid = chat.uuid.clone();
+
+    let (session, _) = InternalSession::start(&db, &alice_uuid)?;
+
+    let alice = alice_uuid.load(&db)?.unwrap();
+    let username = alice.username.clone();
+    let report = alice.delete(&db)?;
+
+    assert!(report.user);
+    assert!(report.username_index);
+    assert_eq!(report.images, 1);
+    assert_eq!(report.ratings, 1);
+    //the admin chat and its welcome message go too
+    assert_eq!(report.chats, 2);
+    assert_eq!(report.messages, 2);
+    assert_eq!(report.sessions, 1);
+    assert!(!InternalSession::is_active(&db, &session.uuid)?);
+
+    assert!(!alice_uuid.exists(&db)?);
+    assert!(!image_uuid.exists(&db)?);
+    assert!(!chat_uuid.exists(&db)?);
+    assert!(!message_uuid.exists(&db)?);
+    assert!(db.get_user_by_username(&username)?.is_none());
+
+    let bob = bob_uuid.load(&db)?.unwrap();
+    assert!(bob.ratings.is_empty());
+    assert!(!bob.chats.contains(&chat_uuid));
+    assert!(bob.notifications.is_empty());
+
+    DB::destroy_database_for_real_dangerous("test_delete_user");
+    Ok(())
+}
@@ -92,7 +92,6 @@ fn insert_dummy_data() -> Result<(), Box<dyn std::error::Error>> {
         let mut chat = InternalChat {
             uuid: chat_uuid,
             users: vec![user.uuid.clone(), uuids[n].clone()],
-            messages: messages.iter().map(|m| m.uuid.clone()).collect(),
             most_recent_message: messages.last().unwrap().content.clone(),
             unread: vec![0, 0],
             most_recent_sender: Some(uuids[n].clone()),
@@ -0,0 +1,94 @@
+#[test]
+fn geo_matching_uses_great_circle_distance() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_prefs::{PreferenceRange, UnknownPolicy},
+                internal_user::InternalUser,
+                shared::{InternalUuid, Save},
+            },
+        },
+        test::fake::Gen,
+        util::to_i16,
+    };
+    use std::collections::HashSet;
+
+    DB::destroy_database_for_real_dangerous("test_geo_matching");
+    let db = DB::new("test_geo_matching").unwrap();
+    db.migrate_all().unwrap();
+
+    //open to everyone but for the radius, so only distance decides
+    let user_at = |latitude: f64,
+                   longitude: f64,
+                   radius: Option<i16>|
+     -> Result<InternalUser, Box<dyn std::error::Error>> {
+        let mut user: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+        for pref in user.prefs.iter_mut() {
+            pref.range = PreferenceRange {
+                min: i16::MIN,
+                max: i16::MAX,
+            };
+            pref.strictness = Default::default();
+        }
+        if let Some(radius) = radius {
+            let pref = user
+                .prefs
+                .iter_mut()
+                .find(|p| p.name == "max_distance_km")
+                .unwrap();
+            pref.range = PreferenceRange {
+                min: 0,
+                max: radius,
+            };
+            //only people who shared where they are
+            pref.unknown = UnknownPolicy::Reject;
+        }
+        for prop in user.props.iter_mut() {
+            match prop.name.as_str() {
+                "latitude" => prop.value = to_i16(latitude, -90.0, 90.0),
+                "longitude" => prop.value = to_i16(longitude, -180.0, 180.0),
+                _ => {}
+            }
+        }
+        Ok(user)
+    };
+    let save =
+        |user: InternalUser| -> Result<InternalUuid<InternalUser>, Box<dyn std::error::Error>> {
+            user.save(&db)
+        };
+    let mutual = |user: &InternalUser| -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+        Ok(db
+            .get_mutual_preference_users(user)?
+            .into_iter()
+            .map(|u| u.uuid.id)
+            .collect())
+    };
+
+    //montreal, up to 600km away
+    let montreal = user_at(45.5017, -73.5673, Some(600))?;
+    let toronto = save(user_at(43.6532, -79.3832, None)?)?;
+    //inside the lat/long box around montreal but about 790km away
+    let corner = save(user_at(50.5, -66.0, None)?)?;
+    let vancouver = save(user_at(49.2827, -123.1207, None)?)?;
+    //wants montreal to be within 400km, toronto is about 500km
+    let picky = save(user_at(43.7, -79.4, Some(400))?)?;
+
+    let found = mutual(&montreal)?;
+    assert!(found.contains(&toronto.id));
+    assert!(!found.contains(&corner.id));
+    assert!(!found.contains(&vancouver.id));
+    assert!(!found.contains(&picky.id));
+
+    //suva and a point about 200km east, on the other side of the antimeridian
+    let suva = user_at(-18.1248, 178.4501, Some(300))?;
+    let across = save(user_at(-18.0, -179.7, None)?)?;
+    let found = mutual(&suva)?;
+    assert!(found.contains(&across.id));
+    assert!(!found.contains(&toronto.id));
+    assert_eq!(db.get_users_i_prefer_count(&suva)?, 1);
+
+    DB::destroy_database_for_real_dangerous("test_geo_matching");
+    Ok(())
+}
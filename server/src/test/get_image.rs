@@ -0,0 +1,80 @@
+#[actix_web::test]
+async fn get_image_access_and_etag() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        middleware::jwt::make_jwt,
+        models::{
+            api_models::{api_image::ApiImageWritable, api_user::ApiUserWritable},
+            internal_models::{
+                internal_image::{Access, ImageSize},
+                internal_session::InternalSession,
+                internal_user::InternalUser,
+                shared::{InternalUuid, Save},
+            },
+        },
+        routes::get_image::get_image,
+        test::fake::Gen,
+    };
+    use actix_web::{http::StatusCode, test, web, App};
+
+    DB::destroy_database_for_real_dangerous("test_get_image");
+    let db = DB::new("test_get_image").unwrap();
+    db.migrate_all().unwrap();
+    let db = web::Data::new(db);
+
+    let alice: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let bob: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let (alice, bob) = (alice.save(&db)?, bob.save(&db)?);
+    let token = |uuid: &InternalUuid<InternalUser>| -> Result<String, Box<dyn std::error::Error>> {
+        let (session, _) = InternalSession::start(&db, uuid)?;
+        make_jwt(uuid, &session.uuid)
+    };
+    let (alice_token, bob_token) = (token(&alice)?, token(&bob)?);
+
+    //only alice is on the list
+    let image = ApiImageWritable::gen(&false)
+        .to_internal(&db, Access::UserList(vec![alice.clone()]))?
+        .save(&db)?;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(db.clone())
+            .service(web::resource("/image/{uuid}").route(web::get().to(get_image))),
+    )
+    .await;
+    let get = |token: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/image/{}", image.id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+    };
+
+    //not being on the list looks the same as the image not being there
+    let res = test::call_service(&app, get(&bob_token).to_request()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let res = test::call_service(&app, get(&alice_token).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let etag = format!("\"{}-{:?}\"", image.id, ImageSize::default());
+    assert_eq!(res.headers().get("ETag").unwrap().to_str()?, etag);
+
+    let res = test::call_service(
+        &app,
+        get(&alice_token)
+            .insert_header(("If-None-Match", etag.as_str()))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    //a tag that doesn't match gets the image again
+    let res = test::call_service(
+        &app,
+        get(&alice_token)
+            .insert_header(("If-None-Match", "\"stale\""))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    Ok(())
+}
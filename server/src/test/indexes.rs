@@ -0,0 +1,76 @@
+#[test]
+fn indexes_follow_writes() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        index::IndexConflict,
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_session::InternalSession,
+                internal_user::{InternalUser, USERS_BY_USERNAME},
+                shared::Save,
+            },
+        },
+        tasks::rebuild_indexes::rebuild_indexes,
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_indexes");
+    let db = DB::new("test_indexes").unwrap();
+    db.migrate_all().unwrap();
+
+    let username = |db: &DB, name: &str| USERS_BY_USERNAME.get::<InternalUser>(db, name);
+
+    let mut alice: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    alice.username = "alice".to_string();
+    let alice = alice.save(&db)?;
+    let mut bob: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    bob.username = "bob".to_string();
+    let bob = bob.save(&db)?;
+    assert_eq!(username(&db, "alice")?, Some(alice.clone()));
+
+    //a rename takes the old name with it
+    db.update(&alice, |user: &mut InternalUser| {
+        user.username = "alicia".to_string()
+    })?;
+    assert_eq!(username(&db, "alice")?, None);
+    assert_eq!(username(&db, "alicia")?, Some(alice.clone()));
+
+    //a taken name is turned down, and nothing of the write lands
+    let taken = db.update(&bob, |user: &mut InternalUser| {
+        user.username = "alicia".to_string()
+    });
+    assert!(taken.is_err_and(|e| e.is::<IndexConflict>()));
+    assert_eq!(bob.load(&db)?.unwrap().username, "bob");
+    assert_eq!(username(&db, "alicia")?, Some(alice.clone()));
+    assert_eq!(username(&db, "bob")?, Some(bob.clone()));
+
+    //keys nobody tracks, like ones written before indexes were declared, don't hold a name
+    db.write_index(USERS_BY_USERNAME.name, &"ghost".to_string(), &alice)?;
+    db.write_index(USERS_BY_USERNAME.name, &"stale".to_string(), &alice)?;
+    let mut carol: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    carol.username = "ghost".to_string();
+    let carol = carol.save(&db)?;
+    assert_eq!(username(&db, "ghost")?, Some(carol.clone()));
+
+    //and a rebuild drops them
+    let rebuilt = rebuild_indexes(&db)?;
+    assert_eq!(rebuilt.conflicts, 0);
+    assert_eq!(username(&db, "stale")?, None);
+    assert_eq!(username(&db, "alicia")?, Some(alice.clone()));
+    assert_eq!(username(&db, "ghost")?, Some(carol.clone()));
+
+    //multi valued indexes list every object under a value, deletes take theirs out
+    let (first, _) = InternalSession::start(&db, &bob)?;
+    let (second, _) = InternalSession::start(&db, &bob)?;
+    assert_eq!(db.get_sessions(&bob)?.len(), 2);
+    first.end(&db)?;
+    let sessions = db.get_sessions(&bob)?;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].uuid, second.uuid);
+
+    carol.delete(&db)?;
+    assert_eq!(username(&db, "ghost")?, None);
+
+    Ok(())
+}
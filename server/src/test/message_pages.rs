@@ -0,0 +1,72 @@
+#[test]
+fn message_pages_follow_the_cursor() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_chat::InternalChat,
+                internal_message::InternalMessage,
+                internal_user::InternalUser,
+                shared::{InternalUuid, Save},
+            },
+        },
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_message_pages");
+    let db = DB::new("test_message_pages").unwrap();
+    db.migrate_all().unwrap();
+
+    let alice: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let bob: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let mut chat = InternalChat::new(vec![alice.uuid.clone(), bob.uuid.clone()]);
+    let author = alice.uuid.clone();
+    alice.save(&db)?;
+    bob.save(&db)?;
+
+    let mut sent: Vec<InternalUuid<InternalMessage>> = vec![];
+    for n in 0..5 {
+        let message = InternalMessage {
+            uuid: InternalUuid::new(),
+            sent_at: 1000 + n,
+            edited: false,
+            author: author.clone(),
+            content: n.to_string(),
+            image: None,
+            read_by: vec![],
+            chat: chat.uuid.clone(),
+        };
+        sent.push(message.save(&mut chat, &db)?);
+    }
+    let chat_uuid = chat.uuid.clone();
+    assert_eq!(InternalMessage::in_chat(&db, &chat_uuid)?, sent);
+
+    let contents = |messages: &[InternalMessage]| -> Vec<String> {
+        messages.iter().map(|m| m.content.clone()).collect()
+    };
+    let page = InternalMessage::page(&db, &chat_uuid, None, 2)?;
+    assert_eq!(contents(&page.messages), vec!["4", "3"]);
+    let page = InternalMessage::page(&db, &chat_uuid, page.next_cursor.as_deref(), 2)?;
+    assert_eq!(contents(&page.messages), vec!["2", "1"]);
+    let page = InternalMessage::page(&db, &chat_uuid, page.next_cursor.as_deref(), 2)?;
+    assert_eq!(contents(&page.messages), vec!["0"]);
+    assert_eq!(page.next_cursor, None);
+
+    //an edit keeps its place, a deleted message leaves no gap
+    let mut edited = sent[1].load(&db)?.unwrap();
+    edited.content = "edited".to_string();
+    edited.edited = true;
+    edited.save(&mut chat, &db)?;
+    sent[3].load(&db)?.unwrap().delete(&db)?;
+    let page = InternalMessage::page(&db, &chat_uuid, None, 10)?;
+    assert_eq!(contents(&page.messages), vec!["4", "2", "edited", "0"]);
+    assert_eq!(page.next_cursor, None);
+
+    //saving messages doesn't touch the chat beyond its summary
+    let chat = chat_uuid.load(&db)?.unwrap();
+    assert_eq!(chat.most_recent_message, "4");
+
+    DB::destroy_database_for_real_dangerous("test_message_pages");
+    Ok(())
+}
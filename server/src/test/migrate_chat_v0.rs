@@ -0,0 +1,58 @@
+#[test]
+fn migrate_chat_v0_indexes_messages() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::internal_models::{
+            internal_chat::InternalChat,
+            internal_message::InternalMessage,
+            migration::internal_chat::internal_chat_v0::InternalChatV0,
+            shared::{Insertable, InternalUuid},
+        },
+    };
+
+    DB::destroy_database_for_real_dangerous("test_migrate_chat_v0");
+    let db = DB::new("test_migrate_chat_v0").unwrap();
+    db.set_version::<InternalMessage>(1)?;
+
+    let uuid: InternalUuid<InternalChat> = InternalUuid::new();
+    let author = InternalUuid::new();
+    let messages = (0..3)
+        .map(|n| InternalMessage {
+            uuid: InternalUuid::new(),
+            sent_at: 1000 + n,
+            edited: false,
+            author: author.clone(),
+            content: n.to_string(),
+            image: None,
+            read_by: vec![],
+            chat: uuid.clone(),
+        })
+        .collect::<Vec<_>>();
+    for message in messages.iter() {
+        message.uuid.write(message, &db)?;
+    }
+    let old = InternalChatV0 {
+        uuid: uuid.clone(),
+        users: vec![author],
+        unread: vec![0],
+        messages: messages.iter().map(|m| m.uuid.clone()).collect(),
+        most_recent_message: "2".to_string(),
+        most_recent_sender: None,
+        most_recent_message_sent_at: 1002,
+    };
+    let old_uuid: InternalUuid<InternalChatV0> = uuid.id.clone().into();
+    old_uuid.write(&old, &db)?;
+
+    db.migrate_all()?;
+    assert_eq!(db.get_version::<InternalChat>()?, InternalChat::version());
+
+    let chat = uuid.load(&db)?.unwrap();
+    assert_eq!(chat.most_recent_message, "2");
+    assert_eq!(
+        InternalMessage::in_chat(&db, &uuid)?,
+        messages.iter().map(|m| m.uuid.clone()).collect::<Vec<_>>()
+    );
+
+    DB::destroy_database_for_real_dangerous("test_migrate_chat_v0");
+    Ok(())
+}
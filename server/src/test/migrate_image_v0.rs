@@ -0,0 +1,43 @@
+#[test]
+fn migrate_image_v0_makes_renditions() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::internal_models::{
+            internal_image::{Access, ImageSize, InternalImage},
+            migration::internal_image::internal_image_v0::InternalImageV0,
+            shared::{Insertable, InternalUuid},
+        },
+        renditions::THUMBNAIL_SIZE,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_migrate_image_v0");
+    let db = DB::new("test_migrate_image_v0").unwrap();
+
+    const TEST_IMG_BYTES: &[u8] = include_bytes!("../models/api_models/test.jpeg");
+    let uuid: InternalUuid<InternalImage> = InternalUuid::new();
+    let broken_uuid: InternalUuid<InternalImage> = InternalUuid::new();
+    for (uuid, content) in [(&uuid, TEST_IMG_BYTES), (&broken_uuid, b"not an image")] {
+        let old = InternalImageV0 {
+            uuid: uuid.clone(),
+            content: content.to_vec(),
+            access: Access::Everyone,
+        };
+        let old_uuid: InternalUuid<InternalImageV0> = uuid.id.clone().into();
+        old_uuid.write(&old, &db)?;
+    }
+
+    db.migrate_all()?;
+    assert_eq!(db.get_version::<InternalImage>()?, InternalImage::version());
+
+    let image = uuid.load(&db)?.unwrap();
+    let thumbnail = image::load_from_memory(&image.rendition(&db, ImageSize::Thumbnail)?)?;
+    assert!(thumbnail.width() <= THUMBNAIL_SIZE && thumbnail.height() <= THUMBNAIL_SIZE);
+    image::load_from_memory(&image.rendition(&db, ImageSize::Full)?)?;
+
+    //what couldn't be decoded is kept as it was
+    let broken = broken_uuid.load(&db)?.unwrap();
+    assert_eq!(broken.rendition(&db, ImageSize::Card)?, b"not an image");
+
+    DB::destroy_database_for_real_dangerous("test_migrate_image_v0");
+    Ok(())
+}
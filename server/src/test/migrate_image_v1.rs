@@ -0,0 +1,41 @@
+#[test]
+fn migrate_image_v1_moves_renditions_to_blobs() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::internal_models::{
+            internal_image::{Access, ImageSize, InternalImage},
+            migration::internal_image::internal_image_v1::InternalImageV1,
+            shared::{Insertable, InternalUuid},
+        },
+        renditions::Renditions,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_migrate_image_v1");
+    let db = DB::new("test_migrate_image_v1").unwrap();
+    db.set_version::<InternalImage>(1)?;
+
+    const TEST_IMG_BYTES: &[u8] = include_bytes!("../models/api_models/test.jpeg");
+    let renditions = Renditions::from_upload(TEST_IMG_BYTES).map_err(|e| e.message())?;
+    let uuid: InternalUuid<InternalImage> = InternalUuid::new();
+    let copy_uuid: InternalUuid<InternalImage> = InternalUuid::new();
+    for uuid in [&uuid, &copy_uuid] {
+        let old = InternalImageV1 {
+            uuid: uuid.clone(),
+            renditions: renditions.clone(),
+            access: Access::Everyone,
+        };
+        let old_uuid: InternalUuid<InternalImageV1> = uuid.id.clone().into();
+        old_uuid.write(&old, &db)?;
+    }
+
+    db.migrate_all()?;
+    assert_eq!(db.get_version::<InternalImage>()?, InternalImage::version());
+
+    let image = uuid.load(&db)?.unwrap();
+    let copy = copy_uuid.load(&db)?.unwrap();
+    assert_eq!(image.blob, copy.blob);
+    assert_eq!(image.rendition(&db, ImageSize::Card)?, renditions.card);
+
+    DB::destroy_database_for_real_dangerous("test_migrate_image_v1");
+    Ok(())
+}
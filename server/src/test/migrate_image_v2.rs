@@ -0,0 +1,44 @@
+#[test]
+fn migrate_image_v2_hashes_images() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::internal_models::{
+            internal_image::{Access, InternalImage},
+            migration::internal_image::internal_image_v2::InternalImageV2,
+            shared::{Insertable, InternalUuid},
+        },
+        renditions::Renditions,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_migrate_image_v2");
+    let db = DB::new("test_migrate_image_v2").unwrap();
+    for version in 1..=2 {
+        db.set_version::<InternalImage>(version)?;
+    }
+
+    const TEST_IMG_BYTES: &[u8] = include_bytes!("../models/api_models/test.jpeg");
+    let renditions = Renditions::from_upload(TEST_IMG_BYTES).map_err(|e| e.message())?;
+    let uuid: InternalUuid<InternalImage> = InternalUuid::new();
+    let old = InternalImageV2 {
+        uuid: uuid.clone(),
+        blob: db.put_blob(&renditions)?,
+        access: Access::Everyone,
+        refs: 2,
+        created_at: 0,
+    };
+    let old_uuid: InternalUuid<InternalImageV2> = uuid.id.clone().into();
+    old_uuid.write(&old, &db)?;
+
+    db.migrate_all()?;
+    assert_eq!(db.get_version::<InternalImage>()?, InternalImage::version());
+
+    let image = uuid.load(&db)?.unwrap();
+    assert_eq!(image.refs, 2);
+    assert_eq!(image.dhash, renditions.dhash());
+    //and it's in the index
+    let found = InternalImage::near_duplicates(&db, image.dhash.unwrap())?;
+    assert!(found.iter().any(|found| found.uuid == uuid));
+
+    DB::destroy_database_for_real_dangerous("test_migrate_image_v2");
+    Ok(())
+}
@@ -0,0 +1,49 @@
+#[test]
+fn migrate_message_v0_dates_read_receipts() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::internal_models::{
+            internal_message::{InternalMessage, ReadReceipt},
+            internal_user::InternalUser,
+            migration::internal_message::internal_message_v0::InternalMessageV0,
+            shared::{Insertable, InternalUuid},
+        },
+    };
+
+    DB::destroy_database_for_real_dangerous("test_migrate_message_v0");
+    let db = DB::new("test_migrate_message_v0").unwrap();
+
+    let uuid: InternalUuid<InternalMessage> = InternalUuid::new();
+    let reader: InternalUuid<InternalUser> = InternalUuid::new();
+    let old = InternalMessageV0 {
+        uuid: uuid.clone(),
+        sent_at: 1000,
+        edited: false,
+        author: reader.clone(),
+        content: "v0".to_string(),
+        image: None,
+        read_by: vec![reader.clone()],
+        chat: InternalUuid::new(),
+    };
+    let old_uuid: InternalUuid<InternalMessageV0> = uuid.id.clone().into();
+    old_uuid.write(&old, &db)?;
+
+    db.migrate_all()?;
+    assert_eq!(
+        db.get_version::<InternalMessage>()?,
+        InternalMessage::version()
+    );
+
+    let message = uuid.load(&db)?.unwrap();
+    assert_eq!(message.content, "v0");
+    assert_eq!(
+        message.read_by,
+        vec![ReadReceipt {
+            user: reader,
+            read_at: 1000,
+        }]
+    );
+
+    DB::destroy_database_for_real_dangerous("test_migrate_message_v0");
+    Ok(())
+}
@@ -0,0 +1,62 @@
+#[test]
+fn migrate_user_v1_makes_prefs_dealbreakers() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::internal_models::{
+            internal_prefs::{LabeledProperty, PreferenceRange, Strictness, DEFAULT_PREF_WEIGHT},
+            internal_user::InternalUser,
+            migration::internal_user::internal_user_v1::{
+                InternalUserV1, LabeledPreferenceRangeV0,
+            },
+            shared::{Insertable, InternalUuid},
+        },
+    };
+
+    DB::destroy_database_for_real_dangerous("test_migrate_user_v1");
+    let db = DB::new("test_migrate_user_v1").unwrap();
+    db.set_version::<InternalUser>(1)?;
+
+    let uuid: InternalUuid<InternalUser> = InternalUuid::new();
+    let old = InternalUserV1 {
+        uuid: uuid.clone(),
+        hashed_password: "".to_string(),
+        elo: 0.0,
+        ratings: vec![],
+        seen: vec![],
+        chats: vec![],
+        images: vec![],
+        preview_image: None,
+        username: "v1".to_string(),
+        display_name: "v1".to_string(),
+        description: "".to_string(),
+        birthdate: 0,
+        prefs: vec![LabeledPreferenceRangeV0 {
+            name: "age".to_string(),
+            range: PreferenceRange { min: 20, max: 30 },
+        }],
+        props: vec![LabeledProperty {
+            name: "age".to_string(),
+            value: 25,
+        }],
+        owned_images: vec![],
+        actions: vec![],
+        notifications: vec![],
+        published: false,
+        bot_props: None,
+    };
+    let old_uuid: InternalUuid<InternalUserV1> = uuid.id.clone().into();
+    old_uuid.write(&old, &db)?;
+
+    db.migrate_all()?;
+    assert_eq!(db.get_version::<InternalUser>()?, InternalUser::version());
+
+    let user = uuid.load(&db)?.unwrap();
+    assert_eq!(user.username, "v1");
+    assert_eq!(user.prefs[0].range.min, 20);
+    assert_eq!(user.prefs[0].range.max, 30);
+    assert_eq!(user.prefs[0].strictness, Strictness::Dealbreaker);
+    assert_eq!(user.prefs[0].weight, DEFAULT_PREF_WEIGHT);
+
+    DB::destroy_database_for_real_dangerous("test_migrate_user_v1");
+    Ok(())
+}
@@ -0,0 +1,66 @@
+#[test]
+fn migrate_user_v3_gives_the_admin_its_role() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::internal_models::{
+            internal_user::{InternalUser, Role},
+            migration::{
+                internal_user::internal_user_v3::InternalUserV3, migration::get_admin_uuid,
+            },
+            shared::{Insertable, InternalUuid},
+        },
+    };
+
+    DB::destroy_database_for_real_dangerous("test_migrate_user_v3");
+    let db = DB::new("test_migrate_user_v3").unwrap();
+    for version in 1..=3 {
+        db.set_version::<InternalUser>(version)?;
+    }
+
+    let old = |uuid: &InternalUuid<InternalUser>, username: &str| InternalUserV3 {
+        uuid: uuid.clone(),
+        hashed_password: "".to_string(),
+        elo: 0.0,
+        ratings: vec![],
+        seen: vec![],
+        chats: vec![],
+        images: vec![],
+        preview_image: None,
+        username: username.to_string(),
+        display_name: username.to_string(),
+        description: "".to_string(),
+        birthdate: 0,
+        prefs: vec![],
+        props: vec![],
+        owned_images: vec![],
+        actions: vec![],
+        notifications: vec![],
+        published: false,
+        bot_props: None,
+    };
+    let admin_uuid = get_admin_uuid();
+    let user_uuid: InternalUuid<InternalUser> = InternalUuid::new();
+    for (uuid, username) in [(&admin_uuid, "admin"), (&user_uuid, "v3")] {
+        let old_uuid: InternalUuid<InternalUserV3> = uuid.id.clone().into();
+        old_uuid.write(&old(uuid, username), &db)?;
+    }
+
+    db.migrate_all()?;
+    assert_eq!(db.get_version::<InternalUser>()?, InternalUser::version());
+
+    let admin = admin_uuid.load(&db)?.unwrap();
+    assert_eq!(admin.role, Some(Role::Admin));
+    assert!(admin.is_admin() && admin.has_role(Role::Support));
+    let mut user = user_uuid.load(&db)?.unwrap();
+    assert_eq!(user.username, "v3");
+    assert_eq!(user.role, None);
+    assert!(!user.has_role(Role::Support));
+
+    //each role can do what the ones below it can, and no more
+    user.role = Some(Role::Moderator);
+    assert!(user.has_role(Role::Support) && user.has_role(Role::Moderator));
+    assert!(!user.is_admin());
+
+    DB::destroy_database_for_real_dangerous("test_migrate_user_v3");
+    Ok(())
+}
@@ -1,2 +1,27 @@
+pub mod admin;
+pub mod backfill_glicko;
+pub mod block;
+pub mod collect_images;
+pub mod delete_user;
 pub mod dummy_data;
 pub mod fake;
+pub mod geo_matching;
+pub mod get_image;
+pub mod indexes;
+pub mod message_pages;
+pub mod migrate_chat_v0;
+pub mod migrate_image_v0;
+pub mod migrate_image_v1;
+pub mod migrate_image_v2;
+pub mod migrate_message_v0;
+pub mod migrate_user_v1;
+pub mod migrate_user_v3;
+pub mod moderation;
+pub mod password_reset;
+pub mod photo_clusters;
+pub mod read_receipts;
+pub mod remap_prefs;
+pub mod sessions;
+pub mod soft_prefs;
+pub mod transaction;
+pub mod unknown_props;
@@ -0,0 +1,142 @@
+#[test]
+fn moderation_queue() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_prefs::PreferenceRange,
+                internal_report::{
+                    Evidence, InternalReport, ModAction, ReportError, ReportKind, ReportStatus,
+                },
+                internal_session::InternalSession,
+                internal_user::{InternalUser, Notification},
+                shared::{InternalUuid, Save},
+            },
+        },
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_moderation");
+    let db = DB::new("test_moderation").unwrap();
+    db.migrate_all().unwrap();
+
+    let mut mallory: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    mallory.published = true;
+    //open to everyone, so searching as mallory finds mallory
+    for pref in mallory.prefs.iter_mut() {
+        pref.range = PreferenceRange {
+            min: i16::MIN,
+            max: i16::MAX,
+        };
+        pref.strictness = Default::default();
+    }
+    let alice: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let admin = db.get_admin()?;
+    let report = |target: Option<&InternalUser>, created_at: i64| -> InternalReport {
+        InternalReport {
+            uuid: InternalUuid::new(),
+            kind: ReportKind::Violation,
+            status: ReportStatus::Open,
+            created_at,
+            reporter: Some(alice.uuid.clone()),
+            target_user: target.map(|user| user.uuid.clone()),
+            target_chat: None,
+            content: "fake profile".to_string(),
+            platform: "test".to_string(),
+            evidence: Evidence::snapshot(&db, vec![1, 2, 3], target, None).unwrap(),
+            assignee: None,
+            notes: vec![],
+            resolution: None,
+        }
+    };
+    let now = chrono::Utc::now().timestamp();
+    let first = report(Some(&mallory), now);
+    let second = report(Some(&mallory), now + 1);
+    let bug = report(None, now + 2);
+    let (first_uuid, second_uuid, bug_uuid) =
+        (first.uuid.clone(), second.uuid.clone(), bug.uuid.clone());
+    assert_eq!(
+        first.evidence.display_name.as_ref(),
+        Some(&mallory.display_name)
+    );
+    first.save(&db)?;
+    second.save(&db)?;
+    bug.save(&db)?;
+    let mallory_uuid = mallory.uuid.clone();
+    let (props, prefs) = (mallory.props.clone(), mallory.prefs.clone());
+    mallory.save(&db)?;
+    let searchable = || -> bool {
+        db.get_mutual_preference_users_direct(&props, &prefs, &vec![])
+            .unwrap()
+            .iter()
+            .any(|user| user.uuid == mallory_uuid)
+    };
+    assert!(searchable());
+    let (session, _) = InternalSession::start(&db, &mallory_uuid)?;
+
+    let listed = |status: ReportStatus| -> Vec<InternalUuid<InternalReport>> {
+        InternalReport::list(&db, status, 10)
+            .unwrap()
+            .into_iter()
+            .map(|report| report.uuid)
+            .collect()
+    };
+    assert_eq!(
+        listed(ReportStatus::Open),
+        vec![first_uuid.clone(), second_uuid.clone(), bug_uuid.clone()]
+    );
+
+    //assigning moves it out of the open queue
+    let assigned = InternalReport::assign(&db, &first_uuid, &admin.uuid)?.unwrap();
+    assert_eq!(assigned.assignee, Some(admin.uuid.clone()));
+    assert_eq!(
+        listed(ReportStatus::Open),
+        vec![second_uuid.clone(), bug_uuid.clone()]
+    );
+    assert_eq!(listed(ReportStatus::Assigned), vec![first_uuid.clone()]);
+    InternalReport::add_note(
+        &db,
+        &first_uuid,
+        &admin.uuid,
+        "same photos as a banned user",
+    )?
+    .unwrap();
+
+    //a warning lands in the target's notifications
+    InternalReport::resolve(&db, &second_uuid, &admin.uuid, ModAction::Warn)?.unwrap();
+    let mallory = mallory_uuid.load(&db)?.unwrap();
+    assert!(mallory
+        .notifications
+        .iter()
+        .any(|n| matches!(n, Notification::System(_))));
+    assert!(!db.is_banned(&mallory_uuid)?);
+    assert!(searchable());
+
+    //a ban unpublishes and logs them out
+    let resolved = InternalReport::resolve(&db, &first_uuid, &admin.uuid, ModAction::Ban)?.unwrap();
+    assert_eq!(resolved.status, ReportStatus::Resolved);
+    assert_eq!(resolved.notes.len(), 1);
+    assert!(db.is_banned(&mallory_uuid)?);
+    assert!(!mallory_uuid.load(&db)?.unwrap().published);
+    assert!(!searchable());
+    assert!(!InternalSession::is_active(&db, &session.uuid)?);
+    assert_eq!(
+        listed(ReportStatus::Resolved),
+        vec![first_uuid.clone(), second_uuid.clone()]
+    );
+
+    //resolved reports stay resolved, and a bug has nobody to act on
+    assert_eq!(
+        InternalReport::resolve(&db, &first_uuid, &admin.uuid, ModAction::Dismiss)?.unwrap_err(),
+        ReportError::AlreadyResolved
+    );
+    assert_eq!(
+        InternalReport::resolve(&db, &bug_uuid, &admin.uuid, ModAction::Ban)?.unwrap_err(),
+        ReportError::NoTarget
+    );
+    InternalReport::resolve(&db, &bug_uuid, &admin.uuid, ModAction::Dismiss)?.unwrap();
+    assert!(listed(ReportStatus::Open).is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+#[test]
+fn reset_codes_are_single_use() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        mailer::{FileMailer, Mail, Mailer},
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_reset_code::{InternalResetCode, ResetError},
+                internal_session::InternalSession,
+                internal_user::InternalUser,
+                shared::Save,
+            },
+        },
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_password_reset");
+    let db = DB::new("test_password_reset").unwrap();
+    db.migrate_all().unwrap();
+
+    let user: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let old_hash = user.hashed_password.clone();
+    let uuid = user.save(&db)?;
+    let (phone, _) = InternalSession::start(&db, &uuid)?;
+    let (laptop, _) = InternalSession::start(&db, &uuid)?;
+
+    //changing the password keeps the device it was changed from
+    assert_eq!(db.end_other_sessions(&uuid, &phone.uuid)?, 1);
+    assert!(InternalSession::is_active(&db, &phone.uuid)?);
+    assert!(!InternalSession::is_active(&db, &laptop.uuid)?);
+
+    //a second code replaces the first
+    let (_, first) = InternalResetCode::issue(&db, &uuid)?;
+    let (_, second) = InternalResetCode::issue(&db, &uuid)?;
+    assert_eq!(
+        InternalResetCode::redeem(&db, &first, "new hash")?,
+        Err(ResetError::Invalid)
+    );
+
+    //typed in by hand, so case and spaces don't matter
+    let typed = format!(" {} ", second.to_lowercase());
+    assert_eq!(
+        InternalResetCode::redeem(&db, &typed, "new hash")?,
+        Ok(uuid.clone())
+    );
+    let user = uuid.load(&db)?.unwrap();
+    assert_ne!(user.hashed_password, old_hash);
+    assert_eq!(user.hashed_password, "new hash");
+    assert_eq!(
+        InternalResetCode::redeem(&db, &second, "another hash")?,
+        Err(ResetError::Invalid)
+    );
+
+    //deleting the user takes their code with it
+    InternalResetCode::issue(&db, &uuid)?;
+    assert!(db.delete_reset_code(&uuid)?);
+    assert!(!db.delete_reset_code(&uuid)?);
+    assert_eq!(db.delete_expired_reset_codes()?, 0);
+
+    let dir = std::env::temp_dir().join(format!("outbox_{}", uuid::Uuid::new_v4()));
+    let mailer = FileMailer::new(&dir)?;
+    mailer.send(&Mail {
+        to: "someone@example.com".to_string(),
+        subject: "Your password reset code".to_string(),
+        body: second.clone(),
+    })?;
+    let sent = std::fs::read_dir(&dir)?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(sent.len(), 1);
+    let contents = std::fs::read_to_string(sent[0].path())?;
+    assert!(contents.starts_with("To: someone@example.com\n"));
+    assert!(contents.contains(&second));
+    std::fs::remove_dir_all(dir)?;
+
+    DB::destroy_database_for_real_dangerous("test_password_reset");
+    Ok(())
+}
@@ -0,0 +1,80 @@
+#[test]
+fn photo_clusters_and_banned_photos() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::{api_image::ApiImageWritable, api_user::ApiUserWritable},
+            internal_models::{
+                internal_image::{photo_clusters, Access, InternalImage},
+                internal_report::{InternalReport, ReportStatus},
+                internal_user::InternalUser,
+                shared::{InternalUuid, Save},
+            },
+        },
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_photo_clusters");
+    let db = DB::new("test_photo_clusters").unwrap();
+    db.migrate_all().unwrap();
+
+    //a smooth made up photo, `brighten` stands in for a filter slapped on a stolen one
+    let photo = |period: f32, brighten: u8| -> Vec<u8> {
+        let img = image::RgbImage::from_fn(300, 200, |x, y| {
+            let v = 120.0 + 100.0 * (x as f32 / period).sin() * (y as f32 / 30.0).cos();
+            let v = (v as u8).saturating_add(brighten);
+            image::Rgb([v, v / 2, 255 - v])
+        });
+        let mut png = vec![];
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    };
+    let upload = |png: Vec<u8>| -> Result<InternalImage, Box<dyn std::error::Error>> {
+        ApiImageWritable::new(png).to_internal(&db, Access::Everyone)
+    };
+    //the generated profiles all show the same test jpeg, so each gets just one photo here
+    let user_showing = |image: &InternalUuid<InternalImage>| {
+        let mut user: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+        user.images = vec![image.clone()];
+        user.preview_image = None;
+        user.owned_images = vec![image.clone()];
+        user.save(&db)
+    };
+
+    let original = upload(photo(40.0, 0))?.save(&db)?;
+    let stolen = upload(photo(40.0, 6))?.save(&db)?;
+    let unrelated = upload(photo(13.0, 0))?.save(&db)?;
+    let alice = user_showing(&original)?;
+    let bob = user_showing(&stolen)?;
+    let carol = user_showing(&unrelated)?;
+
+    let users = [&alice, &bob, &carol]
+        .iter()
+        .map(|uuid| Ok(uuid.load(&db)?.unwrap()))
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+    let clusters = photo_clusters(&db, &users)?;
+    assert_eq!(clusters.len(), 1);
+    assert_eq!(clusters[0].users.len(), 2);
+    assert!(clusters[0].users.contains(&alice) && clusters[0].users.contains(&bob));
+    assert!(!clusters[0].users.contains(&carol));
+    let carol = carol.load(&db)?.unwrap();
+
+    //nobody's banned yet
+    let again = upload(photo(40.0, 3))?;
+    assert!(InternalReport::flag_banned_photo(&db, &carol, &again)?.is_none());
+
+    db.ban_user(&alice)?;
+    let report = InternalReport::flag_banned_photo(&db, &carol, &again)?.unwrap();
+    assert_eq!(report.target_user, Some(carol.uuid.clone()));
+    assert!(report.content.contains(&alice.id));
+    let open = InternalReport::list(&db, ReportStatus::Open, 10)?;
+    assert!(open.iter().any(|open| open.uuid == report.uuid));
+    //a photo unlike any of theirs goes through unflagged
+    let different = upload(photo(13.0, 4))?;
+    assert!(InternalReport::flag_banned_photo(&db, &carol, &different)?.is_none());
+
+    DB::destroy_database_for_real_dangerous("test_photo_clusters");
+    Ok(())
+}
@@ -0,0 +1,108 @@
+#[test]
+fn read_receipts_move_forward() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_chat::{InternalChat, ReadError},
+                internal_message::{InternalMessage, ReadReceipt},
+                internal_user::{InternalUser, Notification},
+                shared::{InternalUuid, Save},
+            },
+        },
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_read_receipts");
+    let db = DB::new("test_read_receipts").unwrap();
+    db.migrate_all().unwrap();
+
+    let mut alice: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let mut bob: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let carol: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let mut chat = InternalChat::new(vec![alice.uuid.clone(), bob.uuid.clone()]);
+    alice.add_chat(&chat);
+    bob.add_chat(&chat);
+    let (alice_uuid, bob_uuid, carol_uuid) =
+        (alice.uuid.clone(), bob.uuid.clone(), carol.uuid.clone());
+    alice.save(&db)?;
+    bob.save(&db)?;
+    carol.save(&db)?;
+
+    //alice sends three messages
+    let mut sent: Vec<InternalUuid<InternalMessage>> = vec![];
+    let now = chrono::Utc::now().timestamp();
+    for (n, content) in ["one", "two", "three"].into_iter().enumerate() {
+        let message = InternalMessage {
+            uuid: InternalUuid::new(),
+            sent_at: now + n as i64,
+            edited: false,
+            author: alice_uuid.clone(),
+            content: content.to_string(),
+            image: None,
+            read_by: vec![ReadReceipt {
+                user: alice_uuid.clone(),
+                read_at: now,
+            }],
+            chat: chat.uuid.clone(),
+        };
+        sent.push(message.save(&mut chat, &db)?);
+    }
+    let chat_uuid = chat.uuid.clone();
+    let (_, mut alice_events) = db.push.connect(&alice_uuid);
+
+    //bob has read the first two
+    let unread = InternalChat::mark_read(&db, &chat_uuid, &bob_uuid, &sent[1])?;
+    assert_eq!(unread, Ok(1));
+    let read = |uuid: &InternalUuid<InternalMessage>| -> Vec<InternalUuid<InternalUser>> {
+        let message = uuid.load(&db).unwrap().unwrap();
+        message.read_by.into_iter().map(|r| r.user).collect()
+    };
+    assert_eq!(read(&sent[0]), vec![alice_uuid.clone(), bob_uuid.clone()]);
+    assert_eq!(read(&sent[1]), vec![alice_uuid.clone(), bob_uuid.clone()]);
+    assert_eq!(read(&sent[2]), vec![alice_uuid.clone()]);
+    let bob = bob_uuid.load(&db)?.unwrap();
+    let unread_notifications = bob
+        .notifications
+        .iter()
+        .filter_map(|n| match n {
+            Notification::UnreadMessage(message) => Some(message.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(unread_notifications, vec![sent[2].clone()]);
+    assert!(alice_events.try_recv()?.contains(r#""type":"Read""#));
+
+    //going back doesn't add receipts or unread messages
+    let first_read_at = sent[0].load(&db)?.unwrap().read_by[1].read_at;
+    assert_eq!(
+        InternalChat::mark_read(&db, &chat_uuid, &bob_uuid, &sent[0])?,
+        Ok(1)
+    );
+    assert!(alice_events.try_recv().is_err());
+    assert_eq!(
+        InternalChat::mark_read(&db, &chat_uuid, &bob_uuid, &sent[2])?,
+        Ok(0)
+    );
+    assert_eq!(read(&sent[0]).len(), 2);
+    assert_eq!(
+        sent[0].load(&db)?.unwrap().read_by[1].read_at,
+        first_read_at
+    );
+    assert_eq!(read(&sent[2]), vec![alice_uuid.clone(), bob_uuid.clone()]);
+    assert_eq!(chat_uuid.load(&db)?.unwrap().unread, vec![0, 0]);
+
+    //only members can mark a chat read, and only up to its own messages
+    assert_eq!(
+        InternalChat::mark_read(&db, &chat_uuid, &carol_uuid, &sent[2])?,
+        Err(ReadError::NotInChat)
+    );
+    assert_eq!(
+        InternalChat::mark_read(&db, &chat_uuid, &bob_uuid, &InternalUuid::new())?,
+        Err(ReadError::MessageNotInChat)
+    );
+
+    DB::destroy_database_for_real_dangerous("test_read_receipts");
+    Ok(())
+}
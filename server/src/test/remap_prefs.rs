@@ -0,0 +1,49 @@
+#[test]
+fn remap_prefs_matches_stored_vectors_by_name() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_prefs_config::prefs_schema, internal_user::InternalUser, shared::Save,
+            },
+        },
+        tasks::remap_prefs::{remap_prefs, remap_prefs_if_needed},
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_remap_prefs");
+    let db = DB::new("test_remap_prefs").unwrap();
+    db.migrate_all().unwrap();
+    remap_prefs_if_needed(&db)?;
+
+    //stored by a schema that had the questions in reverse and without the first one
+    let mut user: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+    let props = user.props.clone();
+    let prefs = user.prefs.clone();
+    user.props.reverse();
+    user.props.pop();
+    user.prefs.reverse();
+    user.prefs.pop();
+    let uuid = user.save(&db)?;
+
+    assert_eq!(remap_prefs(&db, prefs_schema())?, 1);
+    let user = uuid.load(&db)?.unwrap();
+    assert!(prefs_schema().is_laid_out(&user.props, &user.prefs));
+    for (i, (prop, pref)) in user.props.iter().zip(user.prefs.iter()).enumerate().skip(1) {
+        assert_eq!(prop.value, props[i].value);
+        assert_eq!(pref.range.min, prefs[i].range.min);
+        assert_eq!(pref.range.max, prefs[i].range.max);
+    }
+    //the question the user never answered gets its default
+    let first = &prefs_schema().preferences[0];
+    assert_eq!(user.props[0].value, first.default.unwrap_or(-32768));
+    assert_eq!(user.prefs[0].range.min, -32768);
+
+    //everything is laid out now, so nothing is rewritten again
+    assert_eq!(remap_prefs(&db, prefs_schema())?, 0);
+    remap_prefs_if_needed(&db)?;
+
+    DB::destroy_database_for_real_dangerous("test_remap_prefs");
+    Ok(())
+}
@@ -0,0 +1,52 @@
+#[test]
+fn refresh_tokens_rotate_and_revoke() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::internal_models::{
+            internal_session::{InternalSession, RefreshError},
+            internal_user::InternalUser,
+            shared::InternalUuid,
+        },
+    };
+
+    DB::destroy_database_for_real_dangerous("test_sessions");
+    let db = DB::new("test_sessions").unwrap();
+    db.migrate_all().unwrap();
+
+    let user: InternalUuid<InternalUser> = InternalUuid::new();
+    let (phone, first_token) = InternalSession::start(&db, &user)?;
+    let (laptop, _) = InternalSession::start(&db, &user)?;
+    assert_eq!(db.get_sessions(&user)?.len(), 2);
+
+    //each refresh token works once and hands out the next
+    let (refreshed, second_token) = InternalSession::refresh(&db, &first_token)?.unwrap();
+    assert_eq!(refreshed.uuid, phone.uuid);
+    assert_ne!(second_token, first_token);
+    let (_, third_token) = InternalSession::refresh(&db, &second_token)?.unwrap();
+
+    //an old token coming back revokes the session, the newest one with it
+    assert_eq!(
+        InternalSession::refresh(&db, &first_token)?.unwrap_err(),
+        RefreshError::Reused
+    );
+    assert!(!InternalSession::is_active(&db, &phone.uuid)?);
+    assert_eq!(
+        InternalSession::refresh(&db, &third_token)?.unwrap_err(),
+        RefreshError::Invalid
+    );
+    assert_eq!(
+        InternalSession::refresh(&db, "garbage")?.unwrap_err(),
+        RefreshError::Invalid
+    );
+
+    //logging out everywhere takes the other devices too
+    assert!(InternalSession::is_active(&db, &laptop.uuid)?);
+    let (tablet, _) = InternalSession::start(&db, &user)?;
+    assert_eq!(db.end_all_sessions(&user)?, 2);
+    assert!(!InternalSession::is_active(&db, &laptop.uuid)?);
+    assert!(!InternalSession::is_active(&db, &tablet.uuid)?);
+    assert!(db.get_sessions(&user)?.is_empty());
+
+    DB::destroy_database_for_real_dangerous("test_sessions");
+    Ok(())
+}
@@ -0,0 +1,74 @@
+#[test]
+fn soft_prefs_rank_instead_of_filter() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_prefs::{
+                    rank_by_soft_prefs, soft_match_score, LabeledPreferenceRange, PreferenceRange,
+                    Strictness,
+                },
+                internal_user::InternalUser,
+                shared::GetBbox,
+            },
+        },
+        test::fake::Gen,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_soft_prefs");
+    let db = DB::new("test_soft_prefs").unwrap();
+    db.migrate_all().unwrap();
+
+    let user_aged = |age: i16| -> Result<InternalUser, Box<dyn std::error::Error>> {
+        let mut user: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+        for pref in user.prefs.iter_mut() {
+            pref.range = PreferenceRange {
+                min: i16::MIN,
+                max: i16::MAX,
+            };
+        }
+        user.props
+            .iter_mut()
+            .find(|p| p.name == "age")
+            .unwrap()
+            .value = age;
+        Ok(user)
+    };
+
+    //wants someone 25 to 30, but older isn't a dealbreaker
+    let mut me = user_aged(27)?;
+    let age = me.prefs.iter_mut().find(|p| p.name == "age").unwrap();
+    *age = LabeledPreferenceRange {
+        strictness: Strictness::NiceToHave,
+        weight: 2.0,
+        ..LabeledPreferenceRange::dealbreaker(
+            "age".to_string(),
+            PreferenceRange { min: 25, max: 30 },
+        )
+    };
+
+    let in_range = user_aged(28)?;
+    let close = user_aged(40)?;
+    let far = user_aged(90)?;
+    assert_eq!(soft_match_score(&me.prefs, &me.props, &in_range.props), 1.0);
+    let close_score = soft_match_score(&me.prefs, &me.props, &close.props);
+    let far_score = soft_match_score(&me.prefs, &me.props, &far.props);
+    assert!(close_score < 1.0 && far_score < close_score);
+
+    //the index only sees dealbreakers, so the age range is left open
+    let age_index = me.prefs.iter().position(|p| p.name == "age").unwrap();
+    let bbox = me.prefs.get_bbox();
+    assert_eq!(bbox.min[age_index], i16::MIN);
+    assert_eq!(bbox.max[age_index], i16::MAX);
+
+    let ranked = rank_by_soft_prefs(&me, vec![far, in_range, close]);
+    let ages = ranked
+        .iter()
+        .map(|u| u.props.iter().find(|p| p.name == "age").unwrap().value)
+        .collect::<Vec<_>>();
+    assert_eq!(ages, vec![28, 40, 90]);
+
+    DB::destroy_database_for_real_dangerous("test_soft_prefs");
+    Ok(())
+}
@@ -0,0 +1,61 @@
+#[test]
+fn transactions_are_atomic_and_detect_conflicts() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::internal_models::{internal_chat::InternalChat, shared::Save},
+        transaction::{abort, SaveTxn, WriteConflict},
+    };
+
+    DB::destroy_database_for_real_dangerous("test_transaction");
+    let db = DB::new("test_transaction").unwrap();
+    db.migrate_all().unwrap();
+
+    //an aborted transaction leaves nothing behind
+    let chat = InternalChat::new(vec![]);
+    let result: Result<(), _> = db.transaction(|txn| {
+        chat.save_txn(txn)?;
+        Err(abort("changed my mind"))
+    });
+    assert!(result.is_err());
+    assert!(!chat.uuid.exists(&db)?);
+
+    let chat_uuid = chat.save(&db)?;
+    let stale = db.load_versioned(&chat_uuid)?.unwrap();
+
+    //someone else writes the chat after it was read
+    db.update(&chat_uuid, |chat: &mut InternalChat| {
+        chat.most_recent_message_sent_at += 1
+    })?;
+
+    let result = db.transaction(|txn| {
+        txn.expect_version(&chat_uuid, stale.version)?;
+        stale.value.save_txn(txn)
+    });
+    assert!(result.unwrap_err().is::<WriteConflict>());
+    let sent_at = stale.value.most_recent_message_sent_at;
+    assert_eq!(
+        chat_uuid.load(&db)?.unwrap().most_recent_message_sent_at,
+        sent_at + 1
+    );
+
+    //concurrent read-modify-writes don't lose updates
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..25 {
+                    db.update(&chat_uuid, |chat: &mut InternalChat| {
+                        chat.most_recent_message_sent_at += 1
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(
+        chat_uuid.load(&db)?.unwrap().most_recent_message_sent_at,
+        sent_at + 101
+    );
+
+    DB::destroy_database_for_real_dangerous("test_transaction");
+    Ok(())
+}
@@ -0,0 +1,104 @@
+#[test]
+fn unknown_props_follow_the_range_policy() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{
+        db::DB,
+        models::{
+            api_models::api_user::ApiUserWritable,
+            internal_models::{
+                internal_prefs::{
+                    rank_by_soft_prefs, LabeledPreferenceRange, PreferenceRange, UnknownPolicy,
+                },
+                internal_user::InternalUser,
+                shared::Save,
+            },
+        },
+        test::fake::Gen,
+        vec::shared::UNKNOWN,
+    };
+
+    DB::destroy_database_for_real_dangerous("test_unknown_props");
+    let db = DB::new("test_unknown_props").unwrap();
+    db.migrate_all().unwrap();
+
+    //open to everyone, so only height decides
+    let user_with_height = |height: i16| -> Result<InternalUser, Box<dyn std::error::Error>> {
+        let mut user: InternalUser = ApiUserWritable::gen(&db).to_internal(&db, true)?;
+        for pref in user.prefs.iter_mut() {
+            *pref = LabeledPreferenceRange::dealbreaker(
+                pref.name.clone(),
+                PreferenceRange {
+                    min: i16::MIN,
+                    max: i16::MAX,
+                },
+            );
+        }
+        user.props
+            .iter_mut()
+            .find(|p| p.name == "height_cm")
+            .unwrap()
+            .value = height;
+        Ok(user)
+    };
+    let want_height = |user: &mut InternalUser, unknown: UnknownPolicy| {
+        let pref = user
+            .prefs
+            .iter_mut()
+            .find(|p| p.name == "height_cm")
+            .unwrap();
+        pref.range = PreferenceRange { min: 170, max: 190 };
+        pref.unknown = unknown;
+    };
+
+    let tall = user_with_height(180)?;
+    let mut skipped = user_with_height(UNKNOWN)?;
+    skipped.elo = tall.elo + 100.0;
+    let short = user_with_height(150)?;
+    let (tall_uuid, skipped_uuid, short_uuid) =
+        (tall.uuid.clone(), skipped.uuid.clone(), short.uuid.clone());
+    tall.save(&db)?;
+    skipped.save(&db)?;
+    short.save(&db)?;
+
+    let mut me = user_with_height(175)?;
+    let mut counts = vec![];
+    for (unknown, finds_skipped) in [
+        (UnknownPolicy::Accept, true),
+        (UnknownPolicy::Reject, false),
+        (UnknownPolicy::Penalize, true),
+    ] {
+        want_height(&mut me, unknown);
+        let found = db.get_users_who_i_prefer(&me)?;
+        assert!(found.contains(&tall_uuid));
+        assert!(!found.contains(&short_uuid));
+        assert_eq!(found.contains(&skipped_uuid), finds_skipped);
+        //what the dry run route reports
+        counts.push(db.get_users_i_prefer_count_direct(&me.prefs, &me.props, &me.seen)?);
+    }
+    assert_eq!(counts[0], counts[2]);
+    assert!(counts[1] < counts[0]);
+
+    //penalized unknowns are kept but rank below an answer in range, whatever their elo
+    let ranked = rank_by_soft_prefs(
+        &me,
+        vec![
+            skipped_uuid.load(&db)?.unwrap(),
+            tall_uuid.load(&db)?.unwrap(),
+        ],
+    );
+    assert_eq!(ranked[0].uuid, tall_uuid);
+
+    //the other way around, my own unknown height against someone else's range
+    let mut picky = user_with_height(180)?;
+    want_height(&mut picky, UnknownPolicy::Reject);
+    let picky_uuid = picky.save(&db)?;
+    let unsure = user_with_height(UNKNOWN)?;
+    assert!(!db.get_users_who_prefer_me(&unsure)?.contains(&picky_uuid));
+
+    let mut picky = picky_uuid.load(&db)?.unwrap();
+    want_height(&mut picky, UnknownPolicy::Accept);
+    picky.save(&db)?;
+    assert!(db.get_users_who_prefer_me(&unsure)?.contains(&picky_uuid));
+
+    DB::destroy_database_for_real_dangerous("test_unknown_props");
+    Ok(())
+}
@@ -0,0 +1,281 @@
+use std::{cell::RefCell, error::Error, fmt, sync::OnceLock};
+
+use kv::Raw;
+use rkyv::{
+    ser::serializers::{
+        AlignedSerializer, AllocScratch, CompositeSerializer, FallbackScratch, HeapScratch,
+        SharedSerializeMap,
+    },
+    validation::validators::DefaultValidator,
+    AlignedVec, Archive, Deserialize, Infallible, Serialize,
+};
+use sled::transaction::{
+    ConflictableTransactionError, TransactionError, Transactional, TransactionalTree,
+};
+
+use crate::{
+    db::{from_bytes, sled_db, to_bytes, DB, SCRATCH_SPACE_SIZE},
+    index::INDEX_KEYS,
+    models::internal_models::shared::{Insertable, InternalUuid, MODELS},
+    vec::persist::checksum,
+};
+
+//every bucket a transaction can touch, the object buckets and their indexes
+fn txn_buckets() -> &'static [&'static str] {
+    static BUCKETS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    BUCKETS.get_or_init(|| {
+        let mut buckets = vec!["version", INDEX_KEYS];
+        for model in MODELS.iter() {
+            buckets.push((model.bucket)());
+            buckets.extend((model.indexes)().iter().map(|index| index.name));
+        }
+        buckets
+    })
+}
+
+//how many times a read-modify-write is redone before the conflict is returned
+pub const MAX_CONFLICT_RETRIES: usize = 8;
+
+//the version of an object that isn't stored
+pub const ABSENT_VERSION: u64 = 0;
+
+pub type TxnResult<R> = Result<R, ConflictableTransactionError<Box<dyn Error>>>;
+
+type AfterCommit = Box<dyn FnOnce(&DB) -> Result<(), Box<dyn Error>>>;
+
+pub fn abort<E: Into<Box<dyn Error>>>(e: E) -> ConflictableTransactionError<Box<dyn Error>> {
+    ConflictableTransactionError::Abort(e.into())
+}
+
+fn version_of(bytes: Option<&[u8]>) -> u64 {
+    match bytes {
+        Some(bytes) => checksum(bytes).max(1),
+        None => ABSENT_VERSION,
+    }
+}
+
+/// Returned when an object changed between being read and being written back.
+#[derive(Debug)]
+pub struct WriteConflict {
+    pub bucket: &'static str,
+    pub id: String,
+}
+
+impl fmt::Display for WriteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} was changed concurrently", self.bucket, self.id)
+    }
+}
+
+impl Error for WriteConflict {}
+
+/// An object and the version it was read at. The version is a checksum of the
+/// stored bytes, so any write changes it and nothing extra has to be stored.
+pub struct Versioned<T> {
+    pub value: T,
+    pub version: u64,
+}
+
+/// The view of the db inside `DB::transaction`. Nothing written here is visible
+/// to anyone else until the whole transaction commits, and nothing in here may
+/// touch the db outside of the transaction, sled would deadlock.
+pub struct Txn<'a> {
+    trees: &'a [TransactionalTree],
+    after_commit: &'a RefCell<Vec<AfterCommit>>,
+}
+
+impl<'a> Txn<'a> {
+    pub(crate) fn tree(&self, bucket: &str) -> TxnResult<&TransactionalTree> {
+        match txn_buckets().iter().position(|b| *b == bucket) {
+            Some(i) => Ok(&self.trees[i]),
+            None => Err(abort(format!("Bucket {} is not transactional", bucket))),
+        }
+    }
+
+    /// Aborts with a `WriteConflict` unless the object is still at `version`.
+    pub fn expect_version<T: Insertable>(
+        &self,
+        key: &InternalUuid<T>,
+        version: u64,
+    ) -> TxnResult<()> {
+        let current = self.tree(T::bucket())?.get(key.id.as_bytes())?;
+        if version_of(current.as_deref()) != version {
+            return Err(abort(WriteConflict {
+                bucket: T::bucket(),
+                id: key.id.clone(),
+            }));
+        }
+        Ok(())
+    }
+
+    /// Writes the object along with its index keys, see `Insertable::index_values`.
+    pub fn write<T>(&self, key: &InternalUuid<T>, object: &T) -> TxnResult<()>
+    where
+        T: Serialize<
+                CompositeSerializer<
+                    AlignedSerializer<AlignedVec>,
+                    FallbackScratch<HeapScratch<SCRATCH_SPACE_SIZE>, AllocScratch>,
+                    SharedSerializeMap,
+                >,
+            > + Insertable,
+    {
+        //sled holds its write lock while this runs, so it can't go through the plain db
+        let version = match self.tree("version")?.get(T::bucket().as_bytes())? {
+            Some(version) => std::str::from_utf8(&version)
+                .map_err(abort)?
+                .parse::<u64>()
+                .map_err(abort)?,
+            None => 0,
+        };
+        if version != T::version() {
+            return Err(abort(format!(
+                "Version mismatch for object {:?} of type {:?}, object has {:?}, db has {:?}",
+                key.id,
+                T::bucket(),
+                T::version(),
+                version
+            )));
+        }
+        self.write_index_keys(key, object)?;
+        let bytes = to_bytes(object);
+        self.tree(T::bucket())?
+            .insert(key.id.as_bytes(), bytes.as_slice())?;
+        Ok(())
+    }
+
+    pub fn read<T>(&self, key: &InternalUuid<T>) -> TxnResult<Option<T>>
+    where
+        T: Archive + Insertable,
+        for<'b> T::Archived: rkyv::CheckBytes<DefaultValidator<'b>> + Deserialize<T, Infallible>,
+    {
+        match self.tree(T::bucket())?.get(key.id.as_bytes())? {
+            Some(bytes) => Ok(Some(from_bytes(&bytes).map_err(abort)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn delete<T: Insertable>(&self, key: &InternalUuid<T>) -> TxnResult<bool> {
+        self.delete_index_keys(key)?;
+        let removed = self.tree(T::bucket())?.remove(key.id.as_bytes())?;
+        Ok(removed.is_some())
+    }
+
+    pub fn write_index<T>(&self, view: &str, value: &str, uuid: &InternalUuid<T>) -> TxnResult<()> {
+        self.tree(view)?
+            .insert(value.as_bytes(), uuid.id.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn delete_index(&self, view: &str, value: &str) -> TxnResult<bool> {
+        let removed = self.tree(view)?.remove(value.as_bytes())?;
+        Ok(removed.is_some())
+    }
+
+    pub fn read_index<T>(&self, view: &str, value: &str) -> TxnResult<Option<InternalUuid<T>>> {
+        match self.tree(view)?.get(value.as_bytes())? {
+            Some(id) => {
+                let id = String::from_utf8(id.to_vec()).map_err(abort)?;
+                Ok(Some(id.into()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Runs `f` once the transaction has committed, for state that lives outside
+    /// of sled like the vec index. Dropped if the transaction aborts.
+    pub fn after_commit(&self, f: impl FnOnce(&DB) -> Result<(), Box<dyn Error>> + 'static) {
+        self.after_commit.borrow_mut().push(Box::new(f));
+    }
+}
+
+/// Like `Save`, but staged in a transaction.
+pub trait SaveTxn {
+    fn save_txn(&self, txn: &Txn) -> TxnResult<()>;
+}
+
+impl DB {
+    pub fn load_versioned<T>(
+        &self,
+        key: &InternalUuid<T>,
+    ) -> Result<Option<Versioned<T>>, Box<dyn Error>>
+    where
+        T: Archive + Insertable,
+        for<'a> T::Archived: rkyv::CheckBytes<DefaultValidator<'a>> + Deserialize<T, Infallible>,
+    {
+        let bucket = self.store.bucket::<Raw, Raw>(Some(T::bucket()))?;
+        let value_raw = match bucket.get(&Raw::from(key.id.as_bytes()))? {
+            Some(value_raw) => value_raw,
+            None => return Ok(None),
+        };
+        Ok(Some(Versioned {
+            value: from_bytes(&value_raw[..])?,
+            version: version_of(Some(&value_raw[..])),
+        }))
+    }
+
+    /// Runs `f` as one sled transaction over every object bucket, either all of its
+    /// writes land or none do. `f` is rerun if sled sees a concurrent transaction.
+    pub fn transaction<R>(&self, f: impl Fn(&Txn) -> TxnResult<R>) -> Result<R, Box<dyn Error>> {
+        let sled = sled_db(&self.store);
+        let trees = txn_buckets()
+            .iter()
+            .map(|bucket| sled.open_tree(bucket))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let after_commit = RefCell::new(vec![]);
+        let result = trees.as_slice().transaction(|trees| {
+            after_commit.borrow_mut().clear();
+            let txn = Txn {
+                trees,
+                after_commit: &after_commit,
+            };
+            f(&txn)
+        });
+
+        let result = match result {
+            Ok(result) => result,
+            Err(TransactionError::Abort(e)) => return Err(e),
+            Err(TransactionError::Storage(e)) => return Err(e.into()),
+        };
+        for f in after_commit.into_inner() {
+            f(self)?;
+        }
+        Ok(result)
+    }
+
+    /// Reruns `f` while it fails with a `WriteConflict`. `f` has to do its own
+    /// reads so each attempt works on fresh data.
+    pub fn retry_on_conflict<R>(
+        &self,
+        mut f: impl FnMut() -> Result<R, Box<dyn Error>>,
+    ) -> Result<R, Box<dyn Error>> {
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Err(e) if e.is::<WriteConflict>() && attempt < MAX_CONFLICT_RETRIES => {
+                    log::info!("Retrying after conflict, attempt {}: {}", attempt, e);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Read-modify-write of a single object, retried until nobody else wrote it in between.
+    pub fn update<T>(&self, key: &InternalUuid<T>, f: impl Fn(&mut T)) -> Result<(), Box<dyn Error>>
+    where
+        T: Archive + Insertable + SaveTxn,
+        for<'a> T::Archived: rkyv::CheckBytes<DefaultValidator<'a>> + Deserialize<T, Infallible>,
+    {
+        self.retry_on_conflict(|| {
+            let mut object =
+                self.load_versioned(key)?
+                    .ok_or(format!("{} {} not found", T::bucket(), key.id))?;
+            f(&mut object.value);
+            self.transaction(|txn| {
+                txn.expect_version(key, object.version)?;
+                object.value.save_txn(txn)
+            })
+        })
+    }
+}
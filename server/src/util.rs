@@ -1,3 +1,6 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::digest::{digest, SHA256};
+
 use crate::{
     db::DB,
     models::internal_models::{internal_access_code::InternalAccessCode, shared::Save},
@@ -29,3 +32,8 @@ pub fn generate_access_codes(n: usize, db: &DB) -> Result<Vec<String>, Box<dyn s
     }
     Ok(access_codes)
 }
+
+//for tokens and codes handed to users, only the hash is stored
+pub fn hash_secret(secret: &str) -> String {
+    URL_SAFE_NO_PAD.encode(digest(&SHA256, secret.as_bytes()))
+}
@@ -1,3 +1,6 @@
+pub mod persist;
+pub mod search_backend;
 pub mod search_linear;
+pub mod search_spatial;
 pub mod shared;
 pub mod vec_test;
@@ -0,0 +1,389 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{
+    search_backend::{VecBackend, VecIndex},
+    shared::{Bbox, LabelPairBbox, LabelPairVec, VectorSearch},
+};
+
+//bump this whenever the serialized layout of VecIndex changes
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 3;
+
+const SNAPSHOT_FILE: &str = "vec_index.snapshot";
+const JOURNAL_FILE: &str = "vec_index.journal";
+
+/// FNV-1a, stable across rust versions unlike `DefaultHasher`.
+pub fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotHeader {
+    format_version: u32,
+    user_version: u64,
+    //vectors are laid out by the prefs schema, a different one reorders them
+    prefs_schema: u64,
+    checksum: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum JournalEntry<const N: usize> {
+    Add(LabelPairVec<N>),
+    AddBbox(LabelPairBbox<N>),
+    Remove(String),
+    RemoveBbox(String),
+}
+
+/// The vector index plus the files that let it survive a restart: a snapshot of
+/// the whole index, and a journal of every add/remove made since that snapshot.
+/// Every journaled op is an upsert or a remove, so replaying entries that already
+/// made it into the snapshot is harmless.
+pub struct PersistentVecIndex<const N: usize> {
+    index: VecIndex<N>,
+    dir: Option<PathBuf>,
+    journal: Option<File>,
+}
+
+impl<const N: usize> PersistentVecIndex<N> {
+    pub fn in_memory(index: VecIndex<N>) -> Self {
+        PersistentVecIndex {
+            index,
+            dir: None,
+            journal: None,
+        }
+    }
+
+    pub fn backend(&self) -> VecBackend {
+        self.index.backend()
+    }
+
+    /// Loads the snapshot in `dir` and replays the journal on top of it. Returns
+    /// `None` if there's nothing usable and the index has to be rebuilt from the db.
+    pub fn load(
+        dir: &Path,
+        user_version: u64,
+        prefs_schema: u64,
+        backend: VecBackend,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        let snapshot_path = dir.join(SNAPSHOT_FILE);
+        if !snapshot_path.exists() {
+            log::info!("No vec index snapshot found");
+            return Ok(None);
+        }
+
+        let mut reader = BufReader::new(File::open(&snapshot_path)?);
+        let mut header_line = String::new();
+        reader.read_line(&mut header_line)?;
+        let header: SnapshotHeader = match serde_json::from_str(&header_line) {
+            Ok(header) => header,
+            Err(e) => {
+                log::warn!("Unreadable vec index snapshot header {:?}", e);
+                return Ok(None);
+            }
+        };
+        if header.format_version != SNAPSHOT_FORMAT_VERSION {
+            log::info!(
+                "Vec index snapshot format is {}, expected {}",
+                header.format_version,
+                SNAPSHOT_FORMAT_VERSION
+            );
+            return Ok(None);
+        }
+        if header.user_version != user_version {
+            log::info!(
+                "Vec index snapshot was taken at user version {}, db is at {}",
+                header.user_version,
+                user_version
+            );
+            return Ok(None);
+        }
+        if header.prefs_schema != prefs_schema {
+            log::info!("Vec index snapshot was taken with a different prefs schema");
+            return Ok(None);
+        }
+
+        let mut body = vec![];
+        reader.read_to_end(&mut body)?;
+        if checksum(&body) != header.checksum {
+            log::warn!("Vec index snapshot checksum mismatch");
+            return Ok(None);
+        }
+        let mut index: VecIndex<N> = serde_json::from_slice(&body)?;
+        if index.backend() != backend {
+            log::info!(
+                "Vec index snapshot is {:?}, wanted {:?}",
+                index.backend(),
+                backend
+            );
+            return Ok(None);
+        }
+
+        let journal_path = dir.join(JOURNAL_FILE);
+        let mut replayed = 0;
+        if journal_path.exists() {
+            for line in BufReader::new(File::open(&journal_path)?).lines() {
+                let line = line?;
+                let entry = match parse_journal_line::<N>(&line) {
+                    Some(entry) => entry,
+                    None => {
+                        log::warn!("Vec index journal entry {} is corrupt", replayed);
+                        return Ok(None);
+                    }
+                };
+                apply(&mut index, entry);
+                replayed += 1;
+            }
+        }
+        log::info!(
+            "Loaded vec index snapshot, replayed {} journal entries",
+            replayed
+        );
+
+        let mut loaded = Self::in_memory(index);
+        loaded.attach(dir)?;
+        Ok(Some(loaded))
+    }
+
+    /// Starts journaling into `dir`, entries are appended to whatever is already there.
+    pub fn attach(&mut self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(dir)?;
+        let journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))?;
+        self.dir = Some(dir.to_path_buf());
+        self.journal = Some(journal);
+        Ok(())
+    }
+
+    /// Another handle on the journal, so it can be synced without holding the index.
+    pub fn journal_handle(&self) -> std::io::Result<Option<File>> {
+        self.journal.as_ref().map(File::try_clone).transpose()
+    }
+
+    /// Writes the whole index to the snapshot file and empties the journal.
+    pub fn snapshot(&mut self, user_version: u64, prefs_schema: u64) -> Result<(), Box<dyn Error>> {
+        let dir = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => return Ok(()),
+        };
+        let body = serde_json::to_vec(&self.index)?;
+        let header = SnapshotHeader {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            user_version,
+            prefs_schema,
+            checksum: checksum(&body),
+        };
+
+        let tmp_path = dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            tmp.write_all(serde_json::to_string(&header)?.as_bytes())?;
+            tmp.write_all(b"\n")?;
+            tmp.write_all(&body)?;
+            tmp.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, dir.join(SNAPSHOT_FILE))?;
+
+        //a crash before this leaves entries the snapshot already has, which replay fine
+        match &self.journal {
+            Some(journal) => journal.set_len(0)?,
+            None => self.attach(&dir)?,
+        }
+        log::info!("Wrote vec index snapshot ({} bytes)", body.len());
+        Ok(())
+    }
+
+    fn journal(&mut self, entry: JournalEntry<N>) {
+        let journal = match &mut self.journal {
+            Some(journal) => journal,
+            None => return,
+        };
+        let result = serde_json::to_string(&entry)
+            .map_err(|e| e.into())
+            .and_then(|json| -> Result<(), Box<dyn Error>> {
+                writeln!(journal, "{:016x} {}", checksum(json.as_bytes()), json)?;
+                Ok(())
+            });
+        if let Err(e) = result {
+            //the journal can't be trusted anymore, make the next boot rebuild
+            log::error!("Failed to write vec index journal {:?}", e);
+            self.journal = None;
+            if let Some(dir) = &self.dir {
+                let _ = std::fs::remove_file(dir.join(SNAPSHOT_FILE));
+            }
+        }
+    }
+}
+
+fn parse_journal_line<const N: usize>(line: &str) -> Option<JournalEntry<N>> {
+    let (sum, json) = line.split_once(' ')?;
+    let sum = u64::from_str_radix(sum, 16).ok()?;
+    if checksum(json.as_bytes()) != sum {
+        return None;
+    }
+    serde_json::from_str(json).ok()
+}
+
+fn apply<const N: usize>(index: &mut VecIndex<N>, entry: JournalEntry<N>) {
+    match entry {
+        JournalEntry::Add(label_pair) => index.add(&label_pair.vec, &label_pair.label),
+        JournalEntry::AddBbox(label_pair) => index.add_bbox(&label_pair.bbox, &label_pair.label),
+        JournalEntry::Remove(label) => index.remove(&label),
+        JournalEntry::RemoveBbox(label) => index.remove_bbox(&label),
+    }
+}
+
+impl<const N: usize> VectorSearch<N> for PersistentVecIndex<N> {
+    fn new() -> Self {
+        Self::in_memory(VecIndex::new())
+    }
+
+    fn new_vec_store(vecs: Vec<LabelPairVec<N>>) -> Self {
+        Self::in_memory(VecIndex::new_vec_store(vecs))
+    }
+
+    fn new_bbox_store(bboxes: Vec<LabelPairBbox<N>>) -> Self {
+        Self::in_memory(VecIndex::new_bbox_store(bboxes))
+    }
+
+    fn search<'a>(
+        &'a self,
+        bbox: &'a Bbox<N>,
+        skip_labels: Option<&'a HashSet<String>>,
+    ) -> impl Iterator<Item = LabelPairVec<N>> + 'a {
+        self.index.search(bbox, skip_labels)
+    }
+
+    fn search_inverse<'a>(
+        &'a self,
+        location: &'a [i16; N],
+        skip_labels: Option<&'a HashSet<String>>,
+    ) -> impl Iterator<Item = LabelPairBbox<N>> + 'a {
+        self.index.search_inverse(location, skip_labels)
+    }
+
+    fn contains_vec(&self, label: &String) -> bool {
+        self.index.contains_vec(label)
+    }
+
+    fn contains_bbox(&self, label: &String) -> bool {
+        self.index.contains_bbox(label)
+    }
+
+    fn add(&mut self, location: &[i16; N], label: &String) {
+        self.index.add(location, label);
+        self.journal(JournalEntry::Add(LabelPairVec {
+            label: label.clone(),
+            vec: *location,
+        }));
+    }
+
+    fn add_bbox(&mut self, bbox: &Bbox<N>, label: &String) {
+        self.index.add_bbox(bbox, label);
+        self.journal(JournalEntry::AddBbox(LabelPairBbox {
+            label: label.clone(),
+            bbox: bbox.clone(),
+        }));
+    }
+
+    fn remove(&mut self, label: &String) {
+        self.index.remove(label);
+        self.journal(JournalEntry::Remove(label.clone()));
+    }
+
+    fn remove_bbox(&mut self, label: &String) {
+        self.index.remove_bbox(label);
+        self.journal(JournalEntry::RemoveBbox(label.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("vec_persist_{}", uuid::Uuid::new_v4()))
+    }
+
+    fn bbox(min: i16, max: i16) -> Bbox<2> {
+        Bbox {
+            min: [min; 2],
+            max: [max; 2],
+            accept_unknown: [true; 2],
+        }
+    }
+
+    #[test]
+    fn test_snapshot_and_journal_replay() {
+        let dir = test_dir();
+        let mut index = PersistentVecIndex::<2>::in_memory(VecIndex::new());
+        index.attach(&dir).unwrap();
+        index.add(&[1, 1], &"a".to_string());
+        index.add_bbox(&bbox(0, 5), &"a".to_string());
+        index.snapshot(1, 7).unwrap();
+
+        //these only live in the journal
+        index.add(&[3, 3], &"b".to_string());
+        index.remove(&"a".to_string());
+        drop(index);
+
+        let loaded = PersistentVecIndex::<2>::load(&dir, 1, 7, VecBackend::default())
+            .unwrap()
+            .unwrap();
+        assert!(!loaded.contains_vec(&"a".to_string()));
+        assert!(loaded.contains_vec(&"b".to_string()));
+        assert!(loaded.contains_bbox(&"a".to_string()));
+        assert_eq!(loaded.search(&bbox(0, 5), None).count(), 1);
+
+        //a different user version or prefs schema means the snapshot is stale
+        assert!(
+            PersistentVecIndex::<2>::load(&dir, 2, 7, VecBackend::default())
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            PersistentVecIndex::<2>::load(&dir, 1, 8, VecBackend::default())
+                .unwrap()
+                .is_none()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_journal_forces_rebuild() {
+        let dir = test_dir();
+        let mut index = PersistentVecIndex::<2>::in_memory(VecIndex::new());
+        index.attach(&dir).unwrap();
+        index.snapshot(0, 0).unwrap();
+        index.add(&[1, 1], &"a".to_string());
+        drop(index);
+
+        let mut journal = OpenOptions::new()
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))
+            .unwrap();
+        journal.write_all(b"0000000000000000 {\"Remove\"").unwrap();
+        drop(journal);
+
+        assert!(
+            PersistentVecIndex::<2>::load(&dir, 0, 0, VecBackend::default())
+                .unwrap()
+                .is_none()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
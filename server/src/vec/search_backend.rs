@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use super::search_linear::LinearSearch;
+use super::search_spatial::SpatialSearch;
+use super::shared::{Bbox, LabelPairBbox, LabelPairVec, VectorSearch};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VecBackend {
+    Linear,
+    #[default]
+    Spatial,
+}
+
+impl std::str::FromStr for VecBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "linear" => Ok(VecBackend::Linear),
+            "spatial" => Ok(VecBackend::Spatial),
+            _ => Err(format!("Unknown vector search backend {}", s)),
+        }
+    }
+}
+
+/// The vector index the db holds, `VectorSearch` isn't object safe so the backend
+/// is picked through this enum instead of a trait object.
+#[derive(Serialize, Deserialize)]
+pub enum VecIndex<const N: usize> {
+    Linear(LinearSearch<N>),
+    Spatial(SpatialSearch<N>),
+}
+
+impl<const N: usize> VecIndex<N> {
+    pub fn with_backend(backend: VecBackend) -> Self {
+        match backend {
+            VecBackend::Linear => VecIndex::Linear(LinearSearch::new()),
+            VecBackend::Spatial => VecIndex::Spatial(SpatialSearch::new()),
+        }
+    }
+
+    pub fn backend(&self) -> VecBackend {
+        match self {
+            VecIndex::Linear(_) => VecBackend::Linear,
+            VecIndex::Spatial(_) => VecBackend::Spatial,
+        }
+    }
+}
+
+impl<const N: usize> VectorSearch<N> for VecIndex<N> {
+    fn new() -> Self {
+        Self::with_backend(VecBackend::default())
+    }
+
+    fn new_vec_store(vecs: Vec<LabelPairVec<N>>) -> Self {
+        VecIndex::Spatial(SpatialSearch::new_vec_store(vecs))
+    }
+
+    fn new_bbox_store(bboxes: Vec<LabelPairBbox<N>>) -> Self {
+        VecIndex::Spatial(SpatialSearch::new_bbox_store(bboxes))
+    }
+
+    fn search<'a>(
+        &'a self,
+        bbox: &'a Bbox<N>,
+        skip_labels: Option<&'a HashSet<String>>,
+    ) -> impl Iterator<Item = LabelPairVec<N>> + 'a {
+        let iter: Box<dyn Iterator<Item = LabelPairVec<N>> + 'a> = match self {
+            VecIndex::Linear(search) => Box::new(search.search(bbox, skip_labels)),
+            VecIndex::Spatial(search) => Box::new(search.search(bbox, skip_labels)),
+        };
+        iter
+    }
+
+    fn search_inverse<'a>(
+        &'a self,
+        location: &'a [i16; N],
+        skip_labels: Option<&'a HashSet<String>>,
+    ) -> impl Iterator<Item = LabelPairBbox<N>> + 'a {
+        let iter: Box<dyn Iterator<Item = LabelPairBbox<N>> + 'a> = match self {
+            VecIndex::Linear(search) => Box::new(search.search_inverse(location, skip_labels)),
+            VecIndex::Spatial(search) => Box::new(search.search_inverse(location, skip_labels)),
+        };
+        iter
+    }
+
+    fn contains_vec(&self, label: &String) -> bool {
+        match self {
+            VecIndex::Linear(search) => search.contains_vec(label),
+            VecIndex::Spatial(search) => search.contains_vec(label),
+        }
+    }
+
+    fn contains_bbox(&self, label: &String) -> bool {
+        match self {
+            VecIndex::Linear(search) => search.contains_bbox(label),
+            VecIndex::Spatial(search) => search.contains_bbox(label),
+        }
+    }
+
+    fn add(&mut self, location: &[i16; N], label: &String) {
+        match self {
+            VecIndex::Linear(search) => search.add(location, label),
+            VecIndex::Spatial(search) => search.add(location, label),
+        }
+    }
+
+    fn add_bbox(&mut self, bbox: &Bbox<N>, label: &String) {
+        match self {
+            VecIndex::Linear(search) => search.add_bbox(bbox, label),
+            VecIndex::Spatial(search) => search.add_bbox(bbox, label),
+        }
+    }
+
+    fn remove(&mut self, label: &String) {
+        match self {
+            VecIndex::Linear(search) => search.remove(label),
+            VecIndex::Spatial(search) => search.remove(label),
+        }
+    }
+
+    fn remove_bbox(&mut self, label: &String) {
+        match self {
+            VecIndex::Linear(search) => search.remove_bbox(label),
+            VecIndex::Spatial(search) => search.remove_bbox(label),
+        }
+    }
+}
@@ -60,16 +60,7 @@ impl<const N: usize> VectorSearch<N> for LinearSearch<N> {
                         return false;
                     }
                 }
-                for i in 0..N {
-                    if label_pair.vec[i] < bbox.min[i] || label_pair.vec[i] > bbox.max[i] {
-                        // log::info!(
-                        //     "[SRCH] idx: {}, min: {}, max: {}, loc: {}",
-                        //     i, bbox.min[i], bbox.max[i], label_pair.vec[i]
-                        // );
-                        return false;
-                    }
-                }
-                true
+                bbox.contains(&label_pair.vec)
             })
             .cloned()
     }
@@ -87,17 +78,7 @@ impl<const N: usize> VectorSearch<N> for LinearSearch<N> {
                         return false;
                     }
                 }
-                for i in 0..N {
-                    if label_pair.bbox.min[i] > location[i] || label_pair.bbox.max[i] < location[i]
-                    {
-                        // log::info!(
-                        //     "[INV] idx: {}, min: {}, max: {}, loc: {}",
-                        //     i, label_pair.bbox.min[i], label_pair.bbox.max[i], location[i]
-                        // );
-                        return false;
-                    }
-                }
-                true
+                label_pair.bbox.contains(location)
             })
             .cloned()
     }
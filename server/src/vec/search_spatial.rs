@@ -0,0 +1,418 @@
+use serde::{Deserialize, Serialize};
+
+use super::shared::{Bbox, LabelPairBbox, LabelPairVec, VectorSearch, UNKNOWN};
+use std::collections::{HashMap, HashSet};
+
+//leaves are scanned linearly, so this trades tree depth against scan length
+const LEAF_SIZE: usize = 64;
+//how many items are looked at when picking the split dimension of a node
+const SPLIT_SAMPLE_SIZE: usize = 32;
+//always tolerate this many unindexed/removed slots before rebuilding
+const MIN_REBUILD_SLACK: usize = 256;
+
+/// Anything the kd-tree can be built over: a list of items, each with a fixed
+/// number of i16 keys.
+trait KdKeys {
+    fn dims(&self) -> usize;
+    fn key(&self, item: usize, dim: usize) -> i16;
+}
+
+impl<const N: usize> KdKeys for [LabelPairVec<N>] {
+    fn dims(&self) -> usize {
+        N
+    }
+
+    fn key(&self, item: usize, dim: usize) -> i16 {
+        self[item].vec[dim]
+    }
+}
+
+//a bbox is indexed as the 2N dimensional point (min, max), "bbox contains point p"
+//then becomes the range query min <= p && max >= p, so the same tree answers both
+impl<const N: usize> KdKeys for [LabelPairBbox<N>] {
+    fn dims(&self) -> usize {
+        N * 2
+    }
+
+    fn key(&self, item: usize, dim: usize) -> i16 {
+        if dim < N {
+            self[item].bbox.min[dim]
+        } else {
+            self[item].bbox.max[dim - N]
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum KdNode {
+    Leaf {
+        start: u32,
+        end: u32,
+    },
+    Split {
+        dim: u16,
+        //everything left of the split is <= value, everything right is >= value
+        value: i16,
+        left: u32,
+        right: u32,
+    },
+}
+
+/// Static kd-tree over the first `len` slots of a store. Slots are referenced by
+/// index, `order` holds them grouped by leaf.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct KdTree {
+    order: Vec<u32>,
+    nodes: Vec<KdNode>,
+}
+
+impl KdTree {
+    fn build<K: KdKeys + ?Sized>(keys: &K, slots: Vec<u32>) -> Self {
+        let mut tree = KdTree {
+            order: slots,
+            nodes: vec![],
+        };
+        if !tree.order.is_empty() {
+            let len = tree.order.len();
+            tree.build_node(keys, 0, len);
+        }
+        tree
+    }
+
+    fn build_node<K: KdKeys + ?Sized>(&mut self, keys: &K, start: usize, end: usize) -> u32 {
+        let node_idx = self.nodes.len() as u32;
+        if end - start <= LEAF_SIZE {
+            self.nodes.push(KdNode::Leaf {
+                start: start as u32,
+                end: end as u32,
+            });
+            return node_idx;
+        }
+
+        let (dim, spread) = self.widest_dim(keys, start, end);
+        //every sampled key is identical, nothing left to split on
+        if spread == 0 {
+            self.nodes.push(KdNode::Leaf {
+                start: start as u32,
+                end: end as u32,
+            });
+            return node_idx;
+        }
+
+        let slice = &mut self.order[start..end];
+        let mid = slice.len() / 2;
+        slice.select_nth_unstable_by_key(mid, |&slot| keys.key(slot as usize, dim));
+        let value = keys.key(slice[mid] as usize, dim);
+
+        //placeholder, children are filled in once they exist
+        self.nodes.push(KdNode::Leaf { start: 0, end: 0 });
+        let left = self.build_node(keys, start, start + mid);
+        let right = self.build_node(keys, start + mid, end);
+        self.nodes[node_idx as usize] = KdNode::Split {
+            dim: dim as u16,
+            value,
+            left,
+            right,
+        };
+        node_idx
+    }
+
+    fn spread<K: KdKeys + ?Sized>(&self, keys: &K, start: usize, end: usize, dim: usize) -> i32 {
+        let step = ((end - start) / SPLIT_SAMPLE_SIZE).max(1);
+        let mut min = i16::MAX;
+        let mut max = i16::MIN;
+        for &slot in self.order[start..end].iter().step_by(step) {
+            let key = keys.key(slot as usize, dim);
+            min = min.min(key);
+            max = max.max(key);
+        }
+        max as i32 - min as i32
+    }
+
+    fn widest_dim<K: KdKeys + ?Sized>(&self, keys: &K, start: usize, end: usize) -> (usize, i32) {
+        let mut best_dim = 0;
+        let mut best_spread = -1;
+        for dim in 0..keys.dims() {
+            let spread = self.spread(keys, start, end, dim);
+            if spread > best_spread {
+                best_dim = dim;
+                best_spread = spread;
+            }
+        }
+        (best_dim, best_spread)
+    }
+
+    /// Calls `visit` for every slot in a leaf whose region may intersect `[lo, hi]`.
+    /// Callers still have to check each slot, this only prunes.
+    fn query(&self, lo: &[i16], hi: &[i16], mut visit: impl FnMut(u32)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0u32];
+        while let Some(node) = stack.pop() {
+            match &self.nodes[node as usize] {
+                KdNode::Leaf { start, end } => {
+                    for &slot in &self.order[*start as usize..*end as usize] {
+                        visit(slot);
+                    }
+                }
+                KdNode::Split {
+                    dim,
+                    value,
+                    left,
+                    right,
+                } => {
+                    let dim = *dim as usize;
+                    if lo[dim] <= *value {
+                        stack.push(*left);
+                    }
+                    if hi[dim] >= *value {
+                        stack.push(*right);
+                    }
+                }
+            }
+        }
+    }
+}
+
+trait Labeled {
+    fn label(&self) -> &String;
+}
+
+impl<const N: usize> Labeled for LabelPairVec<N> {
+    fn label(&self) -> &String {
+        &self.label
+    }
+}
+
+impl<const N: usize> Labeled for LabelPairBbox<N> {
+    fn label(&self) -> &String {
+        &self.label
+    }
+}
+
+/// A store of labeled items with a kd-tree over most of them. New and updated
+/// items land in an unindexed tail that is scanned linearly, removed items are
+/// tombstoned, and the tree is rebuilt once either grows too large.
+#[derive(Serialize, Deserialize)]
+struct SpatialStore<T> {
+    items: Vec<T>,
+    alive: Vec<bool>,
+    slots: HashMap<String, usize>,
+    tree: KdTree,
+    indexed: usize,
+    dead: usize,
+}
+
+impl<T: Labeled> SpatialStore<T>
+where
+    [T]: KdKeys,
+{
+    fn new(items: Vec<T>) -> Self {
+        let mut store = SpatialStore {
+            items: vec![],
+            alive: vec![],
+            slots: HashMap::new(),
+            tree: KdTree::default(),
+            indexed: 0,
+            dead: 0,
+        };
+        for item in items {
+            store.push(item);
+        }
+        store.rebuild();
+        store
+    }
+
+    fn contains(&self, label: &String) -> bool {
+        self.slots.contains_key(label)
+    }
+
+    fn push(&mut self, item: T) {
+        if let Some(old) = self.slots.insert(item.label().clone(), self.items.len()) {
+            self.alive[old] = false;
+            self.dead += 1;
+        }
+        self.items.push(item);
+        self.alive.push(true);
+    }
+
+    fn insert(&mut self, item: T) {
+        self.push(item);
+        self.maybe_rebuild();
+    }
+
+    fn remove(&mut self, label: &String) {
+        if let Some(old) = self.slots.remove(label) {
+            self.alive[old] = false;
+            self.dead += 1;
+        }
+        self.maybe_rebuild();
+    }
+
+    fn maybe_rebuild(&mut self) {
+        let slack = (self.items.len() - self.indexed) + self.dead;
+        if slack > MIN_REBUILD_SLACK.max(self.items.len() / 8) {
+            self.rebuild();
+        }
+    }
+
+    fn rebuild(&mut self) {
+        let items = std::mem::take(&mut self.items);
+        let alive = std::mem::take(&mut self.alive);
+        self.items = items
+            .into_iter()
+            .zip(alive)
+            .filter_map(|(item, alive)| if alive { Some(item) } else { None })
+            .collect();
+        self.slots = self
+            .items
+            .iter()
+            .enumerate()
+            .map(|(slot, item)| (item.label().clone(), slot))
+            .collect();
+        self.alive = vec![true; self.items.len()];
+        self.dead = 0;
+        self.indexed = self.items.len();
+        self.tree = KdTree::build(
+            self.items.as_slice(),
+            (0..self.items.len() as u32).collect(),
+        );
+    }
+
+    /// Every live item that may lie in `[lo, hi]`, the caller does the exact check.
+    fn candidates(&self, lo: &[i16], hi: &[i16]) -> Vec<&T> {
+        let mut out = vec![];
+        self.tree.query(lo, hi, |slot| {
+            if self.alive[slot as usize] {
+                out.push(&self.items[slot as usize]);
+            }
+        });
+        for slot in self.indexed..self.items.len() {
+            if self.alive[slot] {
+                out.push(&self.items[slot]);
+            }
+        }
+        out
+    }
+}
+
+/// `VectorSearch` backed by a kd-tree over the vectors and a kd-tree over the
+/// bbox corners, returns the same results as `LinearSearch`.
+#[derive(Serialize, Deserialize)]
+pub struct SpatialSearch<const N: usize> {
+    vecs: SpatialStore<LabelPairVec<N>>,
+    bboxes: SpatialStore<LabelPairBbox<N>>,
+}
+
+impl<const N: usize> VectorSearch<N> for SpatialSearch<N> {
+    fn new_vec_store(vecs: Vec<LabelPairVec<N>>) -> Self {
+        SpatialSearch {
+            vecs: SpatialStore::new(vecs),
+            bboxes: SpatialStore::new(vec![]),
+        }
+    }
+
+    fn new_bbox_store(bboxes: Vec<LabelPairBbox<N>>) -> Self {
+        SpatialSearch {
+            vecs: SpatialStore::new(vec![]),
+            bboxes: SpatialStore::new(bboxes),
+        }
+    }
+
+    fn new() -> Self {
+        SpatialSearch {
+            vecs: SpatialStore::new(vec![]),
+            bboxes: SpatialStore::new(vec![]),
+        }
+    }
+
+    fn search<'a>(
+        &'a self,
+        bbox: &'a Bbox<N>,
+        skip_labels: Option<&'a HashSet<String>>,
+    ) -> impl Iterator<Item = LabelPairVec<N>> + 'a {
+        //unknowns sit at the bottom of their dimension, reach down to them where they're accepted
+        let mut lo = bbox.min;
+        for (lo, accept) in lo.iter_mut().zip(bbox.accept_unknown) {
+            if accept {
+                *lo = UNKNOWN;
+            }
+        }
+
+        self.vecs
+            .candidates(&lo, &bbox.max)
+            .into_iter()
+            .filter(move |label_pair| {
+                if let Some(skip) = skip_labels {
+                    if skip.contains(&label_pair.label) {
+                        return false;
+                    }
+                }
+                bbox.contains(&label_pair.vec)
+            })
+            .cloned()
+    }
+
+    fn search_inverse<'a>(
+        &'a self,
+        location: &'a [i16; N],
+        skip_labels: Option<&'a HashSet<String>>,
+    ) -> impl Iterator<Item = LabelPairBbox<N>> + 'a {
+        //min in [i16::MIN, location], max in [location, i16::MAX], an unknown value
+        //says nothing about the range so its dimension isn't pruned on
+        let mut lo = [i16::MIN; N].to_vec();
+        lo.extend_from_slice(location);
+        let mut hi = location.to_vec();
+        hi.extend_from_slice(&[i16::MAX; N]);
+        for i in 0..N {
+            if location[i] == UNKNOWN {
+                hi[i] = i16::MAX;
+                lo[N + i] = i16::MIN;
+            }
+        }
+
+        self.bboxes
+            .candidates(&lo, &hi)
+            .into_iter()
+            .filter(move |label_pair| {
+                if let Some(skip) = skip_labels {
+                    if skip.contains(&label_pair.label) {
+                        return false;
+                    }
+                }
+                label_pair.bbox.contains(location)
+            })
+            .cloned()
+    }
+
+    fn contains_vec(&self, label: &String) -> bool {
+        self.vecs.contains(label)
+    }
+
+    fn contains_bbox(&self, label: &String) -> bool {
+        self.bboxes.contains(label)
+    }
+
+    fn add(&mut self, location: &[i16; N], label: &String) {
+        self.vecs.insert(LabelPairVec {
+            label: label.clone(),
+            vec: *location,
+        });
+    }
+
+    fn add_bbox(&mut self, bbox: &Bbox<N>, label: &String) {
+        self.bboxes.insert(LabelPairBbox {
+            label: label.clone(),
+            bbox: bbox.clone(),
+        });
+    }
+
+    fn remove(&mut self, label: &String) {
+        self.vecs.remove(label);
+    }
+
+    fn remove_bbox(&mut self, label: &String) {
+        self.bboxes.remove(label);
+    }
+}
@@ -49,6 +49,9 @@ impl<const N: usize> Hash for LabelPairBbox<N> {
     }
 }
 
+//the value of a dimension nobody filled in, it's never compared as a coordinate
+pub const UNKNOWN: i16 = i16::MIN;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Bbox<const N: usize> {
     #[serde(
@@ -61,12 +64,35 @@ pub struct Bbox<const N: usize> {
         deserialize_with = "array_helpers::deserialize"
     )]
     pub max: [i16; N],
+    //whether an `UNKNOWN` value in each dimension is inside the box
+    #[serde(
+        serialize_with = "array_helpers::serialize",
+        deserialize_with = "array_helpers::deserialize"
+    )]
+    pub accept_unknown: [bool; N],
+}
+
+impl<const N: usize> Bbox<N> {
+    /// Whether `location` lies in the box. Unknown values are decided by
+    /// `accept_unknown` alone, whatever the range of their dimension.
+    pub fn contains(&self, location: &[i16; N]) -> bool {
+        (0..N).all(|i| {
+            if location[i] == UNKNOWN {
+                self.accept_unknown[i]
+            } else {
+                self.min[i] <= location[i] && location[i] <= self.max[i]
+            }
+        })
+    }
 }
 
 impl<const N: usize> PartialEq for Bbox<N> {
     fn eq(&self, other: &Self) -> bool {
         for i in 0..N {
-            if self.min[i] != other.min[i] || self.max[i] != other.max[i] {
+            if self.min[i] != other.min[i]
+                || self.max[i] != other.max[i]
+                || self.accept_unknown[i] != other.accept_unknown[i]
+            {
                 return false;
             }
         }
@@ -80,6 +106,7 @@ impl<const N: usize> Hash for Bbox<N> {
         for i in 0..N {
             self.min[i].hash(state);
             self.max[i].hash(state);
+            self.accept_unknown[i].hash(state);
         }
     }
 }
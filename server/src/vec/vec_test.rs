@@ -6,6 +6,7 @@ fn run_test_suite() {
 
     use crate::vec::{
         search_linear::LinearSearch,
+        search_spatial::SpatialSearch,
         shared::{Bbox, LabelPairBbox, LabelPairVec},
     };
 
@@ -39,6 +40,7 @@ fn run_test_suite() {
         for i in 0..n {
             let mut min = [0; N];
             let mut max = [0; N];
+            let mut accept_unknown = [false; N];
             for elem in &mut min {
                 *elem = rng.gen::<i16>();
             }
@@ -59,12 +61,17 @@ fn run_test_suite() {
                 if ignored {
                     min[i] = i16::MIN;
                     max[i] = i16::MAX;
+                    accept_unknown[i] = true;
                 }
             }
 
             bboxes.push(LabelPairBbox {
                 label: i.to_string(),
-                bbox: Bbox { min, max },
+                bbox: Bbox {
+                    min,
+                    max,
+                    accept_unknown,
+                },
             });
         }
         bboxes
@@ -127,20 +134,131 @@ fn run_test_suite() {
         let bboxes30_this_cardinality = bboxes30.iter().take(*n).cloned().collect();
         let vecs100_this_cardinality = vecs100.iter().take(*n).cloned().collect();
         let bboxes100_this_cardinality = bboxes100.iter().take(*n).cloned().collect();
-        let (_, _) = run_search_tests::<10, LinearSearch<10>>(
+        let linear10 = run_search_tests::<10, LinearSearch<10>>(
             &vecs10_this_cardinality,
             &bboxes10_this_cardinality,
             "Linear",
         );
-        let (_, _) = run_search_tests::<30, LinearSearch<30>>(
+        let spatial10 = run_search_tests::<10, SpatialSearch<10>>(
+            &vecs10_this_cardinality,
+            &bboxes10_this_cardinality,
+            "Spatial",
+        );
+        assert_eq!(linear10, spatial10);
+        let linear30 = run_search_tests::<30, LinearSearch<30>>(
             &vecs30_this_cardinality,
             &bboxes30_this_cardinality,
             "Linear",
         );
-        let (_, _) = run_search_tests::<100, LinearSearch<100>>(
+        let spatial30 = run_search_tests::<30, SpatialSearch<30>>(
+            &vecs30_this_cardinality,
+            &bboxes30_this_cardinality,
+            "Spatial",
+        );
+        assert_eq!(linear30, spatial30);
+        let linear100 = run_search_tests::<100, LinearSearch<100>>(
             &vecs100_this_cardinality,
             &bboxes100_this_cardinality,
             "Linear",
         );
+        let spatial100 = run_search_tests::<100, SpatialSearch<100>>(
+            &vecs100_this_cardinality,
+            &bboxes100_this_cardinality,
+            "Spatial",
+        );
+        assert_eq!(linear100, spatial100);
+    }
+}
+
+#[test]
+fn run_update_test_suite() {
+    use rand::Rng;
+    use std::collections::HashSet;
+
+    use crate::vec::{
+        search_linear::LinearSearch,
+        search_spatial::SpatialSearch,
+        shared::{Bbox, VectorSearch, UNKNOWN},
+    };
+
+    const N: usize = 8;
+    const LABELS: usize = 5000;
+    const OPS: usize = 20000;
+
+    let mut rng = rand::thread_rng();
+    let mut linear = LinearSearch::<N>::new();
+    let mut spatial = SpatialSearch::<N>::new();
+
+    //small value range so searches actually hit something, with some values unknown
+    let random_vec = |rng: &mut rand::rngs::ThreadRng| {
+        let mut vec = [0i16; N];
+        for elem in &mut vec {
+            *elem = if rng.gen_bool(0.1) {
+                UNKNOWN
+            } else {
+                rng.gen_range(-8..8)
+            };
+        }
+        vec
+    };
+    let random_accept_unknown = |rng: &mut rand::rngs::ThreadRng| {
+        let mut accept_unknown = [false; N];
+        for elem in &mut accept_unknown {
+            *elem = rng.gen_bool(0.5);
+        }
+        accept_unknown
+    };
+
+    for op in 0..OPS {
+        let label = rng.gen_range(0..LABELS).to_string();
+        match rng.gen_range(0..4) {
+            0 => {
+                let vec = random_vec(&mut rng);
+                linear.add(&vec, &label);
+                spatial.add(&vec, &label);
+            }
+            1 => {
+                let a = random_vec(&mut rng);
+                let b = random_vec(&mut rng);
+                let mut bbox = Bbox {
+                    min: a,
+                    max: b,
+                    accept_unknown: random_accept_unknown(&mut rng),
+                };
+                for i in 0..N {
+                    if bbox.min[i] > bbox.max[i] {
+                        std::mem::swap(&mut bbox.min[i], &mut bbox.max[i]);
+                    }
+                }
+                linear.add_bbox(&bbox, &label);
+                spatial.add_bbox(&bbox, &label);
+            }
+            2 => {
+                linear.remove(&label);
+                spatial.remove(&label);
+            }
+            _ => {
+                linear.remove_bbox(&label);
+                spatial.remove_bbox(&label);
+            }
+        }
+        assert_eq!(linear.contains_vec(&label), spatial.contains_vec(&label));
+        assert_eq!(linear.contains_bbox(&label), spatial.contains_bbox(&label));
+
+        if op % 500 == 0 {
+            let query = Bbox {
+                min: [-4; N],
+                max: [4; N],
+                accept_unknown: random_accept_unknown(&mut rng),
+            };
+            let linear_result: HashSet<_> = linear.search(&query, None).collect();
+            let spatial_result: HashSet<_> = spatial.search(&query, None).collect();
+            assert_eq!(linear_result, spatial_result);
+
+            let location = random_vec(&mut rng);
+            let linear_result: HashSet<_> = linear.search_inverse(&location, None).collect();
+            let spatial_result: HashSet<_> = spatial.search_inverse(&location, None).collect();
+            assert_eq!(linear_result, spatial_result);
+        }
     }
 }